@@ -0,0 +1,36 @@
+//! Benchmarks for move generation.
+//! 
+//! Runs perft on the starting position and two tactically busy positions at
+//! a few depths. Throughput is reported in nodes (leaf positions) per
+//! second, so a slowdown in move generation shows up directly.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use chess_game::chess::{perft, Board, START_FEN};
+
+// (name, FEN, depths to run). Depths are chosen so each run takes well under
+// a second.
+const POSITIONS: [(&str, &str, &[u32]); 3] = [
+    ("start", START_FEN, &[2, 3, 4]),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[1, 2, 3]),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[2, 3, 4]),
+];
+
+fn bench_perft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("perft");
+    for (name, fen, depths) in POSITIONS {
+        let board = Board::from_fen(fen).expect("benchmark FENs are valid");
+        for &depth in depths {
+            // Count the nodes once up front so criterion can turn the timings
+            // into nodes per second.
+            group.throughput(Throughput::Elements(perft(&board, depth)));
+            group.bench_with_input(BenchmarkId::new(name, depth), &depth, |b, &depth| {
+                b.iter(|| perft(&board, depth))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_perft);
+criterion_main!(benches);
@@ -0,0 +1,16 @@
+//! Entry point of the UCI engine.
+//! 
+//! This file is responsible for:
+//! - Running the crate's engine as a UCI engine on stdin and stdout, so it
+//!   can be loaded into chess GUIs and tournament managers
+//!
+//! Nothing else may be written to stdout, so unlike the server this binary
+//! doesn't log.
+
+use std::io;
+
+use chess_game::engine::uci;
+
+fn main() {
+    uci::run(io::stdin().lock(), io::stdout());
+}
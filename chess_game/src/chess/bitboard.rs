@@ -0,0 +1,796 @@
+//! Bitboard representation of a position.
+//! 
+//! This file defines:
+//! - The BitBoards struct: one 64-bit set of squares per piece type and color
+//! - Conversion between BitBoards and the array-based Board
+//! - Move generation, make and unmake on BitBoards, as its BoardRepr
+//!   implementation
+//! - The Bitboard set-of-squares type and its operators
+//! - Precomputed attack tables for knights, kings and pawns
+//! - Magic bitboard attack lookups for rooks and bishops
+
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use std::sync::OnceLock;
+
+use crate::chess::board::{
+    Board, BoardRepr, CastlingRights, BISHOP_DIRECTIONS, KING_OFFSETS, KNIGHT_OFFSETS,
+    ROOK_DIRECTIONS,
+};
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::r#move::{Move, MoveFlag, UndoInfo, PROMOTION_KINDS};
+use crate::chess::square::Square;
+
+// A bitboard is a set of squares packed into a u64: bit 0 is a1, bit 1 is b1
+// and so on up to bit 63 for h8, the same order as Square's index. Setting
+// up, say, "every square a white knight stands on" as one number lets us
+// answer questions about many squares at once with a single AND or OR,
+// instead of looping over the board.
+//
+// For example, the white pawns in the starting position are the second rank:
+//
+//   0b11111111_00000000 = 0xFF00
+//     \______/ \______/
+//      rank 2   rank 1
+//
+// The u64 is wrapped in its own type so a set of squares can't be mixed up
+// with a count or a hash, and so the bit tricks live here behind readable
+// names. The `&`, `|`, `^` and `!` operators work as set intersection, union,
+// symmetric difference and complement.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(!0);
+    pub const FILE_A: Bitboard = Bitboard(0x0101_0101_0101_0101);
+    pub const FILE_H: Bitboard = Bitboard(0x8080_8080_8080_8080);
+    pub const RANK_1: Bitboard = Bitboard(0xFF);
+    pub const RANK_8: Bitboard = Bitboard(0xFF00_0000_0000_0000);
+
+    // The set containing just `square`.
+    pub const fn from_square(square: Square) -> Bitboard {
+        Bitboard(1 << square.index())
+    }
+
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square.index()) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // The number of squares in the set.
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    // The lowest-indexed square in the set (nearest a1).
+    pub fn lsb(self) -> Option<Square> {
+        Square::from_index(self.0.trailing_zeros() as usize)
+    }
+
+    // Removes the lowest-indexed square from the set and returns it.
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        // `b & (b - 1)` clears the lowest set bit.
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    // Iterates over the squares in the set, lowest index first.
+    pub fn iter_squares(self) -> impl Iterator<Item = Square> {
+        let mut remaining = self;
+        std::iter::from_fn(move || remaining.pop_lsb())
+    }
+
+    // Every square moved one step towards rank 8 (north) or rank 1 (south).
+    // Squares pushed off the top or bottom simply fall away.
+    pub fn north(self) -> Bitboard {
+        Bitboard(self.0 << 8)
+    }
+
+    pub fn south(self) -> Bitboard {
+        Bitboard(self.0 >> 8)
+    }
+
+    // Every square moved one file towards h (east) or a (west). A plain shift
+    // by one would carry the h-file round onto the a-file of the next rank,
+    // so those squares are masked off first.
+    pub fn east(self) -> Bitboard {
+        Bitboard((self & !Bitboard::FILE_H).0 << 1)
+    }
+
+    pub fn west(self) -> Bitboard {
+        Bitboard((self & !Bitboard::FILE_A).0 >> 1)
+    }
+}
+
+impl From<Square> for Bitboard {
+    fn from(square: Square) -> Self {
+        Bitboard::from_square(square)
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = Square>>(squares: I) -> Self {
+        squares.into_iter().fold(Bitboard::EMPTY, |set, square| set | square)
+    }
+}
+
+// Printed as the list of squares, e.g. `Bitboard[e2, f3, h3]`, which is much
+// easier to check than a 64-bit number.
+impl fmt::Debug for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.iter_squares().map(|sq| sq.to_string()).collect();
+        write!(f, "Bitboard[{}]", names.join(", "))
+    }
+}
+
+// Drawn as an 8x8 grid from White's side, "1" for squares in the set.
+impl fmt::Display for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rank in (0..8).rev() {
+            let row: Vec<&str> = (0..8)
+                .map(|file| {
+                    let square = Square::new(file, rank).expect("in range");
+                    if self.contains(square) { "1" } else { "." }
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+    fn bitand(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 & other.0)
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+    fn bitor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 | other.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+    fn bitxor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ other.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        self.0 &= other.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Bitboard) {
+        self.0 ^= other.0;
+    }
+}
+
+// A single square can stand in for the set holding just it, which keeps
+// expressions like `occupied & square` short.
+impl BitAnd<Square> for Bitboard {
+    type Output = Bitboard;
+    fn bitand(self, square: Square) -> Bitboard {
+        self & Bitboard::from_square(square)
+    }
+}
+
+impl BitOr<Square> for Bitboard {
+    type Output = Bitboard;
+    fn bitor(self, square: Square) -> Bitboard {
+        self | Bitboard::from_square(square)
+    }
+}
+
+impl BitOrAssign<Square> for Bitboard {
+    fn bitor_assign(&mut self, square: Square) {
+        *self |= Bitboard::from_square(square);
+    }
+}
+
+// Builds a table of the squares a "leaping" piece (one that jumps straight to
+// its target, like a knight or king) attacks from each square. This runs at
+// compile time, so at run time finding a knight's attacks is one array
+// lookup instead of eight bounds checks.
+const fn leaper_attacks(offsets: &[(i8, i8)]) -> [Bitboard; 64] {
+    // Operator traits can't be called in a const fn, so this one works on
+    // the raw u64 and wraps it at the end.
+    let mut table = [Bitboard::EMPTY; 64];
+    let mut index = 0;
+    while index < 64 {
+        let file = (index % 8) as i8;
+        let rank = (index / 8) as i8;
+        let mut bits = 0;
+        let mut i = 0;
+        while i < offsets.len() {
+            let (df, dr) = offsets[i];
+            let (f, r) = (file + df, rank + dr);
+            if f >= 0 && f < 8 && r >= 0 && r < 8 {
+                bits |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        table[index] = Bitboard(bits);
+        index += 1;
+    }
+    table
+}
+
+// Squares a knight attacks, indexed by the square it stands on.
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+
+// Squares a king attacks, indexed by the square it stands on.
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_attacks(&KING_OFFSETS);
+
+// Squares a pawn attacks: the two diagonals in front of it. Indexed by the
+// pawn's color and then its square. A pawn can't stand on its own back rank,
+// but the entries are filled in anyway, since looking backwards from a square
+// with the other color's table is how we find pawns attacking it.
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] =
+    [leaper_attacks(&[(-1, 1), (1, 1)]), leaper_attacks(&[(-1, -1), (1, -1)])];
+
+// Squares a sliding piece attacks by walking each direction until it leaves
+// the board or hits a piece. The blocking piece's square is included, since
+// it can be captured (or, if it's ours, is defended).
+fn ray_attacks(square: Square, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = Bitboard::EMPTY;
+    for &(df, dr) in directions {
+        let mut current = square.offset(df, dr);
+        while let Some(sq) = current {
+            attacks |= sq;
+            if occupied.contains(sq) {
+                break;
+            }
+            current = sq.offset(df, dr);
+        }
+    }
+    attacks
+}
+
+// Magic bitboards turn a sliding piece's attacks into a table lookup.
+//
+// Only the pieces on the squares a rook could slide over matter for its
+// attacks (the "mask"; the edge squares are left out, since a piece there
+// blocks nothing further). There are at most 2^12 arrangements of pieces on
+// those squares, so we can precompute the attacks for every one of them. The
+// trick is turning an arrangement into a table index quickly: multiplying the
+// masked occupancy by a carefully chosen "magic" number gathers the relevant
+// bits into the top of the product, and shifting those down gives an index
+// with no two arrangements (that have different attacks) colliding.
+//
+// Good magic numbers are found by trial and error: try random numbers with
+// few bits set until one maps every arrangement to a usable slot. That search
+// is slow, so it was run once and the results are listed below, one per
+// square in Square index order. Only the attack tables themselves are built
+// at run time, the first time a lookup is needed.
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    // Where this square's entries start in the shared attack table.
+    offset: usize,
+}
+
+struct MagicTables {
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    attacks: Vec<Bitboard>,
+}
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x2080_0020_8040_0010, 0x00C0_0020_0140_1000, 0x2100_1100_0840_2002, 0x0880_0800_8104_1000,
+    0x0200_0200_2004_1008, 0x2300_0400_0801_0012, 0x0C00_2830_0400_8201, 0x0180_0100_0040_7A80,
+    0x0168_8000_8040_0020, 0x0010_4000_4020_1000, 0x1001_0020_0100_1048, 0x1001_0024_0810_0100,
+    0x0801_0004_0801_0012, 0x4001_0002_0900_0400, 0x08A2_0004_C802_0001, 0x2002_8011_4500_2280,
+    0x0080_8600_2100_4200, 0x0010_00C0_0940_2002, 0x00B0_0020_0400_2800, 0x100A_8080_1002_0800,
+    0x8101_0100_0800_0410, 0x0244_0080_0200_0480, 0x0000_0400_1081_0208, 0x2000_0200_0044_8534,
+    0x4104_4004_8000_8033, 0x0000_8101_0020_4000, 0x0440_4309_0020_0010, 0x4600_2409_0010_0100,
+    0x0060_0800_8004_0080, 0x0001_0003_0008_0400, 0x0004_0844_0001_1002, 0x0023_0402_0000_8041,
+    0x0580_0500_4300_2080, 0x0400_8040_0280_2008, 0x0001_0020_0100_4010, 0x1000_2009_0100_1000,
+    0x4410_8008_0180_0C00, 0xA012_0038_0600_1004, 0x0020_1001_0400_8802, 0x0004_8084_0200_0041,
+    0x0010_4001_7089_8000, 0x0080_5000_2000_4004, 0x1040_4080_1202_0020, 0x8010_0400_0800_4040,
+    0x2001_0801_0011_0004, 0x0000_0200_0400_8080, 0x0021_0108_1004_0002, 0x0800_008C_4302_0024,
+    0x0000_8000_2100_5100, 0x0070_2010_4000_8080, 0x0000_D042_8200_6A00, 0x0010_0144_0008_0240,
+    0x0001_0801_1005_0100, 0x0012_0008_1024_0600, 0x0402_0008_0104_0200, 0x0281_0010_8A00_4100,
+    0x0050_8003_0010_2045, 0x8208_2100_4012_0882, 0x8010_6001_0118_3441, 0x020B_0009_1000_6045,
+    0x0241_0010_0248_0005, 0x0081_0004_0088_0241, 0x0000_0090_0802_4124, 0x0048_1229_8041_0402,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0848_0208_2204_0013, 0x8010_A400_8582_1200, 0x0008_0084_3084_0822, 0x0808_0481_0804_0000,
+    0x1304_0421_0000_8104, 0x5001_0120_1020_4023, 0x8104_8801_B820_0420, 0x200A_0080_8401_2000,
+    0x0040_1020_0104_2084, 0x840A_5050_4242_8020, 0x0000_7001_0220_2920, 0x4410_1C0C_1080_0002,
+    0x0040_0404_2200_0000, 0x0180_0208_0209_0202, 0x4020_0208_1104_1202, 0x0001_0430_8C04_2000,
+    0x4140_6610_0242_4400, 0x0028_0120_0801_0460, 0x0188_0621_0200_2A00, 0x0014_0048_4010_2008,
+    0x0105_0002_9040_0002, 0x8001_0222_0041_0400, 0x104A_0419_1801_3446, 0x008A_0000_8200_8238,
+    0x04A0_0600_0810_0430, 0x0008_2200_0882_0801, 0x2508_0412_0800_5010, 0x4008_0802_0020_2020,
+    0x2441_0010_1300_4000, 0x0030_0080_6040_7000, 0x4008_1080_0042_0800, 0x0012_0210_5029_0100,
+    0x0210_0804_8220_0500, 0xCC01_1120_4810_0480, 0x0020_4028_0650_0440, 0x0004_8E00_8058_0080,
+    0x0040_1020_2002_0080, 0x0028_0104_4008_0807, 0x4601_0411_0800_8800, 0x8040_810E_0410_4200,
+    0x9012_1011_0400_088A, 0xA003_0802_1208_1050, 0x00C1_0040_4840_1004, 0x9000_00A0_1440_0800,
+    0x0008_0210_4040_5401, 0x4020_0082_0600_2090, 0x0004_1904_2403_0100, 0x0424_008A_0202_6250,
+    0x8004_0882_5090_0040, 0x1C00_4300_88A0_4200, 0x0001_0200_9404_0001, 0x8040_2100_2088_0061,
+    0x2010_0404_5044_2032, 0x0800_8408_5004_4001, 0x0004_0408_0214_0004, 0x0004_080A_0422_2020,
+    0x8088_8021_1002_2000, 0x1081_A104_1611_4400, 0x0205_010A_2406_0820, 0x0000_0007_2041_1080,
+    0x1008_0002_0843_0400, 0x580C_0260_2881_0840, 0x8020_2044_1020_A110, 0x12C0_0224_0102_0018,
+];
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).0.wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+// The squares whose occupancy can change a slider's attacks: each ray
+// without its final square on the edge of the board.
+fn relevant_mask(square: Square, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = Bitboard::EMPTY;
+    for &(df, dr) in directions {
+        let mut current = square.offset(df, dr);
+        while let Some(sq) = current {
+            current = sq.offset(df, dr);
+            if current.is_some() {
+                mask |= sq;
+            }
+        }
+    }
+    mask
+}
+
+// Fills in one square's part of the attack table, using a ray scan for every
+// arrangement of pieces on its mask.
+fn build_magic(square: Square, directions: &[(i8, i8)], magic: u64, attacks: &mut Vec<Bitboard>) -> Magic {
+    let mask = relevant_mask(square, directions);
+    let bits = mask.count();
+    let entry = Magic { mask, magic, shift: 64 - bits, offset: attacks.len() };
+    attacks.resize(attacks.len() + (1 << bits), Bitboard::EMPTY);
+
+    // Walk through every subset of the mask with the "carry-rippler" trick.
+    let mut subset = Bitboard::EMPTY;
+    loop {
+        let slot = entry.index(subset);
+        let expected = ray_attacks(square, subset, directions);
+        // Two arrangements may share a slot only if their attacks agree; a
+        // mistyped magic would break that.
+        assert!(attacks[slot].is_empty() || attacks[slot] == expected, "bad magic for {}", square);
+        attacks[slot] = expected;
+        subset = Bitboard(subset.0.wrapping_sub(mask.0)) & mask;
+        if subset.is_empty() {
+            break;
+        }
+    }
+    entry
+}
+
+fn magic_tables() -> &'static MagicTables {
+    static TABLES: OnceLock<MagicTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut attacks = Vec::new();
+        let rook = Square::all()
+            .map(|square| build_magic(square, &ROOK_DIRECTIONS, ROOK_MAGICS[square.index()], &mut attacks))
+            .collect();
+        let bishop = Square::all()
+            .map(|square| {
+                build_magic(square, &BISHOP_DIRECTIONS, BISHOP_MAGICS[square.index()], &mut attacks)
+            })
+            .collect();
+        MagicTables { rook, bishop, attacks }
+    })
+}
+
+// Squares a rook on `square` attacks, given every occupied square.
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = magic_tables();
+    tables.attacks[tables.rook[square.index()].index(occupied)]
+}
+
+// Squares a bishop on `square` attacks, given every occupied square.
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = magic_tables();
+    tables.attacks[tables.bishop[square.index()].index(occupied)]
+}
+
+const KINDS: [PieceKind; 6] = [
+    PieceKind::Pawn,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::Queen,
+    PieceKind::King,
+];
+
+const COLORS: [PieceColor; 2] = [PieceColor::White, PieceColor::Black];
+
+// The same position as a Board, stored as bitboards. The piece sets are
+// indexed by color and then kind, in the order of the enums' numeric values.
+// Alongside them we keep each side's occupancy (every square that side has a
+// piece on) since nearly every attack calculation needs it, and the rest of
+// the position state so converting to and from Board loses nothing.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BitBoards {
+    pieces: [[Bitboard; 6]; 2],
+    occupancy: [Bitboard; 2],
+    side_to_move: PieceColor,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+impl BitBoards {
+    // Every square holding a piece of this color and kind.
+    pub fn pieces(&self, color: PieceColor, kind: PieceKind) -> Bitboard {
+        self.pieces[color.index()][kind as usize]
+    }
+
+    // Every square holding a piece of this color.
+    pub fn occupancy(&self, color: PieceColor) -> Bitboard {
+        self.occupancy[color.index()]
+    }
+
+    // Every square holding any piece.
+    pub fn occupied(&self) -> Bitboard {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        for color in COLORS {
+            if !self.occupancy(color).contains(square) {
+                continue;
+            }
+            for kind in KINDS {
+                if self.pieces(color, kind).contains(square) {
+                    return Some(Piece::new(kind, color));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    // The squares attacked by the piece on `square`, or an empty set if the
+    // square is empty. Everything except pawns is a table lookup.
+    pub fn attacks_from(&self, square: Square) -> Bitboard {
+        let Some(piece) = self.piece_at(square) else {
+            return Bitboard::EMPTY;
+        };
+        let occupied = self.occupied();
+        match piece.kind() {
+            PieceKind::Pawn => PAWN_ATTACKS[piece.color().index()][square.index()],
+            PieceKind::Knight => KNIGHT_ATTACKS[square.index()],
+            PieceKind::King => KING_ATTACKS[square.index()],
+            PieceKind::Bishop => bishop_attacks(square, occupied),
+            PieceKind::Rook => rook_attacks(square, occupied),
+            PieceKind::Queen => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+        }
+    }
+
+    // Returns true if any piece of color `by` attacks `square`. As on Board
+    // we look outwards from the square, but here each piece type is a
+    // single AND of its attack set from the square with where those pieces
+    // stand.
+    pub fn is_square_attacked(&self, square: Square, by: PieceColor) -> bool {
+        let occupied = self.occupied();
+        let queens = self.pieces(by, PieceKind::Queen);
+        let attackers = (PAWN_ATTACKS[by.opposite().index()][square.index()] & self.pieces(by, PieceKind::Pawn))
+            | (KNIGHT_ATTACKS[square.index()] & self.pieces(by, PieceKind::Knight))
+            | (KING_ATTACKS[square.index()] & self.pieces(by, PieceKind::King))
+            | (bishop_attacks(square, occupied) & (self.pieces(by, PieceKind::Bishop) | queens))
+            | (rook_attacks(square, occupied) & (self.pieces(by, PieceKind::Rook) | queens));
+        !attackers.is_empty()
+    }
+
+    // True if the king of `color` is attacked, whoever's turn it is.
+    pub fn king_in_check(&self, color: PieceColor) -> bool {
+        self.pieces(color, PieceKind::King)
+            .lsb()
+            .is_some_and(|king| self.is_square_attacked(king, color.opposite()))
+    }
+
+    // Every legal move for the side to move: the pseudo-legal moves, less
+    // those that leave our own king in check, found by playing each one on
+    // a scratch copy.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let us = self.side_to_move;
+        let mut scratch = self.clone();
+        let mut moves = self.generate_pseudo_legal();
+        moves.retain(|&mv| {
+            let undo = scratch.make_move(mv);
+            let in_check = scratch.king_in_check(us);
+            scratch.unmake_move(mv, undo);
+            !in_check
+        });
+        moves
+    }
+
+    // The same moves as Board::generate_pseudo_legal, with the targets of
+    // every piece but pawns taken from the attack tables.
+    pub fn generate_pseudo_legal(&self) -> Vec<Move> {
+        let us = self.side_to_move;
+        let own = self.occupancy(us);
+        let occupied = self.occupied();
+        let mut moves = Vec::with_capacity(64);
+        for from in own.iter_squares() {
+            let Some(piece) = self.piece_at(from) else { continue };
+            let targets = match piece.kind() {
+                PieceKind::Pawn => {
+                    self.generate_pawn_moves(from, us, &mut moves);
+                    continue;
+                }
+                PieceKind::Knight => KNIGHT_ATTACKS[from.index()],
+                PieceKind::Bishop => bishop_attacks(from, occupied),
+                PieceKind::Rook => rook_attacks(from, occupied),
+                PieceKind::Queen => bishop_attacks(from, occupied) | rook_attacks(from, occupied),
+                PieceKind::King => KING_ATTACKS[from.index()],
+            };
+            for to in (targets & !own).iter_squares() {
+                moves.push(Move::new(from, to));
+            }
+            if piece.kind() == PieceKind::King {
+                self.generate_castling(from, us, &mut moves);
+            }
+        }
+        moves
+    }
+
+    fn generate_pawn_moves(&self, from: Square, us: PieceColor, moves: &mut Vec<Move>) {
+        let (dir, start_rank, promotion_rank) = match us {
+            PieceColor::White => (1, 1, 7),
+            PieceColor::Black => (-1, 6, 0),
+        };
+        let empty = !self.occupied();
+
+        let mut push = |to: Square, flag: MoveFlag| {
+            if to.rank() == promotion_rank {
+                for kind in PROMOTION_KINDS {
+                    moves.push(Move::with_promotion(from, to, kind));
+                }
+            } else {
+                moves.push(Move::with_flag(from, to, flag));
+            }
+        };
+
+        if let Some(one) = from.offset(0, dir).filter(|&sq| empty.contains(sq)) {
+            push(one, MoveFlag::Normal);
+            if from.rank() == start_rank {
+                if let Some(two) = one.offset(0, dir).filter(|&sq| empty.contains(sq)) {
+                    push(two, MoveFlag::DoublePawnPush);
+                }
+            }
+        }
+
+        let captures = PAWN_ATTACKS[us.index()][from.index()];
+        for to in (captures & self.occupancy(us.opposite())).iter_squares() {
+            push(to, MoveFlag::Normal);
+        }
+        if let Some(to) = self.en_passant.filter(|&sq| captures.contains(sq)) {
+            push(to, MoveFlag::EnPassant);
+        }
+    }
+
+    // Castling under the same conditions as on Board: the right kept, the
+    // rook at home, the squares between empty, and the king neither in check
+    // nor passing through an attacked square.
+    fn generate_castling(&self, from: Square, us: PieceColor, moves: &mut Vec<Move>) {
+        let rank = match us {
+            PieceColor::White => 0,
+            PieceColor::Black => 7,
+        };
+        if from != Square::new(4, rank).unwrap() {
+            return;
+        }
+        let on_rank = |files: &[u8]| files.iter().map(|&file| Square::new(file, rank).unwrap()).collect::<Bitboard>();
+        let empty = |files: &[u8]| (on_rank(files) & self.occupied()).is_empty();
+        let safe = |files: &[u8]| on_rank(files).iter_squares().all(|sq| !self.is_square_attacked(sq, us.opposite()));
+        let rooks = self.pieces(us, PieceKind::Rook);
+
+        if self.castling.contains(CastlingRights::kingside(us))
+            && rooks.contains(Square::new(7, rank).unwrap())
+            && empty(&[5, 6])
+            && safe(&[4, 5])
+        {
+            moves.push(Move::with_flag(from, Square::new(6, rank).unwrap(), MoveFlag::CastleKingside));
+        }
+        if self.castling.contains(CastlingRights::queenside(us))
+            && rooks.contains(Square::new(0, rank).unwrap())
+            && empty(&[1, 2, 3])
+            && safe(&[4, 3])
+        {
+            moves.push(Move::with_flag(from, Square::new(2, rank).unwrap(), MoveFlag::CastleQueenside));
+        }
+    }
+
+    // Adds `piece` to an empty square, or takes it off the square it is on:
+    // either way one XOR in its own set and one in its side's occupancy.
+    fn toggle(&mut self, square: Square, piece: Piece) {
+        let bit = Bitboard::from_square(square);
+        self.pieces[piece.color().index()][piece.kind() as usize] ^= bit;
+        self.occupancy[piece.color().index()] ^= bit;
+    }
+
+    fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.piece_at(square)?;
+        self.toggle(square, piece);
+        Some(piece)
+    }
+
+    // Plays a move without checking that it is legal, exactly as
+    // Board::make_move does, returning what `unmake_move` needs.
+    pub fn make_move(&mut self, mv: Move) -> UndoInfo {
+        let us = self.side_to_move;
+        let piece = self
+            .remove_piece(mv.from())
+            .expect("make_move called with no piece on the from-square");
+        let captured = self.remove_piece(Board::capture_square(&mv));
+        let undo = UndoInfo {
+            captured,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+        };
+
+        let placed = mv.promotion().map_or(piece, |kind| Piece::new(kind, us));
+        self.toggle(mv.to(), placed);
+        if let Some((rook_from, rook_to)) = Board::castling_rook_squares(&mv) {
+            if let Some(rook) = self.remove_piece(rook_from) {
+                self.toggle(rook_to, rook);
+            }
+        }
+
+        self.castling = Board::castling_rights_after(self.castling, piece, &mv);
+        self.en_passant = match mv.flag() {
+            MoveFlag::DoublePawnPush => Square::new(mv.from().file(), (mv.from().rank() + mv.to().rank()) / 2),
+            _ => None,
+        };
+        if piece.kind() == PieceKind::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if us == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = us.opposite();
+        undo
+    }
+
+    // Takes back a move previously played with `make_move`.
+    pub fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        let us = self.side_to_move.opposite();
+        self.side_to_move = us;
+        if us == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+
+        let moved = self
+            .remove_piece(mv.to())
+            .expect("unmake_move called with no piece on the to-square");
+        let original = match mv.promotion() {
+            Some(_) => Piece::new(PieceKind::Pawn, us),
+            None => moved,
+        };
+        self.toggle(mv.from(), original);
+        if let Some(captured) = undo.captured {
+            self.toggle(Board::capture_square(&mv), captured);
+        }
+        if let Some((rook_from, rook_to)) = Board::castling_rook_squares(&mv) {
+            if let Some(rook) = self.remove_piece(rook_to) {
+                self.toggle(rook_from, rook);
+            }
+        }
+    }
+}
+
+// Everything generic code asks of a position is answered from the bitboards
+// themselves; only `to_board` builds a Board.
+impl BoardRepr for BitBoards {
+    type Undo = UndoInfo;
+
+    fn from_board(board: &Board) -> Self {
+        BitBoards::from(board)
+    }
+
+    fn to_board(&self) -> Board {
+        Board::from(self)
+    }
+
+    fn piece_at(&self, square: Square) -> Option<Piece> {
+        BitBoards::piece_at(self, square)
+    }
+
+    fn side_to_move(&self) -> PieceColor {
+        BitBoards::side_to_move(self)
+    }
+
+    fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    fn legal_moves(&self) -> Vec<Move> {
+        BitBoards::legal_moves(self)
+    }
+
+    fn king_in_check(&self, color: PieceColor) -> bool {
+        BitBoards::king_in_check(self, color)
+    }
+
+    fn make_move(&mut self, mv: Move) -> UndoInfo {
+        BitBoards::make_move(self, mv)
+    }
+
+    fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        BitBoards::unmake_move(self, mv, undo)
+    }
+}
+
+impl From<&Board> for BitBoards {
+    fn from(board: &Board) -> Self {
+        let mut pieces = [[Bitboard::EMPTY; 6]; 2];
+        let mut occupancy = [Bitboard::EMPTY; 2];
+        for square in Square::all() {
+            if let Some(piece) = board.piece_at(square) {
+                let color = piece.color().index();
+                pieces[color][piece.kind() as usize] |= square;
+                occupancy[color] |= square;
+            }
+        }
+        BitBoards {
+            pieces,
+            occupancy,
+            side_to_move: board.side_to_move(),
+            castling: board.castling_rights(),
+            en_passant: board.en_passant(),
+            halfmove_clock: board.halfmove_clock(),
+            fullmove_number: board.fullmove_number(),
+        }
+    }
+}
+
+impl From<&BitBoards> for Board {
+    fn from(bitboards: &BitBoards) -> Self {
+        let mut board = Board::empty();
+        for color in COLORS {
+            for kind in KINDS {
+                for square in bitboards.pieces(color, kind).iter_squares() {
+                    board.put_piece(square, Piece::new(kind, color));
+                }
+            }
+        }
+        board.set_side_to_move(bitboards.side_to_move);
+        board.set_castling_rights(bitboards.castling);
+        board.set_en_passant(bitboards.en_passant);
+        board.set_halfmove_clock(bitboards.halfmove_clock);
+        board.set_fullmove_number(bitboards.fullmove_number);
+        board
+    }
+}
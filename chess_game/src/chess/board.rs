@@ -5,11 +5,745 @@
 //! - Methods for initializing the board with pieces
 //! - Functions for querying and modifying board state
 //! - Helper methods for move validation
+//! - Attack lookups for knights, kings and pawns
+//! - Pin and discovered-check queries
+//! - Draw detection: insufficient material and dead positions
+//! - An ASCII diagram of the board via Display
+//! - Serialization as FEN, or packed fields in binary formats
+//! - The BoardRepr trait that lets code run on other board representations
 
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::chess::bitboard::{
+    bishop_attacks, rook_attacks, Bitboard, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS,
+};
+use crate::chess::fen::START_FEN;
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::psqt::{piece_value, square_bonus};
+use crate::chess::r#move::{Move, UndoInfo};
+use crate::chess::square::Square;
+use crate::chess::zobrist::{castling_key, en_passant_key, piece_key, side_key};
+
+// Castling rights are four independent yes/no flags, so like Piece we pack
+// them into the low bits of a single byte:
+//
+// Bit position: 3 2 1 0
+//               | | | |
+//               | | | +-- White may castle kingside  (K)
+//               | | +---- White may castle queenside (Q)
+//               | +------ Black may castle kingside  (k)
+//               +-------- Black may castle queenside (q)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const WHITE_KINGSIDE: CastlingRights = CastlingRights(0b0001);
+    pub const WHITE_QUEENSIDE: CastlingRights = CastlingRights(0b0010);
+    pub const BLACK_KINGSIDE: CastlingRights = CastlingRights(0b0100);
+    pub const BLACK_QUEENSIDE: CastlingRights = CastlingRights(0b1000);
+    pub const ALL: CastlingRights = CastlingRights(0b1111);
+
+    // True if every flag set in `other` is also set in `self`.
+    pub fn contains(self, other: CastlingRights) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: CastlingRights) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: CastlingRights) {
+        self.0 &= !other.0;
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // The raw flags, 0 to 15, e.g. for indexing a table by castling rights.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    // The inverse of `bits`. Returns None if any bit above the four flags
+    // is set.
+    pub fn from_bits(bits: u8) -> Option<CastlingRights> {
+        (bits <= CastlingRights::ALL.0).then_some(CastlingRights(bits))
+    }
+
+    pub fn kingside(color: PieceColor) -> CastlingRights {
+        match color {
+            PieceColor::White => CastlingRights::WHITE_KINGSIDE,
+            PieceColor::Black => CastlingRights::BLACK_KINGSIDE,
+        }
+    }
+
+    pub fn queenside(color: PieceColor) -> CastlingRights {
+        match color {
+            PieceColor::White => CastlingRights::WHITE_QUEENSIDE,
+            PieceColor::Black => CastlingRights::BLACK_QUEENSIDE,
+        }
+    }
+
+    // Both castling flags belonging to one side.
+    pub fn both(color: PieceColor) -> CastlingRights {
+        CastlingRights(CastlingRights::kingside(color).0 | CastlingRights::queenside(color).0)
+    }
+}
+
+// Offsets (file, rank) a knight can jump by.
+pub(crate) const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+];
+
+// Offsets (file, rank) to the eight neighbouring squares of a king.
+pub(crate) const KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1),
+];
+
+// Directions rooks (and queens) slide along.
+pub(crate) const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+// Directions bishops (and queens) slide along.
+pub(crate) const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+// Squares a knight on `square` attacks. These three are lookups in tables
+// built at compile time, so move generation and check detection don't redo
+// the coordinate arithmetic (and its bounds checks) every time.
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square.index()]
+}
+
+// Squares a king on `square` attacks.
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square.index()]
+}
+
+// Squares a pawn of `color` on `square` attacks.
+pub fn pawn_attacks(color: PieceColor, square: Square) -> Bitboard {
+    PAWN_ATTACKS[color.index()][square.index()]
+}
+
+// The board is a simple "mailbox": one optional piece per square, indexed by
+// Square. Alongside the pieces we keep the rest of the position state that
+// FEN describes, so a Board on its own is a complete position.
+//
+// The search asks for the Zobrist hash and the evaluation at every node, and
+// working them out by looking at all 64 squares each time was most of its
+// cost. So the board also keeps them as running totals. Every change to the
+// position goes through put_piece, remove_piece or one of the setters, and
+// each of those adjusts the totals for just what it changed; make_move and
+// unmake_move get incremental updates for free by only using those.
+#[derive(Clone, PartialEq, Eq)]
 pub struct Board {
-    // TODO: Implement board representation
+    squares: [Option<Piece>; 64],
+    side_to_move: PieceColor,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    // The Zobrist hash of everything above except the move counters.
+    hash: u64,
+    // Per color, indexed by PieceColor::index: the total value of its pieces,
+    // and the sum of their piece-square bonuses.
+    material: [i32; 2],
+    positional: [i32; 2],
 }
 
 impl Board {
-    // TODO: Implement board methods
+    // Creates a board set up in the standard starting position.
+    pub fn new() -> Self {
+        Board::from_fen(START_FEN).expect("the start position FEN is valid")
+    }
+
+    // Creates a board with no pieces, White to move and no castling rights.
+    pub fn empty() -> Self {
+        Board {
+            squares: [None; 64],
+            side_to_move: PieceColor::White,
+            castling: CastlingRights::NONE,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            // No pieces, White to move and no en passant square add nothing;
+            // "no castling rights" has a key like any other combination.
+            hash: castling_key(CastlingRights::NONE),
+            material: [0; 2],
+            positional: [0; 2],
+        }
+    }
+
+    // Every piece on the board with its square, in square order. For a
+    // sparse endgame this is far smaller to send than all 64 squares.
+    pub fn piece_list(&self) -> Vec<(Square, Piece)> {
+        Square::all()
+            .filter_map(|square| self.piece_at(square).map(|piece| (square, piece)))
+            .collect()
+    }
+
+    // Builds a board holding exactly the listed pieces. Only the pieces are
+    // described, so the rest of the state is as for `Board::empty`: White to
+    // move, no castling rights and no en passant square.
+    pub fn from_piece_list(pieces: impl IntoIterator<Item = (Square, Piece)>) -> Board {
+        let mut board = Board::empty();
+        for (square, piece) in pieces {
+            board.put_piece(square, piece);
+        }
+        board
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.squares[square.index()]
+    }
+
+    // Places a piece on a square, replacing whatever was there.
+    pub fn put_piece(&mut self, square: Square, piece: Piece) {
+        self.remove_piece(square);
+        self.squares[square.index()] = Some(piece);
+        self.hash ^= piece_key(piece, square);
+        self.material[piece.color().index()] += piece_value(piece.kind());
+        self.positional[piece.color().index()] += square_bonus(piece, square);
+    }
+
+    // Empties a square, returning the piece that was on it (if any).
+    pub fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.squares[square.index()].take()?;
+        self.hash ^= piece_key(piece, square);
+        self.material[piece.color().index()] -= piece_value(piece.kind());
+        self.positional[piece.color().index()] -= square_bonus(piece, square);
+        Some(piece)
+    }
+
+    // The Zobrist hash of the position: pieces, side to move, castling rights
+    // and en passant square. The move counters are left out, since they don't
+    // change which moves are possible. See chess::zobrist for the keys.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    // The total value of `color`'s pieces in centipawns, kings excluded.
+    pub fn material(&self, color: PieceColor) -> i32 {
+        self.material[color.index()]
+    }
+
+    // The sum of the piece-square bonuses of `color`'s pieces.
+    pub fn positional_score(&self, color: PieceColor) -> i32 {
+        self.positional[color.index()]
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    // The square a pawn may capture onto en passant, if the last move was a
+    // double pawn push.
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    // Number of half-moves since the last pawn move or capture.
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    // Starts at 1 and goes up after every Black move.
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn set_side_to_move(&mut self, color: PieceColor) {
+        if color != self.side_to_move {
+            self.hash ^= side_key();
+        }
+        self.side_to_move = color;
+    }
+
+    pub fn set_castling_rights(&mut self, rights: CastlingRights) {
+        self.hash ^= castling_key(self.castling) ^ castling_key(rights);
+        self.castling = rights;
+    }
+
+    pub fn set_en_passant(&mut self, square: Option<Square>) {
+        if let Some(old) = self.en_passant {
+            self.hash ^= en_passant_key(old);
+        }
+        if let Some(new) = square {
+            self.hash ^= en_passant_key(new);
+        }
+        self.en_passant = square;
+    }
+
+    pub fn set_halfmove_clock(&mut self, clock: u32) {
+        self.halfmove_clock = clock;
+    }
+
+    pub fn set_fullmove_number(&mut self, number: u32) {
+        self.fullmove_number = number;
+    }
+
+    // Draws the board as text, one line per rank from the 8th down to the
+    // 1st, using FEN letters for pieces (uppercase for White) and '.' for
+    // empty squares:
+    //
+    //   r n b q k b n r
+    //   p p p p p p p p
+    //   . . . . . . . .
+    //   ...
+    pub fn to_ascii(&self) -> String {
+        let mut lines = Vec::with_capacity(8);
+        for rank in (0..8).rev() {
+            let row: Vec<String> = (0..8)
+                .map(|file| {
+                    let square = Square::new(file, rank).unwrap();
+                    self.piece_at(square).map_or('.', Piece::to_fen_char).to_string()
+                })
+                .collect();
+            lines.push(row.join(" "));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    // Returns the board reflected left to right: the a-file swaps with the
+    // h-file, b with g, and so on. Colors and the side to move stay the same,
+    // so a symmetric evaluation should score both boards identically.
+    //
+    // Castling rights are dropped, since after the flip the kings and rooks
+    // are no longer on the squares castling starts from.
+    pub fn flip_horizontal(&self) -> Board {
+        let mirror = |square: Square| Square::new(7 - square.file(), square.rank()).unwrap();
+        let mut flipped = Board::from_piece_list(
+            self.piece_list().into_iter().map(|(square, piece)| (mirror(square), piece)),
+        );
+        flipped.set_side_to_move(self.side_to_move);
+        flipped.set_en_passant(self.en_passant.map(mirror));
+        flipped.set_halfmove_clock(self.halfmove_clock);
+        flipped.set_fullmove_number(self.fullmove_number);
+        flipped
+    }
+
+    // Every square holding a piece of the given kind, of either color, in
+    // square order (a1, b1, ... h8). Handy for evaluation terms like the
+    // bishop pair or rooks on open files.
+    pub fn squares_with(&self, kind: PieceKind) -> Vec<(Square, PieceColor)> {
+        Square::all()
+            .filter_map(|square| {
+                self.piece_at(square)
+                    .filter(|piece| piece.kind() == kind)
+                    .map(|piece| (square, piece.color()))
+            })
+            .collect()
+    }
+
+    // Locates the king of the given color. Only a broken position has no king,
+    // so callers generally treat None as "nothing to protect".
+    pub fn find_king(&self, color: PieceColor) -> Option<Square> {
+        Square::all().find(|&square| {
+            self.piece_at(square)
+                .is_some_and(|piece| piece.kind() == PieceKind::King && piece.color() == color)
+        })
+    }
+
+    // True if the king of `color` is attacked, whoever's turn it is. A board
+    // without that king is never in check.
+    pub fn king_in_check(&self, color: PieceColor) -> bool {
+        self.find_king(color)
+            .is_some_and(|king| self.is_square_attacked(king, color.opposite()))
+    }
+
+    // True if neither side can possibly checkmate: only kings are left, or
+    // kings and a single bishop or knight, or kings and any number of bishops
+    // (for either side) that all stand on squares of the same color. Bishops
+    // on one color can never cover the squares of the other, so the king
+    // always has somewhere to go.
+    pub fn insufficient_material(&self) -> bool {
+        let others: Vec<(Square, PieceKind)> = self
+            .piece_list()
+            .into_iter()
+            .map(|(square, piece)| (square, piece.kind()))
+            .filter(|&(_, kind)| kind != PieceKind::King)
+            .collect();
+        let square_color = |square: Square| (square.file() + square.rank()) % 2;
+
+        match others.as_slice() {
+            [] | [(_, PieceKind::Knight)] => true,
+            [(first, _), ..] => others
+                .iter()
+                .all(|&(square, kind)| kind == PieceKind::Bishop && square_color(square) == square_color(*first)),
+        }
+    }
+
+    // True if no sequence of legal moves can end in checkmate, even though
+    // there's more than bare material left. This is a deeper question than
+    // `insufficient_material`, which only counts pieces.
+    //
+    // Proving that in general is hard, so this only recognises the classic
+    // blocked-pawns case: nothing but kings and pawns, every pawn stuck
+    // behind an enemy pawn with nothing to capture, and neither king able to
+    // get next to an enemy pawn. Then the pawns can never move again and
+    // kings alone can't give check. Anything it doesn't recognise counts as
+    // alive, so a live game is never cut short.
+    pub fn is_dead_position(&self) -> bool {
+        let pieces = self.piece_list();
+        if pieces.iter().any(|(_, piece)| !matches!(piece.kind(), PieceKind::Pawn | PieceKind::King)) {
+            return false;
+        }
+        let pawns: Vec<(Square, PieceColor)> = pieces
+            .iter()
+            .filter(|(_, piece)| piece.kind() == PieceKind::Pawn)
+            .map(|&(square, piece)| (square, piece.color()))
+            .collect();
+        if pawns.is_empty() {
+            return false;
+        }
+
+        // Every square holding a pawn, and for each color, its pawns.
+        let mut all_pawns = Bitboard::EMPTY;
+        let mut pawns_of = [Bitboard::EMPTY; 2];
+        for &(square, color) in &pawns {
+            all_pawns |= square;
+            pawns_of[color.index()] |= square;
+        }
+
+        for &(square, color) in &pawns {
+            let enemy_pawns = pawns_of[color.opposite().index()];
+            let ahead = square.offset(0, if color == PieceColor::White { 1 } else { -1 });
+            let blocked = ahead.is_some_and(|ahead| enemy_pawns.contains(ahead));
+            let can_capture = !(pawn_attacks(color, square) & enemy_pawns).is_empty();
+            if !blocked || can_capture {
+                return false;
+            }
+        }
+
+        // With the pawns frozen, see where each king can ever walk: any
+        // square not holding a pawn or covered by an enemy pawn. The other
+        // king is ignored, which only makes the area larger.
+        [PieceColor::White, PieceColor::Black].into_iter().all(|color| {
+            let Some(king) = self.find_king(color) else {
+                return true;
+            };
+            let enemy_pawns = pawns_of[color.opposite().index()];
+            let covered = enemy_pawns
+                .iter_squares()
+                .fold(Bitboard::EMPTY, |covered, pawn| covered | pawn_attacks(color.opposite(), pawn));
+
+            let mut reachable = vec![king];
+            let mut seen = Bitboard::from_square(king);
+            while let Some(square) = reachable.pop() {
+                if !(king_attacks(square) & enemy_pawns).is_empty() {
+                    return false;
+                }
+                for next in (king_attacks(square) & !seen & !all_pawns & !covered).iter_squares() {
+                    seen |= next;
+                    reachable.push(next);
+                }
+            }
+            true
+        })
+    }
+
+    // Pieces of `color` that can't leave the line between their own king and
+    // an enemy rook, bishop or queen without exposing the king.
+    pub fn pinned_pieces(&self, color: PieceColor) -> Bitboard {
+        self.find_king(color)
+            .map_or(Bitboard::EMPTY, |king| self.line_blockers(king, color, color.opposite()))
+    }
+
+    // Pieces of `color` standing between one of its own rooks, bishops or
+    // queens and the enemy king: moving one off the line gives a discovered
+    // check.
+    pub fn discovered_check_candidates(&self, color: PieceColor) -> Bitboard {
+        self.find_king(color.opposite())
+            .map_or(Bitboard::EMPTY, |king| self.line_blockers(king, color, color))
+    }
+
+    // Both questions above come down to the same walk. From the king, look
+    // along each line for a piece of `blocker` color with a `slider` color
+    // piece behind it that moves along that line, and nothing else in between.
+    fn line_blockers(&self, king: Square, blocker: PieceColor, slider: PieceColor) -> Bitboard {
+        let lines = [
+            (ROOK_DIRECTIONS, PieceKind::Rook),
+            (BISHOP_DIRECTIONS, PieceKind::Bishop),
+        ];
+        let mut blockers = Bitboard::EMPTY;
+        for (directions, kind) in lines {
+            for (df, dr) in directions {
+                let mut pieces = std::iter::successors(king.offset(df, dr), |sq| sq.offset(df, dr))
+                    .filter_map(|sq| self.piece_at(sq).map(|piece| (sq, piece)));
+                let (Some((square, first)), Some((_, second))) = (pieces.next(), pieces.next()) else {
+                    continue;
+                };
+                if first.color() == blocker
+                    && second.color() == slider
+                    && (second.kind() == kind || second.kind() == PieceKind::Queen)
+                {
+                    blockers |= square;
+                }
+            }
+        }
+        blockers
+    }
+
+    // The squares the piece on `square` attacks, whether they're empty or hold
+    // a piece of either color. Empty if there is no piece there.
+    pub fn attacks_from(&self, square: Square) -> Bitboard {
+        let Some(piece) = self.piece_at(square) else {
+            return Bitboard::EMPTY;
+        };
+        let occupied: Bitboard = self.piece_list().into_iter().map(|(sq, _)| sq).collect();
+        match piece.kind() {
+            PieceKind::Pawn => pawn_attacks(piece.color(), square),
+            PieceKind::Knight => knight_attacks(square),
+            PieceKind::King => king_attacks(square),
+            PieceKind::Bishop => bishop_attacks(square, occupied),
+            PieceKind::Rook => rook_attacks(square, occupied),
+            PieceKind::Queen => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+        }
+    }
+
+    // For every square, how many of `color`'s pieces attack it, indexed by
+    // Square::index. The more attackers, the harder a square is to take or
+    // for the enemy king to stand near, which is what threat heatmaps and
+    // king-safety terms want to know. Sliders are counted as they are: a
+    // queen stacked behind its own rook doesn't see past the rook.
+    pub fn attack_map(&self, color: PieceColor) -> [u8; 64] {
+        let mut counts = [0; 64];
+        for (square, piece) in self.piece_list() {
+            if piece.color() != color {
+                continue;
+            }
+            for target in self.attacks_from(square).iter_squares() {
+                counts[target.index()] += 1;
+            }
+        }
+        counts
+    }
+
+    // The number of distinct squares `color` attacks, as a rough measure of
+    // space and piece activity. Each square counts once however many pieces
+    // hit it, and squares holding our own pieces count too (they're
+    // defended), so the figure doesn't swing with every capture.
+    pub fn count_attacks(&self, color: PieceColor) -> u32 {
+        Square::all().filter(|&square| self.is_square_attacked(square, color)).count() as u32
+    }
+
+    // Returns true if any piece of color `by` attacks `square`.
+    //
+    // Instead of generating every move of the attacking side, we look outwards
+    // from the target square: if a knight-jump away there is an enemy knight,
+    // the square is attacked by a knight, and so on for each piece type.
+    pub fn is_square_attacked(&self, square: Square, by: PieceColor) -> bool {
+        let any = |targets: Bitboard, kind: PieceKind| {
+            targets.iter_squares().any(|sq| self.piece_at(sq) == Some(Piece::new(kind, by)))
+        };
+
+        // Pawns attack diagonally forwards, so we look diagonally *backwards*
+        // from the target, which is where a pawn of the other color would
+        // attack.
+        if any(pawn_attacks(by.opposite(), square), PieceKind::Pawn)
+            || any(knight_attacks(square), PieceKind::Knight)
+            || any(king_attacks(square), PieceKind::King)
+        {
+            return true;
+        }
+
+        let sliders = [
+            (ROOK_DIRECTIONS, [PieceKind::Rook, PieceKind::Queen]),
+            (BISHOP_DIRECTIONS, [PieceKind::Bishop, PieceKind::Queen]),
+        ];
+        for (directions, kinds) in sliders {
+            for (df, dr) in directions {
+                // Walk along the ray until we fall off the board or bump into
+                // a piece; only the first piece hit can be an attacker.
+                let mut current = square.offset(df, dr);
+                while let Some(sq) = current {
+                    if let Some(piece) = self.piece_at(sq) {
+                        if piece.color() == by && kinds.contains(&piece.kind()) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = sq.offset(df, dr);
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
+    }
+}
+
+// Prints the board as Board::to_ascii with rank numbers down the side and
+// file letters underneath, always from White's side. It sticks to plain
+// ASCII so it is safe to write to log files:
+//
+//   8 r n b q k b n r
+//   7 p p p p p p p p
+//   ...
+//   1 R N B Q K B N R
+//     a b c d e f g h
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, rank) in self.to_ascii().lines().zip((1..=8).rev()) {
+            writeln!(f, "{} {}", rank, line)?;
+        }
+        writeln!(f, "  a b c d e f g h")
+    }
+}
+
+// Binary formats store a board as its fields rather than as FEN: one byte per
+// square (the piece's packed byte, or EMPTY_SQUARE) and the rest of the
+// position as numbers. That skips parsing FEN on the way back in.
+const EMPTY_SQUARE: u8 = 0xFF;
+
+#[derive(Serialize, Deserialize)]
+struct PackedBoard {
+    squares: Vec<u8>,
+    side_to_move: PieceColor,
+    castling: u8,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+// In JSON a board is its FEN string, the same text clients already send and
+// receive for positions.
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&self.to_fen());
+        }
+        PackedBoard {
+            squares: self.squares.iter().map(|piece| piece.map_or(EMPTY_SQUARE, Piece::to_raw)).collect(),
+            side_to_move: self.side_to_move,
+            castling: self.castling.bits(),
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let fen = String::deserialize(deserializer)?;
+            return Board::from_fen(&fen).map_err(de::Error::custom);
+        }
+        let packed = PackedBoard::deserialize(deserializer)?;
+        if packed.squares.len() != 64 {
+            return Err(de::Error::invalid_length(packed.squares.len(), &"64 squares"));
+        }
+        let mut board = Board::empty();
+        for (square, &byte) in Square::all().zip(&packed.squares) {
+            if byte != EMPTY_SQUARE {
+                board.put_piece(square, Piece::try_from(byte).map_err(de::Error::custom)?);
+            }
+        }
+        let castling = CastlingRights::from_bits(packed.castling)
+            .ok_or_else(|| de::Error::custom(format!("invalid castling rights {}", packed.castling)))?;
+        board.set_side_to_move(packed.side_to_move);
+        board.set_castling_rights(castling);
+        board.set_en_passant(packed.en_passant);
+        board.set_halfmove_clock(packed.halfmove_clock);
+        board.set_fullmove_number(packed.fullmove_number);
+        Ok(board)
+    }
+}
+
+// What code needs from a board representation to play a game on it: read
+// the position, list the legal moves, spot check, and play and take back a
+// move. Game, the variants' default rules and perft are written against
+// this, so they run on any representation.
+//
+// Board (the mailbox above) and BitBoards both do all of this natively. A
+// new representation only has to convert to and from a Board; every other
+// method has a default that goes through that conversion. That is slow, but
+// it means the new one can be checked against Board (with perft, say) as
+// soon as its conversions work, and its methods can then be replaced with
+// native versions one at a time.
+pub trait BoardRepr: Clone {
+    // Whatever `unmake_move` needs to undo a move.
+    type Undo: Clone;
+
+    fn from_board(board: &Board) -> Self;
+
+    fn to_board(&self) -> Board;
+
+    fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.to_board().piece_at(square)
+    }
+
+    fn side_to_move(&self) -> PieceColor {
+        self.to_board().side_to_move()
+    }
+
+    fn halfmove_clock(&self) -> u32 {
+        self.to_board().halfmove_clock()
+    }
+
+    fn legal_moves(&self) -> Vec<Move> {
+        self.to_board().legal_moves()
+    }
+
+    // True if the king of `color` is attacked, whoever's turn it is.
+    fn king_in_check(&self, color: PieceColor) -> bool {
+        self.to_board().king_in_check(color)
+    }
+
+    fn make_move(&mut self, mv: Move) -> Self::Undo;
+
+    fn unmake_move(&mut self, mv: Move, undo: Self::Undo);
+}
+
+impl BoardRepr for Board {
+    type Undo = UndoInfo;
+
+    fn from_board(board: &Board) -> Self {
+        board.clone()
+    }
+
+    fn to_board(&self) -> Board {
+        self.clone()
+    }
+
+    fn piece_at(&self, square: Square) -> Option<Piece> {
+        Board::piece_at(self, square)
+    }
+
+    fn side_to_move(&self) -> PieceColor {
+        Board::side_to_move(self)
+    }
+
+    fn halfmove_clock(&self) -> u32 {
+        Board::halfmove_clock(self)
+    }
+
+    fn legal_moves(&self) -> Vec<Move> {
+        Board::legal_moves(self)
+    }
+
+    fn king_in_check(&self, color: PieceColor) -> bool {
+        Board::king_in_check(self, color)
+    }
+
+    fn make_move(&mut self, mv: Move) -> UndoInfo {
+        Board::make_move(self, mv)
+    }
+
+    fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        Board::unmake_move(self, mv, undo)
+    }
 }
@@ -0,0 +1,100 @@
+//! Setting up positions by hand.
+//! 
+//! This file includes:
+//! - BoardBuilder, for placing and removing pieces one square at a time
+//! - Building the result into a Board that has passed validation
+
+use crate::chess::board::{Board, CastlingRights};
+use crate::chess::piece::{Piece, PieceColor};
+use crate::chess::square::Square;
+use crate::chess::validate::PositionError;
+
+// A position under construction, as in a board editor. Start from an empty
+// board (or an existing one), make changes in any order, and call `build`
+// once it's done. The position is only checked at the end, since halfway
+// through setting up it is bound to be invalid, e.g. with only one king.
+//
+//   let board = BoardBuilder::new()
+//       .place(e1, white_king)
+//       .place(e8, black_king)
+//       .place(d1, white_queen)
+//       .build()?;
+#[derive(Clone)]
+pub struct BoardBuilder {
+    board: Board,
+}
+
+impl BoardBuilder {
+    // An empty board with White to move.
+    pub fn new() -> Self {
+        BoardBuilder { board: Board::empty() }
+    }
+
+    // Starts from an existing position, to make changes to it.
+    pub fn from_board(board: Board) -> Self {
+        BoardBuilder { board }
+    }
+
+    // Puts `piece` on `square`, replacing whatever was there.
+    pub fn place(&mut self, square: Square, piece: Piece) -> &mut Self {
+        self.board.put_piece(square, piece);
+        self
+    }
+
+    pub fn remove(&mut self, square: Square) -> &mut Self {
+        self.board.remove_piece(square);
+        self
+    }
+
+    // Takes every piece off the board. The other settings are kept.
+    pub fn clear(&mut self) -> &mut Self {
+        for square in Square::all() {
+            self.board.remove_piece(square);
+        }
+        self
+    }
+
+    pub fn side_to_move(&mut self, color: PieceColor) -> &mut Self {
+        self.board.set_side_to_move(color);
+        self
+    }
+
+    pub fn castling_rights(&mut self, rights: CastlingRights) -> &mut Self {
+        self.board.set_castling_rights(rights);
+        self
+    }
+
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut Self {
+        self.board.set_en_passant(square);
+        self
+    }
+
+    pub fn halfmove_clock(&mut self, clock: u32) -> &mut Self {
+        self.board.set_halfmove_clock(clock);
+        self
+    }
+
+    pub fn fullmove_number(&mut self, number: u32) -> &mut Self {
+        self.board.set_fullmove_number(number);
+        self
+    }
+
+    // The position as it stands, whether or not it is valid yet. Handy for
+    // showing the editor's board while it is being set up.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    // Finishes the position, or says what is wrong with it (see
+    // Board::validate).
+    pub fn build(&self) -> Result<Board, PositionError> {
+        self.board.validate()?;
+        Ok(self.board.clone())
+    }
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        BoardBuilder::new()
+    }
+}
@@ -0,0 +1,76 @@
+//! Chess clocks.
+//! 
+//! This file defines:
+//! - The Clock struct tracking each player's remaining time
+//! - Pressing the clock after a move, including the per-move increment
+//! - Resetting both sides back to the starting time
+
+use std::time::{Duration, Instant};
+
+use crate::chess::piece::PieceColor;
+
+// A two-sided clock with an optional Fischer increment.
+//
+// The clock doesn't read the system time itself: callers pass in "now". That
+// keeps it deterministic, so tests can simulate a player thinking for exactly
+// 3 seconds without actually sleeping.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    initial: Duration,
+    increment: Duration,
+    // Indexed by PieceColor::index(): [White, Black].
+    remaining: [Duration; 2],
+    // When the current player's turn began. None until the first move, so the
+    // clock doesn't run while players are still joining.
+    turn_started: Option<Instant>,
+}
+
+impl Clock {
+    pub fn new(initial: Duration, increment: Duration) -> Self {
+        Clock {
+            initial,
+            increment,
+            remaining: [initial; 2],
+            turn_started: None,
+        }
+    }
+
+    pub fn initial(&self) -> Duration {
+        self.initial
+    }
+
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        self.remaining[color.index()]
+    }
+
+    // Called when `color` finishes a move at time `now`. Charges them for the
+    // time spent since their turn started, adds the increment, and starts the
+    // opponent's turn. Returns how long the move took.
+    pub fn press(&mut self, color: PieceColor, now: Instant) -> Duration {
+        let elapsed = self
+            .turn_started
+            .map(|started| now.saturating_duration_since(started))
+            .unwrap_or(Duration::ZERO);
+        let remaining = &mut self.remaining[color.index()];
+        *remaining = remaining.saturating_sub(elapsed) + self.increment;
+        self.turn_started = Some(now);
+        elapsed
+    }
+
+    // Whether `color`, whose turn it is, has used up all their time by `now`.
+    // A clock that hasn't started yet never runs out.
+    pub fn out_of_time(&self, color: PieceColor, now: Instant) -> bool {
+        self.turn_started
+            .is_some_and(|started| now.saturating_duration_since(started) >= self.remaining(color))
+    }
+
+    // Puts both sides back to the starting time and stops the clock.
+    pub fn reset(&mut self) {
+        self.remaining = [self.initial; 2];
+        self.turn_started = None;
+    }
+}
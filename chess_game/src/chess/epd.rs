@@ -0,0 +1,255 @@
+//! Extended Position Description (EPD) support.
+//! 
+//! This file includes:
+//! - The Epd record: a position plus its operations
+//! - Typed access to the bm, am, id and ce opcodes test suites rely on
+//! - Reading single records and whole suite files, and writing records back
+//! - The error type describing why a record was rejected
+//!
+//! An EPD record is the first four FEN fields followed by operations, each an
+//! opcode, its operands and a semicolon:
+//! `r1b1k2r/... w kq - bm Qxf7+; id "WAC.042";`
+
+use std::fmt;
+
+use crate::chess::board::Board;
+use crate::chess::fen::FenError;
+use crate::chess::r#move::{Move, MoveError};
+
+// One EPD record.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Epd {
+    // The clocks aren't part of the record, so they are 0 and 1 unless the
+    // hmvc and fmvn operations say otherwise; those are kept in `other`.
+    pub board: Board,
+    // "bm": the moves that solve the position. Any one of them will do.
+    pub best_moves: Vec<Move>,
+    // "am": moves that must not be played.
+    pub avoid_moves: Vec<Move>,
+    // "id": the record's name within its suite, e.g. "WAC.001".
+    pub id: Option<String>,
+    // "ce": the evaluation in centipawns, from the side to move's point of
+    // view.
+    pub centipawn_eval: Option<i32>,
+    // Every other operation, in the order it was read, so it is written back
+    // unchanged.
+    pub other: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EpdError {
+    InvalidPosition(FenError),
+    // An operation with no opcode, e.g. a stray ";".
+    MissingOpcode,
+    // An opcode that isn't a letter followed by letters, digits and '_'.
+    InvalidOpcode(String),
+    // The last operation has no closing ';'.
+    MissingSemicolon(String),
+    UnterminatedString,
+    // A bm or am move that can't be played in the position.
+    InvalidMove { opcode: String, san: String, error: MoveError },
+    // An operand of the wrong form, e.g. a ce that isn't a number.
+    InvalidOperand { opcode: String, operand: String },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::InvalidPosition(error) => write!(f, "invalid position: {}", error),
+            EpdError::MissingOpcode => write!(f, "operation without an opcode"),
+            EpdError::InvalidOpcode(opcode) => write!(f, "invalid opcode '{}'", opcode),
+            EpdError::MissingSemicolon(opcode) => write!(f, "operation '{}' isn't ended by ';'", opcode),
+            EpdError::UnterminatedString => write!(f, "string operand without a closing quote"),
+            EpdError::InvalidMove { opcode, san, error } => write!(f, "{} {}: {}", opcode, san, error),
+            EpdError::InvalidOperand { opcode, operand } => write!(f, "invalid operand '{}' for {}", operand, opcode),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+// Splits off the first `count` whitespace-separated fields, returning them
+// and whatever follows.
+fn split_fields(text: &str, count: usize) -> (Vec<&str>, &str) {
+    let mut fields = Vec::new();
+    let mut rest = text.trim_start();
+    while fields.len() < count && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    (fields, rest)
+}
+
+fn is_opcode(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Reads the operations after the position: opcode, operands, ';', repeated.
+// String operands are in double quotes and may hold spaces and semicolons.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None => break,
+            Some(';') => {
+                chars.next();
+                let mut words = std::mem::take(&mut words).into_iter();
+                let opcode = words.next().ok_or(EpdError::MissingOpcode)?;
+                operations.push((opcode, words.collect()));
+            }
+            Some('"') => {
+                chars.next();
+                let mut operand = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => operand.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                if words.is_empty() {
+                    return Err(EpdError::MissingOpcode);
+                }
+                words.push(operand);
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';' && *c != '"') {
+                    word.push(c);
+                }
+                if words.is_empty() && !is_opcode(&word) {
+                    return Err(EpdError::InvalidOpcode(word));
+                }
+                words.push(word);
+            }
+        }
+    }
+    match words.into_iter().next() {
+        Some(opcode) => Err(EpdError::MissingSemicolon(opcode)),
+        None => Ok(operations),
+    }
+}
+
+// Writes an operand, quoting it if it wouldn't read back as one word.
+fn operand_text(operand: &str) -> String {
+    if operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"') {
+        format!("\"{}\"", operand)
+    } else {
+        operand.to_string()
+    }
+}
+
+impl Epd {
+    // A record with the given position and no operations.
+    pub fn new(board: Board) -> Self {
+        Epd { board, best_moves: Vec::new(), avoid_moves: Vec::new(), id: None, centipawn_eval: None, other: Vec::new() }
+    }
+
+    // Parses one record. The bm and am moves are SAN, read as loosely as
+    // Move::from_san allows, and must be legal in the position.
+    pub fn parse(text: &str) -> Result<Epd, EpdError> {
+        let (fields, rest) = split_fields(text, 4);
+        let board = Board::from_fen(&fields.join(" ")).map_err(EpdError::InvalidPosition)?;
+        let mut epd = Epd::new(board);
+
+        for (opcode, operands) in parse_operations(rest)? {
+            let invalid = |operand: &str| EpdError::InvalidOperand { opcode: opcode.clone(), operand: operand.to_string() };
+            match opcode.as_str() {
+                "bm" | "am" => {
+                    let moves = operands
+                        .iter()
+                        .map(|san| {
+                            Move::from_san(san, &epd.board).map_err(|error| EpdError::InvalidMove {
+                                opcode: opcode.clone(),
+                                san: san.clone(),
+                                error,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if opcode == "bm" {
+                        epd.best_moves.extend(moves);
+                    } else {
+                        epd.avoid_moves.extend(moves);
+                    }
+                }
+                "id" => match operands.as_slice() {
+                    [id] => epd.id = Some(id.clone()),
+                    _ => return Err(invalid(&operands.join(" "))),
+                },
+                "ce" => match operands.as_slice() {
+                    [ce] => epd.centipawn_eval = Some(ce.parse().map_err(|_| invalid(ce))?),
+                    _ => return Err(invalid(&operands.join(" "))),
+                },
+                _ => epd.other.push((opcode, operands)),
+            }
+        }
+        Ok(epd)
+    }
+
+    // Parses a suite file, one record per line. Blank lines are skipped.
+    // Each record comes with its line number (from 1), so a bad one can be
+    // reported and the rest still used.
+    pub fn parse_suite(text: &str) -> impl Iterator<Item = (usize, Result<Epd, EpdError>)> + '_ {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, Epd::parse(line)))
+    }
+
+    // The operands of one of the other operations, e.g. "c0" for a
+    // comment.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.other.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // True if playing `mv` passes this test: it is one of the best moves (if
+    // any are given) and none of the moves to avoid.
+    pub fn is_solved_by(&self, mv: &Move) -> bool {
+        let listed = |moves: &[Move]| moves.iter().any(|candidate| candidate.same_squares(mv));
+        (self.best_moves.is_empty() || listed(&self.best_moves)) && !listed(&self.avoid_moves)
+    }
+}
+
+impl fmt::Display for Epd {
+    // The position's four FEN fields, then bm, am, ce and id, then the other
+    // operations.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fen = self.board.to_fen();
+        let position: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", position.join(" "))?;
+
+        let sans = |moves: &[Move]| moves.iter().map(|mv| mv.to_san(&self.board)).collect::<Vec<_>>();
+        let mut operations = Vec::new();
+        if !self.best_moves.is_empty() {
+            operations.push(("bm".to_string(), sans(&self.best_moves)));
+        }
+        if !self.avoid_moves.is_empty() {
+            operations.push(("am".to_string(), sans(&self.avoid_moves)));
+        }
+        if let Some(ce) = self.centipawn_eval {
+            operations.push(("ce".to_string(), vec![ce.to_string()]));
+        }
+        if let Some(id) = &self.id {
+            // Always quoted, as suites write it.
+            operations.push(("id".to_string(), vec![format!("\"{}\"", id)]));
+        }
+        for (opcode, operands) in &self.other {
+            operations.push((opcode.clone(), operands.iter().map(|operand| operand_text(operand)).collect()));
+        }
+
+        for (opcode, operands) in operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                write!(f, " {}", operand)?;
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+//! Notifications about what happens in a game.
+//! 
+//! This file defines:
+//! - The GameEvent enum: moves, checks, draw offers, low clocks, game ends
+//! - The GameObserver trait for code that wants to hear about them
+//!
+//! Game only announces events; it doesn't know who listens. Broadcasting,
+//! persistence and rating updates can each register their own observer.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chess::game::{GameResult, Termination};
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::Move;
+
+// Once a player has less than this left on their clock, a ClockLow event is
+// sent for them.
+pub const CLOCK_LOW_THRESHOLD: Duration = Duration::from_secs(30);
+
+// Something that happened in a game, in the order it happened. Playing one
+// move can send several: MovePlayed, then CheckGiven, then GameEnded for a
+// mate.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    MovePlayed { color: PieceColor, mv: Move, san: String },
+    // `color`'s king has been put in check.
+    CheckGiven { color: PieceColor },
+    DrawOffered { color: PieceColor },
+    // `color`'s clock has just dropped below CLOCK_LOW_THRESHOLD. Sent once
+    // per crossing.
+    ClockLow { color: PieceColor, remaining_ms: u64 },
+    // None for an aborted game.
+    GameEnded { result: Option<GameResult>, termination: Termination },
+}
+
+// Receives a game's events as they happen. Observers run inside the call
+// that caused the event, often with the game locked, so they should be
+// quick: hand the event on rather than do slow work. Any
+// `Fn(&GameEvent) + Send + Sync` closure is an observer.
+pub trait GameObserver: Send + Sync {
+    fn on_event(&self, event: &GameEvent);
+}
+
+impl<F: Fn(&GameEvent) + Send + Sync> GameObserver for F {
+    fn on_event(&self, event: &GameEvent) {
+        self(event)
+    }
+}
@@ -0,0 +1,222 @@
+//! Forsyth-Edwards Notation (FEN) support.
+//! 
+//! This file includes:
+//! - The standard starting position as a FEN string
+//! - Parsing a FEN string into a Board
+//! - Serializing a Board back into FEN
+//! - The error type describing why a FEN string was rejected
+//! - Reading and writing the castling field on its own
+
+use std::fmt;
+
+use crate::chess::board::{Board, CastlingRights};
+use crate::chess::piece::{Piece, PieceColor};
+use crate::chess::square::Square;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Everything that can be wrong with a FEN string, one variant per field so the
+// caller can tell the user which part to fix.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    InvalidPiecePlacement(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(n) => write!(f, "expected 4 to 6 FEN fields, found {}", n),
+            FenError::InvalidPiecePlacement(s) => write!(f, "invalid piece placement '{}'", s),
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// The castling letters in FEN, in the order FEN writes them.
+const CASTLING_LETTERS: [(CastlingRights, char); 4] = [
+    (CastlingRights::WHITE_KINGSIDE, 'K'),
+    (CastlingRights::WHITE_QUEENSIDE, 'Q'),
+    (CastlingRights::BLACK_KINGSIDE, 'k'),
+    (CastlingRights::BLACK_QUEENSIDE, 'q'),
+];
+
+impl CastlingRights {
+    // Reads FEN's castling field: some of "KQkq", or "-" for none.
+    pub fn from_fen(field: &str) -> Option<CastlingRights> {
+        let mut rights = CastlingRights::NONE;
+        if field == "-" {
+            return Some(rights);
+        }
+        if field.is_empty() {
+            return None;
+        }
+        for c in field.chars() {
+            let (right, _) = CASTLING_LETTERS.iter().find(|(_, letter)| *letter == c)?;
+            rights.insert(*right);
+        }
+        Some(rights)
+    }
+
+    // Writes the rights as FEN's castling field.
+    pub fn to_fen(self) -> String {
+        if self.is_empty() {
+            return "-".to_string();
+        }
+        CASTLING_LETTERS
+            .iter()
+            .filter(|(right, _)| self.contains(*right))
+            .map(|(_, letter)| letter)
+            .collect()
+    }
+}
+
+impl Board {
+    // Parses a FEN string such as
+    // "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".
+    //
+    // Fields may be separated by any amount of whitespace, and the two clock
+    // fields may be left off (as in EPD), in which case they default to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&fields.len()) {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        let mut board = Board::empty();
+
+        // Field 1: piece placement, rank 8 first, ranks separated by '/'.
+        // Digits stand for that many empty squares.
+        let placement = fields[0];
+        let bad_placement = || FenError::InvalidPiecePlacement(placement.to_string());
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(bad_placement());
+        }
+        for (i, rank_text) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file = 0u8;
+            for c in rank_text.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    if !(1..=8).contains(&skip) {
+                        return Err(bad_placement());
+                    }
+                    file += skip as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or_else(bad_placement)?;
+                    let square = Square::new(file, rank).ok_or_else(bad_placement)?;
+                    board.put_piece(square, piece);
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err(bad_placement());
+                }
+            }
+            if file != 8 {
+                return Err(bad_placement());
+            }
+        }
+
+        // Field 2: side to move.
+        board.set_side_to_move(match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
+        });
+
+        // Field 3: castling rights, "-" for none.
+        let castling = CastlingRights::from_fen(fields[2])
+            .ok_or_else(|| FenError::InvalidCastling(fields[2].to_string()))?;
+        board.set_castling_rights(castling);
+
+        // Field 4: en passant target square, "-" for none. It can only ever
+        // be on the third or sixth rank.
+        if fields[3] != "-" {
+            let square = Square::from_algebraic(fields[3])
+                .filter(|sq| sq.rank() == 2 || sq.rank() == 5)
+                .ok_or_else(|| FenError::InvalidEnPassant(fields[3].to_string()))?;
+            board.set_en_passant(Some(square));
+        }
+
+        // Fields 5 and 6: halfmove clock and fullmove number.
+        let halfmove_text = fields.get(4).copied().unwrap_or("0");
+        let halfmove = halfmove_text
+            .parse()
+            .map_err(|_| FenError::InvalidHalfmoveClock(halfmove_text.to_string()))?;
+        board.set_halfmove_clock(halfmove);
+        let fullmove_text = fields.get(5).copied().unwrap_or("1");
+        let fullmove = fullmove_text
+            .parse()
+            .ok()
+            .filter(|&n: &u32| n >= 1)
+            .ok_or_else(|| FenError::InvalidFullmoveNumber(fullmove_text.to_string()))?;
+        board.set_fullmove_number(fullmove);
+
+        Ok(board)
+    }
+
+    // Serializes the position into a six-field FEN string.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let square = Square::new(file, rank).unwrap();
+                match self.piece_at(square) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.to_fen_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move() {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        });
+
+        fen.push(' ');
+        fen.push_str(&self.castling_rights().to_fen());
+
+        fen.push(' ');
+        match self.en_passant() {
+            Some(square) => fen.push_str(&square.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock(), self.fullmove_number()));
+        fen
+    }
+}
+
+// Rewrites a FEN string in canonical form: single spaces between fields,
+// castling rights in "KQkq" order, "-" for empty castling and en passant
+// fields, and explicit clock fields. Two FENs describing the same position
+// normalize to the same string, which makes them usable as table keys.
+pub fn normalize_fen(fen: &str) -> Result<String, FenError> {
+    Ok(Board::from_fen(fen)?.to_fen())
+}
@@ -5,11 +5,1114 @@
 //! - Turn-based logic and player management
 //! - Check, checkmate, and stalemate detection
 //! - Game history and move recording
+//! - Saving and loading games through serde
+//!
+//! A Game is generic over the board representation it plays on, Board by
+//! default; see BoardRepr.
 
-pub struct Game {
-    // TODO: Implement game state
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::chess::board::{Board, BoardRepr};
+use crate::chess::clock::Clock;
+use crate::chess::events::{GameEvent, GameObserver, CLOCK_LOW_THRESHOLD};
+use crate::chess::fen::FenError;
+use crate::chess::piece::{PieceColor, PieceKind};
+use crate::chess::r#move::{Move, MoveDetail, MoveError, MoveFlag};
+use crate::chess::square::Square;
+use crate::chess::variant::{variant_by_name, Standard, Variant};
+
+// Serialized in PGN notation, like `as_str`.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum GameResult {
+    #[serde(rename = "1-0")]
+    WhiteWins,
+    #[serde(rename = "0-1")]
+    BlackWins,
+    #[serde(rename = "1/2-1/2")]
+    Draw,
+}
+
+impl GameResult {
+    // The result in PGN notation: "1-0", "0-1" or "1/2-1/2".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    // A win for the given color.
+    pub fn win_for(color: PieceColor) -> GameResult {
+        match color {
+            PieceColor::White => GameResult::WhiteWins,
+            PieceColor::Black => GameResult::BlackWins,
+        }
+    }
+}
+
+// Why a game ended. Serialized the same way as `as_str`.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    // Neither side has enough pieces left to ever deliver mate.
+    InsufficientMaterial,
+    // There is material left, but it's locked up so that no sequence of
+    // moves can lead to mate.
+    DeadPosition,
+    // 75 moves by each side without a pawn move or capture.
+    SeventyFiveMoveRule,
+    // The same position for the fifth time.
+    FivefoldRepetition,
+    // Ended by a player rather than by the rules.
+    Resignation,
+    Agreement,
+    // A player ran out of time.
+    Timeout,
+    // Draws that have to be claimed: the game can go on until a player
+    // does.
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    // A player left the game and didn't come back.
+    Abandonment,
+    // Called off before it really started. Nobody wins and the game has no
+    // result.
+    Aborted,
+    // Won or drawn by a rule of the game's variant that standard chess
+    // doesn't have, like a king reaching the centre in King of the Hill.
+    VariantEnd,
+}
+
+impl Termination {
+    // The reason as sent to clients, e.g. "insufficient_material".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::InsufficientMaterial => "insufficient_material",
+            Termination::DeadPosition => "dead_position",
+            Termination::SeventyFiveMoveRule => "seventy_five_move_rule",
+            Termination::FivefoldRepetition => "fivefold_repetition",
+            Termination::Resignation => "resignation",
+            Termination::Agreement => "agreement",
+            Termination::Timeout => "timeout",
+            Termination::ThreefoldRepetition => "threefold_repetition",
+            Termination::FiftyMoveRule => "fifty_move_rule",
+            Termination::Abandonment => "abandonment",
+            Termination::Aborted => "aborted",
+            Termination::VariantEnd => "variant_end",
+        }
+    }
+
+    // The value of the PGN Termination tag. PGN only has a handful: a game
+    // that ended over the board, however it ended, is "normal".
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Timeout => "time forfeit",
+            Termination::Abandonment => "abandoned",
+            Termination::Aborted => "unterminated",
+            _ => "normal",
+        }
+    }
+
+    // Whether the position on the board ended the game by itself, rather
+    // than a player or the clock. Only these endings go away when the move
+    // that led to them is taken back.
+    pub fn follows_from_position(&self) -> bool {
+        matches!(
+            self,
+            Termination::Checkmate
+                | Termination::Stalemate
+                | Termination::InsufficientMaterial
+                | Termination::DeadPosition
+                | Termination::SeventyFiveMoveRule
+                | Termination::FivefoldRepetition
+                | Termination::VariantEnd
+        )
+    }
+}
+
+// The grounds a player gives for claiming a draw. Unlike the 75-move rule
+// and fivefold repetition, which end the game by themselves, these only
+// apply if a player asks. In JSON: "threefold_repetition" or
+// "fifty_move_rule".
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawClaim {
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+// Where the game stands, as a single value to match on. Worked out after
+// every move, so reading it is free. In JSON: "ongoing", "stalemate",
+// {"checkmate": "white"}, {"draw": "insufficient_material"} or
+// {"won": ["black", "resignation"]}.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GameStatus {
+    Ongoing,
+    // The winner, i.e. the side that delivered mate.
+    Checkmate(PieceColor),
+    Stalemate,
+    // Any other draw, such as insufficient material.
+    Draw(Termination),
+    // A win other than by mate: the winner and how, e.g. on time.
+    Won(PieceColor, Termination),
+    Aborted,
+}
+
+// Analysis games are scratchpads: either side can move pieces around and
+// rewind freely. Standard games are a real contest between two players.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    Standard,
+    Analysis,
+}
+
+// Errors for game-level actions other than playing a move.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GameError {
+    // A reset was asked for in the middle of a standard game and the other
+    // player hasn't agreed to it yet.
+    ResetNotAgreed,
+    // A ply beyond the end of the game was asked for.
+    PlyOutOfRange { ply: usize, max: usize },
+    // The game has already ended.
+    GameOver,
+    // Both sides have moved, so the game can only be resigned or drawn now.
+    TooLateToAbort,
+    // A draw was claimed on grounds that don't hold in this position.
+    DrawClaimRejected(DrawClaim),
+    // A draw offer was answered, but none is open.
+    NoDrawOffer,
+    // A hint was asked for in a rated game.
+    HintsDisabled,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::ResetNotAgreed => write!(f, "both players must agree to reset the game"),
+            GameError::PlyOutOfRange { ply, max } => {
+                write!(f, "ply {} is out of range, the game has {} plies", ply, max)
+            }
+            GameError::GameOver => write!(f, "the game is already over"),
+            GameError::TooLateToAbort => write!(f, "the game can't be aborted once both sides have moved"),
+            GameError::DrawClaimRejected(DrawClaim::ThreefoldRepetition) => {
+                write!(f, "the position hasn't occurred three times")
+            }
+            GameError::DrawClaimRejected(DrawClaim::FiftyMoveRule) => {
+                write!(f, "50 moves haven't been played without a pawn move or capture")
+            }
+            GameError::NoDrawOffer => write!(f, "no draw has been offered"),
+            GameError::HintsDisabled => write!(f, "hints are disabled in rated games"),
+        }
+    }
 }
 
+impl std::error::Error for GameError {}
+
+// One played move, together with what's needed to take it back. The SAN is
+// worked out when the move is played, since it depends on the position.
+#[derive(Clone)]
+pub struct HistoryEntry<B: BoardRepr = Board> {
+    mv: Move,
+    san: String,
+    // The position before the move. Undoing restores it rather than
+    // unplaying the move, since some variants' moves (atomic explosions, for
+    // one) can't be unplayed.
+    before: B,
+    // How long the mover spent on this move, as charged by the clock. None in
+    // games played without a clock.
+    time_taken: Option<Duration>,
+    // What the mover had left on their clock once the move was made,
+    // increment included.
+    clock_remaining: Option<Duration>,
+    // The wall-clock time the move was played at.
+    played_at: SystemTime,
+    // The position this move led to, in the form used to spot repetitions.
+    position_key: String,
+    // The ply of the position reached by the latest pawn move or capture up
+    // to and including this move. No position before it can ever come back,
+    // so repetitions are only looked for from there on.
+    irreversible_ply: usize,
+}
+
+impl<B: BoardRepr> HistoryEntry<B> {
+    pub fn mv(&self) -> Move {
+        self.mv
+    }
+
+    pub fn san(&self) -> &str {
+        &self.san
+    }
+
+    pub fn time_taken(&self) -> Option<Duration> {
+        self.time_taken
+    }
+
+    pub fn clock_remaining(&self) -> Option<Duration> {
+        self.clock_remaining
+    }
+
+    pub fn played_at(&self) -> SystemTime {
+        self.played_at
+    }
+
+    // The PGN clock comment for this move, e.g. "[%clk 0:04:57]", or None
+    // without a clock. PGN clocks count whole seconds.
+    pub fn clock_comment(&self) -> Option<String> {
+        let seconds = self.clock_remaining?.as_secs();
+        Some(format!("[%clk {}:{:02}:{:02}]", seconds / 3600, seconds / 60 % 60, seconds % 60))
+    }
+}
+
+#[derive(Clone)]
+pub struct Game<B: BoardRepr = Board> {
+    board: B,
+    // The position the game started from, so history can be replayed.
+    start: B,
+    history: Vec<HistoryEntry<B>>,
+    // Moves taken back with `undo`, the most recently undone last, so `redo`
+    // can play them again. Playing any other move forgets them.
+    undone: Vec<HistoryEntry<B>>,
+    result: Option<GameResult>,
+    // Set together with `result`.
+    termination: Option<Termination>,
+    clock: Option<Clock>,
+    mode: GameMode,
+    // Whether a pawn move to the last rank without a promotion piece becomes
+    // a queen instead of being rejected.
+    auto_queen: bool,
+    // Whether the game counts for the players' ratings, which rules out
+    // help from the engine.
+    rated: bool,
+    // Which players have asked to reset the game, indexed by color.
+    reset_requests: [bool; 2],
+    // The player who has offered a draw that hasn't been answered yet.
+    draw_offer: Option<PieceColor>,
+    // Display names of the players, indexed by color. None for an
+    // anonymous player.
+    players: [Option<String>; 2],
+    // Told about everything that happens from now on. Shared, not copied,
+    // when the game is cloned.
+    observers: Vec<Arc<dyn GameObserver>>,
+    // The rules the game is played by.
+    variant: Arc<dyn Variant<B>>,
+}
+
+// The constructors that can't tell the board representation from their
+// arguments make Board games; for another representation, start from one of
+// its positions with `from_board`.
 impl Game {
-    // TODO: Implement game methods
+    // Starts a new standard game from the initial position, without a clock.
+    pub fn new() -> Self {
+        Game::from_board(Board::new())
+    }
+
+    // Starts a new game of a variant from its own start position.
+    pub fn with_variant(variant: Arc<dyn Variant>) -> Self {
+        Game::from_board_with_variant(variant.start_position(), variant)
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Ok(Game::from_board(Board::from_fen(fen)?))
+    }
+
+    // Starts a game of a variant from a position in that variant's FEN,
+    // which may have fields standard FEN doesn't.
+    pub fn from_fen_with_variant(fen: &str, variant: Arc<dyn Variant>) -> Result<Self, FenError> {
+        let (board, configured) = variant.read_fen(fen)?;
+        Ok(Game::from_board_with_variant(board, configured.unwrap_or(variant)))
+    }
+}
+
+impl<B: BoardRepr> Game<B> {
+    // Starts a standard game from an arbitrary position.
+    pub fn from_board(board: B) -> Self {
+        Game::from_board_with_variant(board, Arc::new(Standard))
+    }
+
+    // Starts a game of a variant from an arbitrary position.
+    pub fn from_board_with_variant(board: B, variant: Arc<dyn Variant<B>>) -> Self {
+        Game {
+            start: board.clone(),
+            board,
+            history: Vec::new(),
+            undone: Vec::new(),
+            result: None,
+            termination: None,
+            clock: None,
+            mode: GameMode::Standard,
+            auto_queen: false,
+            rated: false,
+            reset_requests: [false; 2],
+            draw_offer: None,
+            players: [None, None],
+            observers: Vec::new(),
+            variant,
+        }
+    }
+
+    pub fn variant(&self) -> &dyn Variant<B> {
+        self.variant.as_ref()
+    }
+
+    pub fn board(&self) -> &B {
+        &self.board
+    }
+
+    // The current position as a full six-field FEN string, clocks included,
+    // which is what browser chessboard widgets expect.
+    pub fn current_fen(&self) -> String {
+        self.board.to_board().to_fen()
+    }
+
+    // The current position in the FEN of the game's variant. The same as
+    // `current_fen` unless the variant extends FEN.
+    pub fn variant_fen(&self) -> String {
+        self.variant.write_fen(self)
+    }
+
+    // The position before the first move.
+    pub fn start_board(&self) -> &B {
+        &self.start
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.board.side_to_move()
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+    }
+
+    pub fn auto_queen(&self) -> bool {
+        self.auto_queen
+    }
+
+    // Turns automatic queening on or off. Off by default, so a promotion
+    // without a piece is an error (MissingPromotion).
+    pub fn set_auto_queen(&mut self, auto_queen: bool) {
+        self.auto_queen = auto_queen;
+    }
+
+    pub fn is_rated(&self) -> bool {
+        self.rated
+    }
+
+    // Marks the game as rated, or not. Games are unrated by default.
+    pub fn set_rated(&mut self, rated: bool) {
+        self.rated = rated;
+    }
+
+    // Whether the engine may suggest a move: not once the game is over, and
+    // never in a rated game.
+    pub fn check_hint_allowed(&self) -> Result<(), GameError> {
+        if self.rated {
+            return Err(GameError::HintsDisabled);
+        }
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        Ok(())
+    }
+
+    // With auto-queen on, adds a queen promotion to a pawn move onto the
+    // last rank that doesn't say what to promote to.
+    fn with_default_promotion(&self, mv: Move) -> Move {
+        let last_rank = match self.side_to_move() {
+            PieceColor::White => 7,
+            PieceColor::Black => 0,
+        };
+        let is_pawn = self
+            .board
+            .piece_at(mv.from())
+            .is_some_and(|piece| piece.kind() == PieceKind::Pawn);
+        if self.auto_queen && is_pawn && mv.promotion().is_none() && mv.to().rank() == last_rank {
+            Move::with_promotion(mv.from(), mv.to(), PieceKind::Queen)
+        } else {
+            mv
+        }
+    }
+
+    // The name of the player with `color`, if they gave one.
+    pub fn player(&self, color: PieceColor) -> Option<&str> {
+        self.players[color.index()].as_deref()
+    }
+
+    pub fn set_player(&mut self, color: PieceColor, name: Option<String>) {
+        self.players[color.index()] = name;
+    }
+
+    // Registers an observer for this game's events.
+    pub fn add_observer(&mut self, observer: Arc<dyn GameObserver>) {
+        self.observers.push(observer);
+    }
+
+    fn emit(&self, event: GameEvent) {
+        for observer in &self.observers {
+            observer.on_event(&event);
+        }
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+    }
+
+    // The moves played so far, oldest first.
+    pub fn history(&self) -> &[HistoryEntry<B>] {
+        &self.history
+    }
+
+    // The number of half-moves played so far. Ply 0 is the starting
+    // position, ply 1 the position after White's first move, and so on.
+    pub fn ply(&self) -> usize {
+        self.history.len()
+    }
+
+    // The move played from the position at `ply`, i.e. the one that led to
+    // ply + 1. None if the game hasn't got that far.
+    pub fn move_at_ply(&self, ply: usize) -> Option<&HistoryEntry<B>> {
+        self.history.get(ply)
+    }
+
+    // Rebuilds the game as it stood after `ply` half-moves by replaying the
+    // history from the start, for stepping through a finished game. The copy
+    // keeps the moves up to that point but has no clock, since the time left
+    // at earlier moves isn't known.
+    pub fn position_at_ply(&self, ply: usize) -> Result<Game<B>, GameError> {
+        if ply > self.ply() {
+            return Err(GameError::PlyOutOfRange { ply, max: self.ply() });
+        }
+        let mut game = Game::from_board_with_variant(self.start.clone(), self.variant.clone());
+        game.mode = self.mode;
+        for entry in &self.history[..ply] {
+            self.variant.play(&mut game.board, entry.mv);
+            game.history.push(entry.clone());
+        }
+        // Only the last position of a game can be a finished one.
+        if ply == self.ply() {
+            game.result = self.result;
+            game.termination = self.termination;
+        }
+        Ok(game)
+    }
+
+    // Every move played so far in UCI notation, oldest first. This is the
+    // move list a UCI engine expects after `position startpos moves`.
+    pub fn uci_history(&self) -> Vec<String> {
+        self.history.iter().map(|entry| entry.mv.to_uci()).collect()
+    }
+
+    // How many milliseconds each move took, in the order they were played.
+    // Entries are None for games without a clock. The clock only starts with
+    // the first move, so that one always takes 0 ms.
+    pub fn move_times(&self) -> Vec<Option<u64>> {
+        self.history
+            .iter()
+            .map(|entry| entry.time_taken.map(|time| time.as_millis() as u64))
+            .collect()
+    }
+
+    // None while the game is still in progress.
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    // How the game ended, if it has.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    // True once the game has ended in any way. Not the same as having a
+    // result: an aborted game is over but has none.
+    pub fn is_over(&self) -> bool {
+        self.termination.is_some()
+    }
+
+    pub fn status(&self) -> GameStatus {
+        match (self.result, self.termination) {
+            (Some(GameResult::WhiteWins), Some(Termination::Checkmate)) => {
+                GameStatus::Checkmate(PieceColor::White)
+            }
+            (Some(GameResult::BlackWins), Some(Termination::Checkmate)) => {
+                GameStatus::Checkmate(PieceColor::Black)
+            }
+            (_, Some(Termination::Stalemate)) => GameStatus::Stalemate,
+            (_, Some(Termination::Aborted)) => GameStatus::Aborted,
+            (Some(GameResult::WhiteWins), Some(termination)) => GameStatus::Won(PieceColor::White, termination),
+            (Some(GameResult::BlackWins), Some(termination)) => GameStatus::Won(PieceColor::Black, termination),
+            (_, Some(termination)) => GameStatus::Draw(termination),
+            (_, None) => GameStatus::Ongoing,
+        }
+    }
+
+    // True if neither side can possibly checkmate. See
+    // `Board::insufficient_material` for the cases covered.
+    pub fn is_insufficient_material(&self) -> bool {
+        self.board.to_board().insufficient_material()
+    }
+
+    // True if no sequence of legal moves can end in checkmate, even though
+    // there's more than bare material left. See `Board::is_dead_position`.
+    pub fn is_dead_position(&self) -> bool {
+        self.board.to_board().is_dead_position()
+    }
+
+    // The moves the side to move may play, by the rules of the game's
+    // variant.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.variant.legal_moves(&self.board)
+    }
+
+    // The legal moves of the piece on one square, e.g. to highlight where a
+    // clicked piece can go.
+    pub fn legal_moves_from(&self, square: Square) -> Vec<Move> {
+        self.legal_moves().into_iter().filter(|mv| mv.from() == square).collect()
+    }
+
+    // The same moves spelled out as MoveDetails, so a client can tell a
+    // capture, castle or promotion apart without knowing the rules.
+    pub fn legal_move_details_from(&self, square: Square) -> Vec<MoveDetail> {
+        let board = self.board.to_board();
+        self.legal_moves_from(square)
+            .iter()
+            .filter_map(|mv| mv.detail(&board))
+            .collect()
+    }
+
+    // True if the side to move is in check.
+    pub fn is_in_check(&self) -> bool {
+        self.variant.in_check(&self.board, self.side_to_move())
+    }
+
+    // Explains why `mv` would be rejected right now, or returns None if it is
+    // legal. This is what `make_move` reports on failure, exposed separately
+    // so a UI can check a move before sending it.
+    pub fn explain_illegal(&self, mv: &Move) -> Option<MoveError> {
+        if self.is_over() {
+            return Some(MoveError::GameOver);
+        }
+        let mv = self.with_default_promotion(*mv);
+        // The variant decides what is legal. The board only knows the
+        // standard rules, so it is asked why a move is wrong only once the
+        // variant has ruled it out, and a move the standard rules allow
+        // still needs a reason of its own.
+        if self.legal_moves().iter().any(|legal| legal.same_squares(&mv)) {
+            return None;
+        }
+        Some(self.board.to_board().explain_illegal(&mv).unwrap_or(MoveError::IllegalMove(mv)))
+    }
+
+    // Plays a move for the side to move.
+    //
+    // The move only needs the right squares (and promotion piece); it is
+    // matched against the legal moves of the position, so a move parsed from
+    // "e1g1" is recognised as castling.
+    pub fn make_move(&mut self, mv: Move) -> Result<(), MoveError> {
+        self.make_move_at(mv, Instant::now())
+    }
+
+    // Like `make_move`, but with the time the move was made passed in rather
+    // than read from the system clock. Used to replay or simulate timed games.
+    // Only the clock goes by `now`; the history still records the real time
+    // of day the move came in.
+    pub fn make_move_at(&mut self, mv: Move, now: Instant) -> Result<(), MoveError> {
+        // A move made after the flag fell doesn't count.
+        if self.is_over() || self.check_time(now) {
+            return Err(MoveError::GameOver);
+        }
+        let mv = self.with_default_promotion(mv);
+        let Some(legal) = self
+            .legal_moves()
+            .into_iter()
+            .find(|candidate| candidate.same_squares(&mv))
+        else {
+            return Err(self.explain_illegal(&mv).unwrap_or(MoveError::IllegalMove(mv)));
+        };
+
+        let mover = self.side_to_move();
+        let san = legal.to_san(&self.board.to_board());
+        let pawn_move = self
+            .board
+            .piece_at(legal.from())
+            .is_some_and(|piece| piece.kind() == PieceKind::Pawn);
+        let capture = legal.flag() == MoveFlag::EnPassant || self.board.piece_at(legal.to()).is_some();
+        let before = self.board.clone();
+        self.variant.play(&mut self.board, legal);
+        let time_before = self.clock.as_ref().map(|clock| clock.remaining(mover));
+        let time_taken = self.clock.as_mut().map(|clock| clock.press(mover, now));
+        let irreversible_ply = if pawn_move || capture {
+            self.ply() + 1
+        } else {
+            self.history.last().map_or(0, |entry| entry.irreversible_ply)
+        };
+        self.history.push(HistoryEntry {
+            mv: legal,
+            san: san.clone(),
+            before,
+            time_taken,
+            clock_remaining: self.clock.as_ref().map(|clock| clock.remaining(mover)),
+            played_at: SystemTime::now(),
+            position_key: position_key(&self.board),
+            irreversible_ply,
+        });
+        self.undone.clear();
+        // Playing on withdraws any pending reset request, and moving instead
+        // of answering a draw offer declines it.
+        self.reset_requests = [false; 2];
+        if self.draw_offer == Some(mover.opposite()) {
+            self.draw_offer = None;
+        }
+        self.update_result();
+
+        self.emit(GameEvent::MovePlayed { color: mover, mv: legal, san });
+        if self.is_in_check() {
+            self.emit(GameEvent::CheckGiven { color: mover.opposite() });
+        }
+        if let (Some(before), Some(clock)) = (time_before, &self.clock) {
+            let after = clock.remaining(mover);
+            if before >= CLOCK_LOW_THRESHOLD && after < CLOCK_LOW_THRESHOLD {
+                self.emit(GameEvent::ClockLow { color: mover, remaining_ms: after.as_millis() as u64 });
+            }
+        }
+        if let Some(termination) = self.termination {
+            self.emit(GameEvent::GameEnded { result: self.result, termination });
+        }
+        Ok(())
+    }
+
+    // Takes back the last move, returning it, or None at the start of the
+    // game. A mate, stalemate or automatic draw is undone with it; a game a
+    // player or the clock ended stays over, and nothing is taken back. The
+    // clock is left alone: time spent thinking stays spent.
+    pub fn undo(&mut self) -> Option<Move> {
+        if self.termination.is_some_and(|termination| !termination.follows_from_position()) {
+            return None;
+        }
+        let entry = self.history.pop()?;
+        self.board = entry.before.clone();
+        self.draw_offer = None;
+        self.result = None;
+        self.termination = None;
+        let mv = entry.mv;
+        self.undone.push(entry);
+        Some(mv)
+    }
+
+    // Plays the last undone move again, returning it, or None if nothing has
+    // been undone since the last move was played or the game has since ended.
+    // Observers aren't told: stepping through moves again isn't news.
+    pub fn redo(&mut self) -> Option<Move> {
+        if self.is_over() {
+            return None;
+        }
+        let entry = self.undone.pop()?;
+        self.variant.play(&mut self.board, entry.mv);
+        let mv = entry.mv;
+        self.history.push(entry);
+        self.update_result();
+        Some(mv)
+    }
+
+    // Undoes or redoes moves until the game stands at `ply`. Any ply from the
+    // start up to the last undone move can be reached, unless a player or the
+    // clock has ended the game.
+    pub fn jump_to_ply(&mut self, ply: usize) -> Result<(), GameError> {
+        let max = self.ply() + self.undone.len();
+        if ply > max {
+            return Err(GameError::PlyOutOfRange { ply, max });
+        }
+        while self.ply() > ply {
+            self.undo().ok_or(GameError::GameOver)?;
+        }
+        while self.ply() < ply {
+            self.redo().ok_or(GameError::GameOver)?;
+        }
+        Ok(())
+    }
+
+    // Plays a move written in UCI notation, e.g. "e2e4" or "e7e8q". Castling
+    // and en passant are recognised from the squares, so "e1g1" castles.
+    pub fn make_move_uci(&mut self, uci: &str) -> Result<(), MoveError> {
+        let mv = Move::from_uci(uci).ok_or_else(|| MoveError::InvalidNotation(uci.to_string()))?;
+        self.make_move(mv)
+    }
+
+    // Plays a move written in SAN, e.g. "Nf3" or "exd5".
+    pub fn make_move_san(&mut self, san: &str) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        let mv = Move::from_san(san, &self.board.to_board())?;
+        self.make_move(mv)
+    }
+
+    // Plays a move typed by a person, read by `Move::parse_lenient`.
+    pub fn make_move_lenient(&mut self, text: &str) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        let mv = Move::parse_lenient(text, &self.board.to_board())?;
+        self.make_move(mv)
+    }
+
+    // The board as text for a terminal client: the board's Display diagram,
+    // with rank numbers and file letters, and a line saying whose turn it
+    // is, e.g. "White to move (move 1)".
+    pub fn to_ascii_with_coordinates(&self) -> String {
+        let board = self.board.to_board();
+        let mut out = board.to_string();
+        let side = match self.side_to_move() {
+            PieceColor::White => "White",
+            PieceColor::Black => "Black",
+        };
+        out.push_str(&format!("{} to move (move {})\n", side, board.fullmove_number()));
+        out
+    }
+
+    // True once 50 moves by each side have gone by without a pawn move or
+    // capture. Either player may then claim a draw, but the game isn't over
+    // until they do (or until the 75-move rule ends it).
+    pub fn fifty_move_rule_reached(&self) -> bool {
+        self.board.halfmove_clock() >= 100
+    }
+
+    // True if the current position has occurred at least three times with
+    // the same player to move, castling rights and en passant options, which
+    // lets either player claim a draw. Only positions since the last pawn move
+    // or capture are compared: those moves can't be undone, so nothing
+    // earlier can repeat.
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    // How many times the current position has occurred, counting this one.
+    pub fn repetition_count(&self) -> usize {
+        let Some(last) = self.history.last() else {
+            return 1;
+        };
+        let since = last.irreversible_ply;
+        // Position i (for i >= 1) is the one reached by history[i - 1]; the
+        // start position only counts when nothing irreversible has happened.
+        let mut count = self.history[since.saturating_sub(1)..]
+            .iter()
+            .filter(|entry| entry.position_key == last.position_key)
+            .count();
+        if since == 0 && position_key(&self.start) == last.position_key {
+            count += 1;
+        }
+        count
+    }
+
+    // Ends the game if the rules of its variant say it is over: for
+    // standard chess, on mate, stalemate and the draws that need no claim.
+    // See `standard_game_end`.
+    fn update_result(&mut self) {
+        if let Some((result, termination)) = self.variant.game_end(self) {
+            self.result = Some(result);
+            self.termination = Some(termination);
+        }
+    }
+
+    pub(crate) fn finish(&mut self, result: Option<GameResult>, termination: Termination) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        self.result = result;
+        self.termination = Some(termination);
+        self.emit(GameEvent::GameEnded { result, termination });
+        Ok(())
+    }
+
+    // `color` gives up; the opponent wins.
+    pub fn resign(&mut self, color: PieceColor) -> Result<(), GameError> {
+        self.finish(Some(GameResult::win_for(color.opposite())), Termination::Resignation)
+    }
+
+    // `color` has left the game for good; the opponent wins.
+    pub fn abandon(&mut self, color: PieceColor) -> Result<(), GameError> {
+        self.finish(Some(GameResult::win_for(color.opposite())), Termination::Abandonment)
+    }
+
+    // Both players have agreed to a draw.
+    pub fn agree_draw(&mut self) -> Result<(), GameError> {
+        self.draw_offer = None;
+        self.finish(Some(GameResult::Draw), Termination::Agreement)
+    }
+
+    // The player whose draw offer is waiting for an answer, if any.
+    pub fn draw_offer(&self) -> Option<PieceColor> {
+        self.draw_offer
+    }
+
+    // `color` offers a draw. The offer stands until the opponent answers it
+    // with `respond_draw` or plays a move. If the opponent had already
+    // offered one, the two offers meet and the game is drawn.
+    pub fn offer_draw(&mut self, color: PieceColor) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        if self.draw_offer == Some(color.opposite()) {
+            return self.agree_draw();
+        }
+        self.draw_offer = Some(color);
+        self.emit(GameEvent::DrawOffered { color });
+        Ok(())
+    }
+
+    // Accepts or declines the open draw offer.
+    pub fn respond_draw(&mut self, accepted: bool) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        if self.draw_offer.take().is_none() {
+            return Err(GameError::NoDrawOffer);
+        }
+        if accepted {
+            self.agree_draw()
+        } else {
+            Ok(())
+        }
+    }
+
+    // Calls the game off without a result. Only allowed until both sides
+    // have made a move.
+    pub fn abort(&mut self) -> Result<(), GameError> {
+        if !self.is_over() && self.ply() >= 2 {
+            return Err(GameError::TooLateToAbort);
+        }
+        self.finish(None, Termination::Aborted)
+    }
+
+    // Draws the game on the claimed grounds, after checking that they hold
+    // in the current position.
+    pub fn claim_draw(&mut self, claim: DrawClaim) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        let (valid, termination) = match claim {
+            DrawClaim::ThreefoldRepetition => (self.is_threefold_repetition(), Termination::ThreefoldRepetition),
+            DrawClaim::FiftyMoveRule => (self.fifty_move_rule_reached(), Termination::FiftyMoveRule),
+        };
+        if !valid {
+            return Err(GameError::DrawClaimRejected(claim));
+        }
+        self.finish(Some(GameResult::Draw), termination)
+    }
+
+    // Ends the game on time if the side to move has used up their clock by
+    // `now`. Returns true if it did. The clock itself only charges time when
+    // it is pressed, so this has to be asked.
+    pub fn check_time(&mut self, now: Instant) -> bool {
+        let side = self.side_to_move();
+        let flagged = self.clock.as_ref().is_some_and(|clock| clock.out_of_time(side, now));
+        flagged && self.finish(Some(GameResult::win_for(side.opposite())), Termination::Timeout).is_ok()
+    }
+
+    // Puts the game back to its variant's starting position: history,
+    // clocks, result and pending requests are all cleared. The mode, the
+    // players and the variant are kept.
+    pub fn reset(&mut self) {
+        self.board = self.variant.start_position();
+        self.start = self.variant.start_position();
+        self.history.clear();
+        self.undone.clear();
+        self.draw_offer = None;
+        self.result = None;
+        self.termination = None;
+        if let Some(clock) = &mut self.clock {
+            clock.reset();
+        }
+        self.reset_requests = [false; 2];
+    }
+
+    // Asks to reset the game on behalf of `color`.
+    //
+    // Analysis games, games that haven't started and finished games reset
+    // straight away. In the middle of a standard game both players have to
+    // ask; the first request is remembered and `ResetNotAgreed` is returned
+    // until the opponent asks too.
+    pub fn request_reset(&mut self, color: PieceColor) -> Result<(), GameError> {
+        let in_progress = !self.history.is_empty() && !self.is_over();
+        if self.mode == GameMode::Analysis || !in_progress {
+            self.reset();
+            return Ok(());
+        }
+
+        self.reset_requests[color.index()] = true;
+        if self.reset_requests.iter().all(|&requested| requested) {
+            self.reset();
+            Ok(())
+        } else {
+            Err(GameError::ResetNotAgreed)
+        }
+    }
+}
+
+// Identifies a position for repetition purposes: the FEN without the move
+// counters. Following FIDE, an en passant square only makes a difference when
+// the capture is actually possible.
+fn position_key<B: BoardRepr>(board: &B) -> String {
+    let fen = board.to_board().to_fen();
+    let mut fields: Vec<&str> = fen.split(' ').take(4).collect();
+    let can_capture_en_passant = board
+        .legal_moves()
+        .iter()
+        .any(|mv| mv.flag() == MoveFlag::EnPassant);
+    if !can_capture_en_passant {
+        fields[3] = "-";
+    }
+    fields.join(" ")
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+// A game is saved as where it started and the moves played since, and loaded
+// by replaying them. That keeps saved games small, and since each move goes
+// through make_move again, a tampered file can't produce an illegal game.
+// The SAN, result and repetition data all come back from the replay.
+//
+// The clock is left out: it measures real time since the last move, which
+// means nothing once the game has been stored and loaded again. What it
+// recorded for each move is kept, and put back after the replay.
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    start: Board,
+    moves: Vec<Move>,
+    mode: GameMode,
+    auto_queen: bool,
+    // Missing from games saved before games could be rated.
+    #[serde(default)]
+    rated: bool,
+    // Missing from games saved before players had names.
+    #[serde(default)]
+    players: [Option<String>; 2],
+    // How the game ended when that can't be told from the moves, such as a
+    // resignation. Both None otherwise.
+    #[serde(default)]
+    result: Option<GameResult>,
+    #[serde(default)]
+    termination: Option<Termination>,
+    // The variant's name. Missing from games saved before there were
+    // variants, which were all standard.
+    #[serde(default = "standard_variant_name")]
+    variant: String,
+    // The start position in the variant's FEN, for variants that keep state
+    // there that `start` can't hold, such as the checks already given in
+    // Three-check. None when it would say no more than `start`.
+    #[serde(default)]
+    start_fen: Option<String>,
+    // Each move's HistoryEntry timings, in milliseconds, one per move.
+    // Missing from games saved before they were kept, which load with the
+    // moves timestamped as they are replayed and no clock readings.
+    #[serde(default)]
+    played_at_ms: Vec<u64>,
+    #[serde(default)]
+    time_taken_ms: Vec<Option<u64>>,
+    #[serde(default)]
+    clock_remaining_ms: Vec<Option<u64>>,
+}
+
+fn standard_variant_name() -> String {
+    Variant::<Board>::name(&Standard).to_string()
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Endings the rules decide are found again when the moves are
+        // replayed.
+        let by_player = !matches!(
+            self.termination,
+            None | Some(
+                Termination::Checkmate
+                    | Termination::Stalemate
+                    | Termination::InsufficientMaterial
+                    | Termination::DeadPosition
+                    | Termination::SeventyFiveMoveRule
+                    | Termination::FivefoldRepetition
+                    | Termination::VariantEnd
+            )
+        );
+        let start_fen = Game::from_board_with_variant(self.start.clone(), self.variant.clone()).variant_fen();
+        SavedGame {
+            start_fen: (start_fen != self.start.to_fen()).then_some(start_fen),
+            start: self.start.clone(),
+            moves: self.history.iter().map(|entry| entry.mv).collect(),
+            mode: self.mode,
+            auto_queen: self.auto_queen,
+            rated: self.rated,
+            players: self.players.clone(),
+            result: if by_player { self.result } else { None },
+            termination: if by_player { self.termination } else { None },
+            variant: self.variant.name().to_string(),
+            played_at_ms: self
+                .history
+                .iter()
+                .map(|entry| entry.played_at.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64))
+                .collect(),
+            time_taken_ms: self.history.iter().map(|entry| entry.time_taken.map(|time| time.as_millis() as u64)).collect(),
+            clock_remaining_ms: self
+                .history
+                .iter()
+                .map(|entry| entry.clock_remaining.map(|time| time.as_millis() as u64))
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = SavedGame::deserialize(deserializer)?;
+        let mut variant = variant_by_name(&saved.variant)
+            .ok_or_else(|| de::Error::custom(format!("unknown variant '{}'", saved.variant)))?;
+        if let Some(fen) = &saved.start_fen {
+            let (_, configured) = variant.read_fen(fen).map_err(|error| de::Error::custom(format!("start: {}", error)))?;
+            variant = configured.unwrap_or(variant);
+        }
+        let mut game = Game::from_board_with_variant(saved.start, variant);
+        game.set_mode(saved.mode);
+        game.set_auto_queen(saved.auto_queen);
+        game.set_rated(saved.rated);
+        game.players = saved.players;
+        for (i, mv) in saved.moves.into_iter().enumerate() {
+            game.make_move(mv)
+                .map_err(|error| de::Error::custom(format!("move {} ({}): {}", i + 1, mv, error)))?;
+        }
+        for (entry, &ms) in game.history.iter_mut().zip(&saved.played_at_ms) {
+            entry.played_at = UNIX_EPOCH + Duration::from_millis(ms);
+        }
+        for (entry, &ms) in game.history.iter_mut().zip(&saved.time_taken_ms) {
+            entry.time_taken = ms.map(Duration::from_millis);
+        }
+        for (entry, &ms) in game.history.iter_mut().zip(&saved.clock_remaining_ms) {
+            entry.clock_remaining = ms.map(Duration::from_millis);
+        }
+        if let (false, Some(termination)) = (game.is_over(), saved.termination) {
+            game.result = saved.result;
+            game.termination = Some(termination);
+        }
+        Ok(game)
+    }
 }
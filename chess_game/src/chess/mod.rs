@@ -6,7 +6,52 @@
 //! - Move validation and execution
 //! - Game state management
 
+pub mod bitboard;
 pub mod board;
+pub mod builder;
 pub mod piece;
-pub mod move;
+pub mod r#move;
 pub mod game;
+pub mod square;
+pub mod fen;
+pub mod clock;
+pub mod epd;
+pub mod events;
+pub mod openings;
+pub mod pawns;
+pub mod perft;
+pub mod pgn;
+pub mod psqt;
+pub mod tactics;
+pub mod zobrist;
+pub mod render;
+pub mod tree;
+pub mod validate;
+pub mod variant;
+
+pub use bitboard::{
+    bishop_attacks, rook_attacks, BitBoards, Bitboard, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS,
+};
+pub use board::{king_attacks, knight_attacks, pawn_attacks, Board, BoardRepr, CastlingRights};
+pub use builder::BoardBuilder;
+pub use clock::Clock;
+pub use epd::{Epd, EpdError};
+pub use events::{GameEvent, GameObserver, CLOCK_LOW_THRESHOLD};
+pub use fen::{normalize_fen, FenError, START_FEN};
+pub use game::{DrawClaim, Game, GameError, GameMode, GameResult, GameStatus, Termination};
+pub use openings::{Opening, OpeningMatch, Openings};
+pub use pawns::PawnStructure;
+pub use perft::{perft, perft_divide};
+pub use pgn::{PgnError, PgnErrorKind, PgnGame, PgnMove};
+pub use piece::{InvalidPieceByte, InvalidPieceChar, Piece, PieceColor, PieceKind};
+pub use render::{piece_glyph, UnicodeBoard};
+pub use r#move::{Move, MoveDetail, MoveError, MoveFlag};
+pub use square::Square;
+pub use tactics::Motif;
+pub use tree::{GameTree, Node, NodeId};
+pub use validate::PositionError;
+pub use variant::{
+    standard_game_end, variant_by_name, variants, Antichess, Atomic, ConfiguredVariant, Horde, KingOfTheHill, Standard, ThreeCheck,
+    Variant,
+    ANTICHESS_PROMOTIONS, STANDARD_PROMOTIONS,
+};
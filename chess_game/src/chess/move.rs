@@ -6,10 +6,1086 @@
 //! - Methods for applying and undoing moves
 //! - Special move handling (castling, promotion, etc.)
 
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::chess::bitboard::Bitboard;
+use crate::chess::board::{
+    king_attacks, knight_attacks, pawn_attacks, Board, CastlingRights, BISHOP_DIRECTIONS,
+    KING_OFFSETS, KNIGHT_OFFSETS, ROOK_DIRECTIONS,
+};
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::square::Square;
+
+// Most moves just pick a piece up and put it down somewhere else, but a few
+// also touch other squares. The flag tells make/unmake which extra work to do.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveFlag {
+    Normal,
+    // A pawn advancing two squares, which creates an en passant target.
+    DoublePawnPush,
+    // A pawn capturing a pawn that is *not* on the destination square.
+    EnPassant,
+    // The king moving two squares; the rook jumps over it.
+    CastleKingside,
+    CastleQueenside,
+}
+
+// The search creates and copies millions of moves, so like Piece and Square
+// a Move is packed as tightly as it will go: two bytes.
+//
+// Bit position: 15 14 13 12 | 11 10 9 8 7 6 | 5 4 3 2 1 0
+//               +---------+ | +-----------+ | +---------+
+//                  code     |   to square   | from square
+//
+// The 4-bit code says what else the move does:
+//   0 normal, 1 double pawn push, 2 en passant,
+//   3 castle kingside, 4 castle queenside,
+//   8 + kind for a promotion (a promotion is never any of the others).
+//
+// For the API layer, where size doesn't matter, MoveDetail spells everything
+// out.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Move {
-    // TODO: Implement move representation
+    data: u16,
+}
+
+const PROMOTION_CODE: u16 = 8;
+
+impl Move {
+    fn pack(from: Square, to: Square, code: u16) -> Self {
+        Move { data: from.index() as u16 | (to.index() as u16) << 6 | code << 12 }
+    }
+
+    // Creates a plain move. Moves parsed from user input start out like this
+    // and are matched against the legal move list to pick up their flag.
+    pub fn new(from: Square, to: Square) -> Self {
+        Move::pack(from, to, 0)
+    }
+
+    // Creates a pawn move that promotes to `kind` on arrival.
+    pub fn with_promotion(from: Square, to: Square, kind: PieceKind) -> Self {
+        Move::pack(from, to, PROMOTION_CODE | kind as u16)
+    }
+
+    pub(crate) fn with_flag(from: Square, to: Square, flag: MoveFlag) -> Self {
+        let code = match flag {
+            MoveFlag::Normal => 0,
+            MoveFlag::DoublePawnPush => 1,
+            MoveFlag::EnPassant => 2,
+            MoveFlag::CastleKingside => 3,
+            MoveFlag::CastleQueenside => 4,
+        };
+        Move::pack(from, to, code)
+    }
+
+    fn code(&self) -> u16 {
+        self.data >> 12
+    }
+
+    // The packed 16 bits, for compact storage.
+    pub fn to_raw(self) -> u16 {
+        self.data
+    }
+
+    // Rebuilds a move from `to_raw`'s 16 bits. Any squares will do, but the
+    // code has to be one of the ones listed above, and a promotion has to be
+    // to a knight, bishop, rook, queen or (for Antichess) king.
+    pub fn from_raw(data: u16) -> Option<Move> {
+        match data >> 12 {
+            0..=4 | 9..=13 => Some(Move { data }),
+            _ => None,
+        }
+    }
+
+    pub fn from(&self) -> Square {
+        Square::from_index((self.data & 0x3f) as usize).expect("six bits always name a square")
+    }
+
+    pub fn to(&self) -> Square {
+        Square::from_index((self.data >> 6 & 0x3f) as usize).expect("six bits always name a square")
+    }
+
+    pub fn promotion(&self) -> Option<PieceKind> {
+        if self.code() & PROMOTION_CODE == 0 {
+            return None;
+        }
+        Some(match self.code() & 0b111 {
+            0 => PieceKind::Pawn,
+            1 => PieceKind::Knight,
+            2 => PieceKind::Bishop,
+            3 => PieceKind::Rook,
+            4 => PieceKind::Queen,
+            _ => PieceKind::King,
+        })
+    }
+
+    pub fn flag(&self) -> MoveFlag {
+        match self.code() {
+            1 => MoveFlag::DoublePawnPush,
+            2 => MoveFlag::EnPassant,
+            3 => MoveFlag::CastleKingside,
+            4 => MoveFlag::CastleQueenside,
+            _ => MoveFlag::Normal,
+        }
+    }
+
+    // True if both moves go between the same squares with the same promotion,
+    // ignoring the flag. This is how user input is matched to a legal move.
+    pub fn same_squares(&self, other: &Move) -> bool {
+        self.from() == other.from() && self.to() == other.to() && self.promotion() == other.promotion()
+    }
+
+    // Everything about the move spelled out for clients, as played on
+    // `board`. None if there's no piece on the from-square.
+    pub fn detail(&self, board: &Board) -> Option<MoveDetail> {
+        let piece = board.piece_at(self.from())?;
+        let captured = match self.flag() {
+            MoveFlag::EnPassant => Some(Piece::new(PieceKind::Pawn, piece.color().opposite())),
+            _ => board.piece_at(self.to()),
+        };
+        Some(MoveDetail {
+            from: self.from(),
+            to: self.to(),
+            piece,
+            captured,
+            promotion: self.promotion(),
+            flag: self.flag(),
+            uci: self.to_uci(),
+            san: self.to_san(board),
+        })
+    }
+
+    // Parses UCI long algebraic notation: "e2e4", or "e7e8q" for a promotion.
+    pub fn from_uci(text: &str) -> Option<Move> {
+        if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+            return None;
+        }
+        let from = Square::from_algebraic(&text[0..2])?;
+        let to = Square::from_algebraic(&text[2..4])?;
+        match text[4..].chars().next() {
+            None => Some(Move::new(from, to)),
+            Some(c) => {
+                let kind = match c {
+                    'q' => PieceKind::Queen,
+                    'r' => PieceKind::Rook,
+                    'b' => PieceKind::Bishop,
+                    'n' => PieceKind::Knight,
+                    'k' => PieceKind::King,
+                    _ => return None,
+                };
+                Some(Move::with_promotion(from, to, kind))
+            }
+        }
+    }
+
+    // Formats the move in UCI notation, the inverse of `from_uci`.
+    pub fn to_uci(&self) -> String {
+        self.to_string()
+    }
+
+    // Formats the move in Standard Algebraic Notation ("Nf3", "exd5", "O-O",
+    // "e8=Q+") as it would be written when played on `board`.
+    //
+    // SAN only names the from-square when another piece of the same kind
+    // could also reach the destination: the file if that's enough to tell
+    // them apart, otherwise the rank, otherwise both.
+    pub fn to_san(&self, board: &Board) -> String {
+        // A move parsed from UCI doesn't know it is castling or en passant
+        // until it is matched against the position.
+        let legal_moves = board.legal_moves();
+        let mv = legal_moves
+            .iter()
+            .find(|legal| legal.same_squares(self))
+            .copied()
+            .unwrap_or(*self);
+        let Some(piece) = board.piece_at(mv.from()) else {
+            return mv.to_uci();
+        };
+
+        let mut san = match mv.flag() {
+            MoveFlag::CastleKingside => "O-O".to_string(),
+            MoveFlag::CastleQueenside => "O-O-O".to_string(),
+            _ => {
+                let is_capture = mv.flag() == MoveFlag::EnPassant || board.piece_at(mv.to()).is_some();
+                let mut san = String::new();
+                if piece.kind() == PieceKind::Pawn {
+                    if is_capture {
+                        san.push((b'a' + mv.from().file()) as char);
+                    }
+                } else {
+                    san.push(piece_letter(piece.kind()));
+                    let rivals: Vec<Square> = legal_moves
+                        .iter()
+                        .filter(|other| other.to() == mv.to() && other.from() != mv.from())
+                        .filter(|other| {
+                            board.piece_at(other.from()).is_some_and(|p| p.kind() == piece.kind())
+                        })
+                        .map(|other| other.from())
+                        .collect();
+                    if !rivals.is_empty() {
+                        let file_unique = rivals.iter().all(|sq| sq.file() != mv.from().file());
+                        let rank_unique = rivals.iter().all(|sq| sq.rank() != mv.from().rank());
+                        let from = mv.from().to_string();
+                        if file_unique {
+                            san.push_str(&from[..1]);
+                        } else if rank_unique {
+                            san.push_str(&from[1..]);
+                        } else {
+                            san.push_str(&from);
+                        }
+                    }
+                }
+                if is_capture {
+                    san.push('x');
+                }
+                san.push_str(&mv.to().to_string());
+                if let Some(kind) = mv.promotion() {
+                    san.push('=');
+                    san.push(piece_letter(kind));
+                }
+                san
+            }
+        };
+
+        let mut after = board.clone();
+        after.make_move(mv);
+        if after.king_in_check(after.side_to_move()) {
+            san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    // Parses a move written in SAN, as `to_san` would write it for `board`.
+    // Check and annotation marks ("+", "#", "!", "?") are optional, and
+    // castling may be written with zeros ("0-0"). The move is found among the
+    // legal moves, so it comes back with the right flag already set.
+    //
+    // Other programs don't always write SAN quite the way we do, so it is
+    // read loosely: more disambiguation than needed ("Ngf3", "Ng1f3"), a
+    // missing "x" or "=" ("ed5", "e8Q") and a trailing "e.p." are all fine,
+    // as long as exactly one legal move fits. The piece, squares and
+    // promotion are matched against the legal moves directly; only when
+    // several fit is each written out in full to see which the text names.
+    pub fn from_san(text: &str, board: &Board) -> Result<Move, MoveError> {
+        let invalid = || MoveError::InvalidNotation(text.to_string());
+        let strip = |san: &str| san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+        let wanted = strip(text.trim().trim_end_matches("e.p.").trim_end());
+        let legal = board.legal_moves();
+
+        let castle = match wanted.as_str() {
+            "O-O" => Some(MoveFlag::CastleKingside),
+            "O-O-O" => Some(MoveFlag::CastleQueenside),
+            _ => None,
+        };
+        if let Some(flag) = castle {
+            return legal.into_iter().find(|mv| mv.flag() == flag).ok_or_else(invalid);
+        }
+
+        let pattern = SanPattern::parse(&wanted).ok_or_else(invalid)?;
+        let candidates: Vec<Move> = legal.into_iter().filter(|mv| pattern.matches(mv, board)).collect();
+        match candidates.as_slice() {
+            [mv] => Ok(*mv),
+            _ => candidates.into_iter().find(|mv| strip(&mv.to_san(board)) == wanted).ok_or_else(invalid),
+        }
+    }
 }
 
 impl Move {
-    // TODO: Implement move methods
+    // Reads a move the way a person might type it into a chat box: in UCI or
+    // SAN, in any case, with or without hyphens, "x", "=", check marks and
+    // annotations. "nf3", "E4", "0-0", "e2-e4" and "Qd1xd8+" all work.
+    //
+    // If the input fits more than one legal move ("b4" in a position where a
+    // bishop and a pawn can both get there, say) the candidates come back in
+    // MoveError::Ambiguous, so the client can ask which was meant.
+    pub fn parse_lenient(text: &str, board: &Board) -> Result<Move, MoveError> {
+        let invalid = || MoveError::InvalidNotation(text.to_string());
+        let cleaned: String = text
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let legal = board.legal_moves();
+
+        let castle = match cleaned.replace('0', "o").as_str() {
+            "oo" => Some(MoveFlag::CastleKingside),
+            "ooo" => Some(MoveFlag::CastleQueenside),
+            _ => None,
+        };
+        if let Some(flag) = castle {
+            return legal.into_iter().find(|mv| mv.flag() == flag).ok_or_else(invalid);
+        }
+
+        let mut candidates: Vec<Move> = match Move::from_uci(&cleaned.replace('x', "")) {
+            Some(typed) => legal
+                .iter()
+                .filter(|mv| mv.from() == typed.from() && mv.to() == typed.to())
+                .filter(|mv| typed.promotion().is_none() || mv.promotion() == typed.promotion())
+                .copied()
+                .collect(),
+            None => {
+                // SAN wants its piece and promotion letters in uppercase. A
+                // leading "b" could be a bishop or the b-pawn, so try both,
+                // unless the player bothered to write a capital letter.
+                let mut san = cleaned.clone();
+                if let Some(last) = san.pop() {
+                    san.push(if "nbrq".contains(last) { last.to_ascii_uppercase() } else { last });
+                }
+                let mut readings = Vec::new();
+                if !text.trim().starts_with(['N', 'B', 'R', 'Q', 'K']) {
+                    readings.push(san.clone());
+                }
+                if san.starts_with(['n', 'b', 'r', 'q', 'k']) {
+                    readings.push(san[..1].to_ascii_uppercase() + &san[1..]);
+                }
+                let patterns: Vec<SanPattern> = readings.iter().filter_map(|san| SanPattern::parse(san)).collect();
+                legal
+                    .iter()
+                    .filter(|mv| patterns.iter().any(|pattern| pattern.matches(mv, board)))
+                    .copied()
+                    .collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(invalid()),
+            1 => Ok(candidates[0]),
+            _ => {
+                candidates.sort();
+                Err(MoveError::Ambiguous(candidates))
+            }
+        }
+    }
+}
+
+// The pieces of a (non-castling) SAN move, for matching loosely written SAN
+// against legal moves.
+struct SanPattern {
+    kind: PieceKind,
+    from_file: Option<u8>,
+    from_rank: Option<u8>,
+    to: Square,
+    promotion: Option<PieceKind>,
+}
+
+impl SanPattern {
+    fn parse(san: &str) -> Option<SanPattern> {
+        if !san.is_ascii() {
+            return None;
+        }
+        let mut rest = san.replace(['x', '='], "");
+
+        let promotion = match rest.chars().last()? {
+            c @ ('N' | 'B' | 'R' | 'Q' | 'K') => {
+                rest.pop();
+                Some(piece_from_letter(c)?)
+            }
+            _ => None,
+        };
+        let kind = match rest.chars().next()? {
+            c if c.is_ascii_uppercase() => {
+                rest.remove(0);
+                piece_from_letter(c)?
+            }
+            _ => PieceKind::Pawn,
+        };
+
+        // What's left is an optional from-file and/or from-rank, then the
+        // destination square.
+        if rest.len() < 2 {
+            return None;
+        }
+        let to = Square::from_algebraic(&rest[rest.len() - 2..])?;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in rest[..rest.len() - 2].bytes() {
+            match c {
+                b'a'..=b'h' if from_file.is_none() && from_rank.is_none() => from_file = Some(c - b'a'),
+                b'1'..=b'8' if from_rank.is_none() => from_rank = Some(c - b'1'),
+                _ => return None,
+            }
+        }
+        Some(SanPattern { kind, from_file, from_rank, to, promotion })
+    }
+
+    fn matches(&self, mv: &Move, board: &Board) -> bool {
+        mv.to() == self.to
+            && mv.promotion() == self.promotion
+            && board.piece_at(mv.from()).is_some_and(|piece| piece.kind() == self.kind)
+            && self.from_file.is_none_or(|file| mv.from().file() == file)
+            && self.from_rank.is_none_or(|rank| mv.from().rank() == rank)
+            && !matches!(mv.flag(), MoveFlag::CastleKingside | MoveFlag::CastleQueenside)
+    }
+}
+
+// The inverse of `piece_letter`, for the pieces SAN names.
+fn piece_from_letter(letter: char) -> Option<PieceKind> {
+    match letter {
+        'N' => Some(PieceKind::Knight),
+        'B' => Some(PieceKind::Bishop),
+        'R' => Some(PieceKind::Rook),
+        'Q' => Some(PieceKind::Queen),
+        'K' => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+// The uppercase letter SAN uses for a piece ('P' is never actually written).
+fn piece_letter(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::Pawn => 'P',
+        PieceKind::Knight => 'N',
+        PieceKind::Bishop => 'B',
+        PieceKind::Rook => 'R',
+        PieceKind::Queen => 'Q',
+        PieceKind::King => 'K',
+    }
+}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Move")
+            .field("from", &self.from())
+            .field("to", &self.to())
+            .field("promotion", &self.promotion())
+            .field("flag", &self.flag())
+            .finish()
+    }
+}
+
+// A move with everything a client might want to show about it. Unlike Move
+// this is big, so it is only built at the edges of the program.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct MoveDetail {
+    pub from: Square,
+    pub to: Square,
+    // The piece that moves.
+    pub piece: Piece,
+    pub captured: Option<Piece>,
+    pub promotion: Option<PieceKind>,
+    pub flag: MoveFlag,
+    pub uci: String,
+    pub san: String,
+}
+
+// Moves sort by origin square, then destination, then promotion piece
+// (knight up to queen), so a sorted move list comes out the same every time
+// and is easy to scan. Comparing the raw u16 would mix the squares' bits up
+// and put the flag last, which is a meaningless order.
+impl Ord for Move {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |mv: &Move| (mv.from(), mv.to(), mv.data >> 12);
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for Move {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from(), self.to())?;
+        if let Some(kind) = self.promotion() {
+            let c = match kind {
+                PieceKind::Queen => 'q',
+                PieceKind::Rook => 'r',
+                PieceKind::Bishop => 'b',
+                PieceKind::Knight => 'n',
+                // Only Antichess lets a pawn become a king.
+                PieceKind::King => 'k',
+                // Pawns are never promotion targets.
+                PieceKind::Pawn => '?',
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+// `"e2e4".parse::<Move>()` reads UCI, like `from_uci`.
+impl FromStr for Move {
+    type Err = MoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Move::from_uci(s).ok_or_else(|| MoveError::InvalidNotation(s.to_string()))
+    }
+}
+
+// UCI is the wire format for moves, so in JSON a move is just its UCI string.
+// Binary formats, which nobody reads by eye, get the packed u16 instead.
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u16(self.data)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let text = String::deserialize(deserializer)?;
+            text.parse().map_err(de::Error::custom)
+        } else {
+            let data = u16::deserialize(deserializer)?;
+            Move::from_raw(data).ok_or_else(|| de::Error::custom(format!("invalid move encoding {:#06x}", data)))
+        }
+    }
+}
+
+// Why a move was refused. Each variant is specific enough to tell the player
+// what they did wrong, rather than just "illegal move".
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    // The text could not be parsed as a move at all.
+    InvalidNotation(String),
+    // The game has already finished.
+    GameOver,
+    // There is nothing on the square the move starts from.
+    NoPieceOnSquare(Square),
+    // The piece on the from-square belongs to the side not on move.
+    NotYourTurn,
+    // The destination holds one of the mover's own pieces.
+    OccupiedByOwnPiece,
+    // The piece doesn't move that way (a knight moving like a bishop, a pawn
+    // moving diagonally without capturing, ...).
+    PieceCannotReach,
+    // The piece moves that way, but something is in the way.
+    BlockedPath,
+    // A pawn reached the last rank without saying what to promote to.
+    MissingPromotion,
+    // A promotion piece was given for a move that can't promote, or the
+    // piece is not one a pawn may become.
+    InvalidPromotion,
+    // Castling isn't possible: rights lost, squares occupied, or the king is
+    // in or passes through check.
+    CastlingNotAllowed,
+    // The move would leave the mover's own king in check.
+    LeavesKingInCheck,
+    // Fallback for anything not covered above.
+    IllegalMove(Move),
+    // Loosely written input that fits more than one legal move; these are
+    // the candidates.
+    Ambiguous(Vec<Move>),
+}
+
+impl MoveError {
+    // The variant as sent to clients, e.g. "leaves_king_in_check", so they
+    // can react to the reason without parsing the message.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            MoveError::InvalidNotation(_) => "invalid_notation",
+            MoveError::GameOver => "game_over",
+            MoveError::NoPieceOnSquare(_) => "no_piece_on_square",
+            MoveError::NotYourTurn => "not_your_turn",
+            MoveError::OccupiedByOwnPiece => "occupied_by_own_piece",
+            MoveError::PieceCannotReach => "piece_cannot_reach",
+            MoveError::BlockedPath => "blocked_path",
+            MoveError::MissingPromotion => "missing_promotion",
+            MoveError::InvalidPromotion => "invalid_promotion",
+            MoveError::CastlingNotAllowed => "castling_not_allowed",
+            MoveError::LeavesKingInCheck => "leaves_king_in_check",
+            MoveError::IllegalMove(_) => "illegal_move",
+            MoveError::Ambiguous(_) => "ambiguous",
+        }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::InvalidNotation(text) => write!(f, "'{}' is not a valid move", text),
+            MoveError::GameOver => write!(f, "the game is already over"),
+            MoveError::NoPieceOnSquare(square) => write!(f, "there is no piece on {}", square),
+            MoveError::NotYourTurn => write!(f, "it is not your turn"),
+            MoveError::OccupiedByOwnPiece => write!(f, "you can't capture your own piece"),
+            MoveError::PieceCannotReach => write!(f, "that piece can't move there"),
+            MoveError::BlockedPath => write!(f, "the path is blocked"),
+            MoveError::MissingPromotion => write!(f, "choose a piece to promote to"),
+            MoveError::InvalidPromotion => write!(f, "that promotion isn't allowed"),
+            MoveError::CastlingNotAllowed => write!(f, "castling isn't allowed here"),
+            MoveError::LeavesKingInCheck => write!(f, "that move would leave your king in check"),
+            MoveError::IllegalMove(mv) => write!(f, "{} is not a legal move", mv),
+            MoveError::Ambiguous(candidates) => {
+                let candidates: Vec<String> = candidates.iter().map(Move::to_uci).collect();
+                write!(f, "that could be any of {}", candidates.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// Everything `make_move` overwrites that can't be worked out from the move
+// itself. Handing it back to `unmake_move` restores the board exactly.
+#[derive(Clone, Copy)]
+pub struct UndoInfo {
+    pub(crate) captured: Option<Piece>,
+    pub(crate) castling: CastlingRights,
+    pub(crate) en_passant: Option<Square>,
+    pub(crate) halfmove_clock: u32,
+}
+
+impl UndoInfo {
+    // The piece the move captured, if any.
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
+}
+
+// The pieces a pawn can promote to, best first.
+pub(crate) const PROMOTION_KINDS: [PieceKind; 4] =
+    [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight];
+
+fn pawn_direction(color: PieceColor) -> i8 {
+    match color {
+        PieceColor::White => 1,
+        PieceColor::Black => -1,
+    }
+}
+
+// The back rank of a color: rank 1 for White, rank 8 for Black.
+fn home_rank(color: PieceColor) -> u8 {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 7,
+    }
+}
+
+impl Board {
+    // Returns every legal move for the side to move.
+    //
+    // We first generate "pseudo-legal" moves, which follow the movement rules
+    // of each piece but may leave our own king in check, and then throw away
+    // the ones that do by actually playing them on a scratch board.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.generate_pseudo_legal();
+
+        let us = self.side_to_move();
+        let mut scratch = self.clone();
+        moves.retain(|&mv| !scratch.check_after_in_place(&mv, us));
+        moves
+    }
+
+    // True if `color`'s king would be in check after `mv`. The board itself is
+    // left untouched; the move is tried on a copy.
+    pub fn is_check_after(&self, mv: &Move, color: PieceColor) -> bool {
+        self.clone().check_after_in_place(mv, color)
+    }
+
+    // Like `is_check_after`, but plays and takes back the move on this board
+    // instead of copying it, which is what makes testing every move of a
+    // position cheap. The board is back as it was when this returns.
+    pub(crate) fn check_after_in_place(&mut self, mv: &Move, color: PieceColor) -> bool {
+        let undo = self.make_move(*mv);
+        let in_check = self.king_in_check(color);
+        self.unmake_move(*mv, undo);
+        in_check
+    }
+
+    // Every move that follows the movement rules of its piece, including ones
+    // that leave our own king in check. Castling is the exception: it is only
+    // generated when the king doesn't start in or pass through check.
+    //
+    // A search that plays moves one by one can use this together with
+    // `is_legal` to check each move only when it actually gets to it, instead
+    // of filtering the whole list up front.
+    pub fn generate_pseudo_legal(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        for from in Square::all() {
+            self.generate_from(from, &mut moves);
+        }
+        moves
+    }
+
+    // True if a move from `generate_pseudo_legal` doesn't leave the mover's
+    // king in check. That is the only thing checked, so passing any other
+    // move gives a meaningless answer.
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        !self.is_check_after(mv, self.side_to_move())
+    }
+
+    // The legal moves of the piece on `from`. Empty if the square is empty or
+    // holds a piece of the side not to move. Only that one piece's moves are
+    // generated, so this is much cheaper than filtering `legal_moves`.
+    pub fn legal_moves_from(&self, from: Square) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.generate_from(from, &mut moves);
+
+        let us = self.side_to_move();
+        let mut scratch = self.clone();
+        moves.retain(|&mv| !scratch.check_after_in_place(&mv, us));
+        moves
+    }
+
+    // Pseudo-legal moves of the piece on `from`, if it belongs to the side to
+    // move.
+    fn generate_from(&self, from: Square, moves: &mut Vec<Move>) {
+        let us = self.side_to_move();
+        let Some(piece) = self.piece_at(from) else { return };
+        if piece.color() != us {
+            return;
+        }
+        match piece.kind() {
+            PieceKind::Pawn => self.generate_pawn_moves(from, us, moves),
+            PieceKind::Knight => self.generate_steps(from, us, knight_attacks(from), moves),
+            PieceKind::Bishop => self.generate_slides(from, us, &BISHOP_DIRECTIONS, moves),
+            PieceKind::Rook => self.generate_slides(from, us, &ROOK_DIRECTIONS, moves),
+            PieceKind::Queen => {
+                self.generate_slides(from, us, &ROOK_DIRECTIONS, moves);
+                self.generate_slides(from, us, &BISHOP_DIRECTIONS, moves);
+            }
+            PieceKind::King => {
+                self.generate_steps(from, us, king_attacks(from), moves);
+                self.generate_castling(from, us, moves);
+            }
+        }
+    }
+
+    // True if `square` holds a piece of the opposite color to `us`.
+    fn is_enemy(&self, square: Square, us: PieceColor) -> bool {
+        self.piece_at(square).is_some_and(|piece| piece.color() != us)
+    }
+
+    fn generate_pawn_moves(&self, from: Square, us: PieceColor, moves: &mut Vec<Move>) {
+        let dir = pawn_direction(us);
+        let promotion_rank = home_rank(us.opposite());
+        let start_rank = match us {
+            PieceColor::White => 1,
+            PieceColor::Black => 6,
+        };
+
+        let mut push = |to: Square, flag: MoveFlag| {
+            if to.rank() == promotion_rank {
+                for kind in PROMOTION_KINDS {
+                    moves.push(Move::with_promotion(from, to, kind));
+                }
+            } else {
+                moves.push(Move::with_flag(from, to, flag));
+            }
+        };
+
+        if let Some(one) = from.offset(0, dir).filter(|&sq| self.piece_at(sq).is_none()) {
+            push(one, MoveFlag::Normal);
+            if from.rank() == start_rank {
+                if let Some(two) = one.offset(0, dir).filter(|&sq| self.piece_at(sq).is_none()) {
+                    push(two, MoveFlag::DoublePawnPush);
+                }
+            }
+        }
+
+        for to in pawn_attacks(us, from).iter_squares() {
+            if self.is_enemy(to, us) {
+                push(to, MoveFlag::Normal);
+            } else if self.en_passant() == Some(to) {
+                push(to, MoveFlag::EnPassant);
+            }
+        }
+    }
+
+    // Knight and king moves: a single jump to any of the `targets` not held
+    // by one of our own pieces.
+    fn generate_steps(&self, from: Square, us: PieceColor, targets: Bitboard, moves: &mut Vec<Move>) {
+        for to in targets.iter_squares() {
+            if self.piece_at(to).is_none_or(|piece| piece.color() != us) {
+                moves.push(Move::new(from, to));
+            }
+        }
+    }
+
+    // Bishop, rook and queen moves: slide until blocked, capturing the first
+    // enemy piece in the way.
+    fn generate_slides(&self, from: Square, us: PieceColor, directions: &[(i8, i8)], moves: &mut Vec<Move>) {
+        for &(df, dr) in directions {
+            let mut current = from.offset(df, dr);
+            while let Some(to) = current {
+                match self.piece_at(to) {
+                    None => moves.push(Move::new(from, to)),
+                    Some(piece) => {
+                        if piece.color() != us {
+                            moves.push(Move::new(from, to));
+                        }
+                        break;
+                    }
+                }
+                current = to.offset(df, dr);
+            }
+        }
+    }
+
+    // Castling is allowed when the right hasn't been lost, the squares between
+    // king and rook are empty, and the king is not in check and does not pass
+    // through an attacked square. Landing in check is caught by the general
+    // legality filter like any other move.
+    fn generate_castling(&self, from: Square, us: PieceColor, moves: &mut Vec<Move>) {
+        let rank = home_rank(us);
+        if from != Square::new(4, rank).unwrap() {
+            return;
+        }
+        let them = us.opposite();
+        let rights = self.castling_rights();
+        let empty = |file: u8| self.piece_at(Square::new(file, rank).unwrap()).is_none();
+        let safe = |file: u8| !self.is_square_attacked(Square::new(file, rank).unwrap(), them);
+        let own_rook = |file: u8| {
+            self.piece_at(Square::new(file, rank).unwrap())
+                .is_some_and(|p| p.kind() == PieceKind::Rook && p.color() == us)
+        };
+
+        if rights.contains(CastlingRights::kingside(us))
+            && own_rook(7)
+            && empty(5)
+            && empty(6)
+            && safe(4)
+            && safe(5)
+        {
+            moves.push(Move::with_flag(from, Square::new(6, rank).unwrap(), MoveFlag::CastleKingside));
+        }
+        if rights.contains(CastlingRights::queenside(us))
+            && own_rook(0)
+            && empty(1)
+            && empty(2)
+            && empty(3)
+            && safe(4)
+            && safe(3)
+        {
+            moves.push(Move::with_flag(from, Square::new(2, rank).unwrap(), MoveFlag::CastleQueenside));
+        }
+    }
+
+    // Works out why `mv` can't be played in this position, or returns None if
+    // it can. The checks run from the most basic ("is there even a piece
+    // there?") to the most subtle ("does it expose the king?"), and the first
+    // one that fails is reported.
+    pub fn explain_illegal(&self, mv: &Move) -> Option<MoveError> {
+        let us = self.side_to_move();
+        let Some(piece) = self.piece_at(mv.from()) else {
+            return Some(MoveError::NoPieceOnSquare(mv.from()));
+        };
+        if piece.color() != us {
+            return Some(MoveError::NotYourTurn);
+        }
+        if self.legal_moves().iter().any(|legal| legal.same_squares(mv)) {
+            return None;
+        }
+        if self.piece_at(mv.to()).is_some_and(|target| target.color() == us) {
+            return Some(MoveError::OccupiedByOwnPiece);
+        }
+
+        let reaches_last_rank =
+            piece.kind() == PieceKind::Pawn && mv.to().rank() == home_rank(us.opposite());
+        match mv.promotion() {
+            None if reaches_last_rank => return Some(MoveError::MissingPromotion),
+            Some(kind) if !reaches_last_rank || !PROMOTION_KINDS.contains(&kind) => {
+                return Some(MoveError::InvalidPromotion);
+            }
+            _ => {}
+        }
+
+        if let Some(error) = self.explain_geometry(piece, mv) {
+            return Some(error);
+        }
+
+        // The piece can make this move, so the only thing left is our king.
+        if self.generate_pseudo_legal().iter().any(|candidate| candidate.same_squares(mv)) {
+            return Some(MoveError::LeavesKingInCheck);
+        }
+        Some(MoveError::IllegalMove(*mv))
+    }
+
+    // Checks that `piece` moves in the shape of `mv` and that nothing is in its
+    // way, ignoring whether the king ends up in check.
+    fn explain_geometry(&self, piece: Piece, mv: &Move) -> Option<MoveError> {
+        let us = piece.color();
+        let file_delta = mv.to().file() as i8 - mv.from().file() as i8;
+        let rank_delta = mv.to().rank() as i8 - mv.from().rank() as i8;
+        let is_empty = |square: Option<Square>| square.is_some_and(|sq| self.piece_at(sq).is_none());
+
+        match piece.kind() {
+            PieceKind::Pawn => {
+                let dir = pawn_direction(us);
+                let start_rank = home_rank(us) as i8 + dir;
+                if file_delta == 0 && rank_delta == dir {
+                    if !is_empty(Some(mv.to())) {
+                        return Some(MoveError::BlockedPath);
+                    }
+                } else if file_delta == 0 && rank_delta == 2 * dir && mv.from().rank() as i8 == start_rank {
+                    if !is_empty(mv.from().offset(0, dir)) || !is_empty(Some(mv.to())) {
+                        return Some(MoveError::BlockedPath);
+                    }
+                } else if file_delta.abs() == 1 && rank_delta == dir {
+                    if !self.is_enemy(mv.to(), us) && self.en_passant() != Some(mv.to()) {
+                        return Some(MoveError::PieceCannotReach);
+                    }
+                } else {
+                    return Some(MoveError::PieceCannotReach);
+                }
+            }
+            PieceKind::Knight => {
+                if !KNIGHT_OFFSETS.contains(&(file_delta, rank_delta)) {
+                    return Some(MoveError::PieceCannotReach);
+                }
+            }
+            PieceKind::King => {
+                if rank_delta == 0 && file_delta.abs() == 2 {
+                    return Some(MoveError::CastlingNotAllowed);
+                }
+                if !KING_OFFSETS.contains(&(file_delta, rank_delta)) {
+                    return Some(MoveError::PieceCannotReach);
+                }
+            }
+            PieceKind::Bishop | PieceKind::Rook | PieceKind::Queen => {
+                let Some(step) = mv.from().direction_to(mv.to()) else {
+                    return Some(MoveError::PieceCannotReach);
+                };
+                let diagonal = step.0 != 0 && step.1 != 0;
+                let allowed = match piece.kind() {
+                    PieceKind::Bishop => diagonal,
+                    PieceKind::Rook => !diagonal,
+                    _ => true,
+                };
+                if !allowed {
+                    return Some(MoveError::PieceCannotReach);
+                }
+                let mut current = mv.from().offset(step.0, step.1);
+                while let Some(square) = current.filter(|&sq| sq != mv.to()) {
+                    if self.piece_at(square).is_some() {
+                        return Some(MoveError::BlockedPath);
+                    }
+                    current = square.offset(step.0, step.1);
+                }
+            }
+        }
+        None
+    }
+
+    // Where the rook starts and ends for a castling move, if it is one.
+    pub(crate) fn castling_rook_squares(mv: &Move) -> Option<(Square, Square)> {
+        let rank = mv.from().rank();
+        match mv.flag() {
+            MoveFlag::CastleKingside => Some((Square::new(7, rank)?, Square::new(5, rank)?)),
+            MoveFlag::CastleQueenside => Some((Square::new(0, rank)?, Square::new(3, rank)?)),
+            _ => None,
+        }
+    }
+
+    // The square of the piece a move captures. For en passant that is beside
+    // the destination rather than on it.
+    pub(crate) fn capture_square(mv: &Move) -> Square {
+        match mv.flag() {
+            MoveFlag::EnPassant => Square::new(mv.to().file(), mv.from().rank()).unwrap(),
+            _ => mv.to(),
+        }
+    }
+
+    // The castling rights left once `piece` has made `mv`. A king move gives
+    // up both of its side's rights; anything leaving or landing on a rook's
+    // home corner gives up that side's right.
+    pub(crate) fn castling_rights_after(mut rights: CastlingRights, piece: Piece, mv: &Move) -> CastlingRights {
+        if piece.kind() == PieceKind::King {
+            rights.remove(CastlingRights::both(piece.color()));
+        }
+        for square in [mv.from(), mv.to()] {
+            let corner = match (square.file(), square.rank()) {
+                (0, 0) => CastlingRights::WHITE_QUEENSIDE,
+                (7, 0) => CastlingRights::WHITE_KINGSIDE,
+                (0, 7) => CastlingRights::BLACK_QUEENSIDE,
+                (7, 7) => CastlingRights::BLACK_KINGSIDE,
+                _ => continue,
+            };
+            rights.remove(corner);
+        }
+        rights
+    }
+
+    // Plays a move on the board without checking that it is legal, returning
+    // what's needed to take it back with `unmake_move`.
+    pub fn make_move(&mut self, mv: Move) -> UndoInfo {
+        let us = self.side_to_move();
+        let piece = self
+            .remove_piece(mv.from())
+            .expect("make_move called with no piece on the from-square");
+        let captured = self.remove_piece(Board::capture_square(&mv));
+
+        let undo = UndoInfo {
+            captured,
+            castling: self.castling_rights(),
+            en_passant: self.en_passant(),
+            halfmove_clock: self.halfmove_clock(),
+        };
+
+        let placed = match mv.promotion() {
+            Some(kind) => Piece::new(kind, us),
+            None => piece,
+        };
+        self.put_piece(mv.to(), placed);
+
+        if let Some((rook_from, rook_to)) = Board::castling_rook_squares(&mv) {
+            if let Some(rook) = self.remove_piece(rook_from) {
+                self.put_piece(rook_to, rook);
+            }
+        }
+
+        self.set_castling_rights(Board::castling_rights_after(self.castling_rights(), piece, &mv));
+
+        self.set_en_passant(match mv.flag() {
+            MoveFlag::DoublePawnPush => {
+                Square::new(mv.from().file(), (mv.from().rank() + mv.to().rank()) / 2)
+            }
+            _ => None,
+        });
+
+        if piece.kind() == PieceKind::Pawn || captured.is_some() {
+            self.set_halfmove_clock(0);
+        } else {
+            self.set_halfmove_clock(self.halfmove_clock() + 1);
+        }
+        if us == PieceColor::Black {
+            self.set_fullmove_number(self.fullmove_number() + 1);
+        }
+        self.set_side_to_move(us.opposite());
+
+        undo
+    }
+
+    // Takes back a move previously played with `make_move`.
+    pub fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        let us = self.side_to_move().opposite();
+        self.set_side_to_move(us);
+        if us == PieceColor::Black {
+            self.set_fullmove_number(self.fullmove_number() - 1);
+        }
+        self.set_castling_rights(undo.castling);
+        self.set_en_passant(undo.en_passant);
+        self.set_halfmove_clock(undo.halfmove_clock);
+
+        let moved = self
+            .remove_piece(mv.to())
+            .expect("unmake_move called with no piece on the to-square");
+        let original = match mv.promotion() {
+            Some(_) => Piece::new(PieceKind::Pawn, us),
+            None => moved,
+        };
+        self.put_piece(mv.from(), original);
+
+        if let Some(captured) = undo.captured {
+            self.put_piece(Board::capture_square(&mv), captured);
+        }
+
+        if let Some((rook_from, rook_to)) = Board::castling_rook_squares(&mv) {
+            if let Some(rook) = self.remove_piece(rook_to) {
+                self.put_piece(rook_from, rook);
+            }
+        }
+    }
 }
@@ -0,0 +1,172 @@
+//! Opening names.
+//! 
+//! This file includes:
+//! - The Opening type: an ECO code and a name
+//! - A built-in table of well-known opening lines
+//! - Openings, a trie of those lines for naming a game's opening and telling
+//!   when it has left known theory
+//!
+//! The table is small, covering the main openings and their best-known
+//! variations rather than the full ECO classification.
+
+use std::sync::OnceLock;
+
+use crate::chess::board::Board;
+use crate::chess::fen::START_FEN;
+use crate::chess::game::Game;
+use crate::chess::r#move::Move;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Opening {
+    // The Encyclopaedia of Chess Openings code, e.g. "C60".
+    pub eco: &'static str,
+    pub name: &'static str,
+}
+
+// ECO code, name and the moves in SAN from the usual start position. A line
+// only needs to be listed if it has a name of its own; its first moves come
+// from the shorter lines before it.
+const LINES: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A04", "Zukertort Opening", "Nf3"),
+    ("A09", "Reti Opening", "Nf3 d5 c4"),
+    ("A10", "English Opening", "c4"),
+    ("A20", "English Opening: King's English Variation", "c4 e5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    ("B02", "Alekhine Defense", "e4 Nf6"),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    ("B12", "Caro-Kann Defense: Advance Variation", "e4 c6 d4 d5 e5"),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    ("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    ("B70", "Sicilian Defense: Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+    ("B90", "Sicilian Defense: Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+    ("C00", "French Defense", "e4 e6"),
+    ("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    ("C15", "French Defense: Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Knight Opening: Normal Variation", "e4 e5 Nf3 Nc6"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Italian Game: Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Italian Game: Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C55", "Italian Game: Two Knights Defense", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C68", "Ruy Lopez: Exchange Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    ("C70", "Ruy Lopez: Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D07", "Queen's Gambit Declined: Chigorin Defense", "d4 d5 c4 Nc6"),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D80", "Grunfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+];
+
+// How far a game's moves follow the known lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OpeningMatch {
+    // The most specific named line the game has followed, if any.
+    pub opening: Option<Opening>,
+    // Whether every move so far is part of a known line. Once false, it
+    // stays false for the rest of the game.
+    pub in_theory: bool,
+}
+
+struct TrieNode {
+    children: Vec<(Move, usize)>,
+    opening: Option<Opening>,
+}
+
+// Known opening lines as a trie keyed by move, rooted at the usual start
+// position. Transpositions aren't recognised: a line counts only when its
+// moves come in the listed order.
+pub struct Openings {
+    nodes: Vec<TrieNode>,
+}
+
+impl Openings {
+    // The built-in table, built on first use.
+    pub fn standard() -> &'static Openings {
+        static STANDARD: OnceLock<Openings> = OnceLock::new();
+        STANDARD.get_or_init(|| Openings::from_lines(LINES))
+    }
+
+    // Builds a trie from (ECO, name, SAN moves) lines. Panics if a line
+    // isn't legal, since the lines are fixed at compile time.
+    fn from_lines(lines: &[(&'static str, &'static str, &'static str)]) -> Self {
+        let mut openings = Openings { nodes: vec![TrieNode { children: Vec::new(), opening: None }] };
+        for &(eco, name, moves) in lines {
+            let mut board = Board::new();
+            let mut node = 0;
+            for san in moves.split_whitespace() {
+                let mv = Move::from_san(san, &board)
+                    .unwrap_or_else(|error| panic!("bad opening line {} ({}): {}", name, san, error));
+                node = openings.child(node, mv);
+                board.make_move(mv);
+            }
+            openings.nodes[node].opening = Some(Opening { eco, name });
+        }
+        openings
+    }
+
+    // The node after `mv` from `node`, added if it isn't there yet.
+    fn child(&mut self, node: usize, mv: Move) -> usize {
+        if let Some(&(_, next)) = self.nodes[node].children.iter().find(|(known, _)| *known == mv) {
+            return next;
+        }
+        let next = self.nodes.len();
+        self.nodes.push(TrieNode { children: Vec::new(), opening: None });
+        self.nodes[node].children.push((mv, next));
+        next
+    }
+
+    // Follows `moves`, played from the usual start position, as far as the
+    // known lines go.
+    pub fn classify(&self, moves: &[Move]) -> OpeningMatch {
+        let mut node = 0;
+        let mut opening = None;
+        for mv in moves {
+            match self.nodes[node].children.iter().find(|(known, _)| known.same_squares(mv)) {
+                Some(&(_, next)) => {
+                    node = next;
+                    opening = self.nodes[node].opening.or(opening);
+                }
+                None => return OpeningMatch { opening, in_theory: false },
+            }
+        }
+        OpeningMatch { opening, in_theory: true }
+    }
+
+    // Classifies a game's moves. A game set up from another position has no
+    // opening and is never in theory.
+    pub fn classify_game(&self, game: &Game) -> OpeningMatch {
+        if game.start_board().to_fen() != START_FEN {
+            return OpeningMatch { opening: None, in_theory: false };
+        }
+        let moves: Vec<Move> = game.history().iter().map(|entry| entry.mv()).collect();
+        self.classify(&moves)
+    }
+}
@@ -0,0 +1,100 @@
+//! Pawn structure analysis.
+//! 
+//! This file includes:
+//! - The PawnStructure report: doubled, isolated, backward and passed pawns
+//! - Board::pawn_structure, which works it out for one side
+
+use crate::chess::bitboard::Bitboard;
+use crate::chess::board::{pawn_attacks, Board};
+use crate::chess::piece::{PieceColor, PieceKind};
+
+// The pawn weaknesses and strengths of one side, each as the set of pawns
+// it applies to. A pawn can be in more than one set: an isolated pawn is
+// often doubled too, and a passed pawn can be isolated.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct PawnStructure {
+    // Pawns sharing their file with another pawn of the same color. All of
+    // them are listed, not only the rear ones.
+    pub doubled: Bitboard,
+    // Pawns with no friendly pawn on either neighbouring file, so no pawn can
+    // ever defend them.
+    pub isolated: Bitboard,
+    // Pawns that have fallen behind their neighbours: no friendly pawn on a
+    // neighbouring file is level with or behind them, and the square in
+    // front is covered by an enemy pawn, so they can't safely catch up.
+    // Isolated pawns have no neighbours to fall behind and aren't counted.
+    pub backward: Bitboard,
+    // Pawns with no enemy pawn in front of them on their own file or either
+    // neighbouring file. Only pieces can stop them from queening.
+    pub passed: Bitboard,
+}
+
+// Every square on the given file.
+fn file_mask(file: u8) -> Bitboard {
+    Bitboard(Bitboard::FILE_A.0 << file)
+}
+
+// The files either side of `file`, without `file` itself.
+fn neighbour_files(file: u8) -> Bitboard {
+    let mut files = Bitboard::EMPTY;
+    if file > 0 {
+        files |= file_mask(file - 1);
+    }
+    if file < 7 {
+        files |= file_mask(file + 1);
+    }
+    files
+}
+
+// Every square on a rank further up the board than `rank`, as `color` sees
+// it: higher ranks for White, lower ones for Black.
+fn ranks_ahead(color: PieceColor, rank: u8) -> Bitboard {
+    match color {
+        PieceColor::White => Bitboard((!0u64).checked_shl(8 * (rank as u32 + 1)).unwrap_or(0)),
+        PieceColor::Black => Bitboard((1u64 << (8 * rank as u32)) - 1),
+    }
+}
+
+impl Board {
+    // Every square holding a pawn of `color`.
+    fn pawns(&self, color: PieceColor) -> Bitboard {
+        self.piece_list()
+            .into_iter()
+            .filter(|(_, piece)| piece.kind() == PieceKind::Pawn && piece.color() == color)
+            .map(|(square, _)| square)
+            .collect()
+    }
+
+    // Classifies `color`'s pawns. See PawnStructure for what each set means.
+    pub fn pawn_structure(&self, color: PieceColor) -> PawnStructure {
+        let own = self.pawns(color);
+        let enemy = self.pawns(color.opposite());
+        let enemy_cover = enemy
+            .iter_squares()
+            .fold(Bitboard::EMPTY, |covered, pawn| covered | pawn_attacks(color.opposite(), pawn));
+
+        let mut structure = PawnStructure::default();
+        for pawn in own.iter_squares() {
+            let file = file_mask(pawn.file());
+            let neighbours = neighbour_files(pawn.file());
+            let ahead = ranks_ahead(color, pawn.rank());
+
+            if (own & file).count() > 1 {
+                structure.doubled |= pawn;
+            }
+            if (own & neighbours).is_empty() {
+                structure.isolated |= pawn;
+            } else if (own & neighbours & !ahead).is_empty() {
+                let forward = if color == PieceColor::White { 1 } else { -1 };
+                let stop = pawn.offset(0, forward);
+                if stop.is_some_and(|stop| enemy_cover.contains(stop)) {
+                    structure.backward |= pawn;
+                }
+            }
+            if (enemy & (file | neighbours) & ahead).is_empty() {
+                structure.passed |= pawn;
+            }
+        }
+        structure
+    }
+}
@@ -0,0 +1,622 @@
+//! PGN (Portable Game Notation) import and export.
+//! 
+//! This file includes:
+//! - PgnGame: tags plus a move tree with comments, NAGs and variations
+//! - Reading PGN text into a PgnGame, with PgnError for when it can't
+//! - Writing a PgnGame back out, wrapped at 80 columns
+//! - Game::to_pgn and Game::from_pgn on top of those
+//!
+//! A PGN file is the standard way to hand a game to other chess software:
+//!
+//!   [Event "?"]
+//!   [Site "?"]
+//!   ...
+//!   [Result "0-1"]
+//!
+//!   1. f3 e5 2. g4 Qh4# 0-1
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chess::board::Board;
+use crate::chess::fen::FenError;
+use crate::chess::game::{Game, GameResult, Termination};
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::{Move, MoveError};
+
+// PGN asks for movetext lines of at most 80 characters.
+const LINE_WIDTH: usize = 80;
+
+// A tag value in quotes, with the two characters PGN treats specially
+// escaped.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The date part of a time in PGN form, "YYYY.MM.DD", in UTC.
+fn pgn_date(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / 86_400) as i64;
+    // Howard Hinnant's days-to-civil algorithm: count in 400-year eras that
+    // start on 1 March, so the leap day falls at the end of the year.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// Joins tokens with spaces, starting a new line whenever the next token
+// wouldn't fit.
+fn wrap(tokens: &[String]) -> String {
+    let mut out = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > LINE_WIDTH {
+            out.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            out.push(' ');
+            line_length += 1;
+        }
+        out.push_str(token);
+        line_length += token.len();
+    }
+    out
+}
+
+// A game as PGN describes it. Unlike Game, which only knows the moves that
+// were played, this keeps everything an annotator adds: comments, numeric
+// annotation glyphs (NAGs) and side variations, so a file can be read and
+// written back without losing any of it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    // Tag pairs in the order they appear.
+    pub tags: Vec<(String, String)>,
+    // The position before the first move: the FEN tag's, or the usual one.
+    pub start: Board,
+    // The main line.
+    pub moves: Vec<PgnMove>,
+    // "1-0", "0-1", "1/2-1/2" or "*".
+    pub result: String,
+}
+
+// One move of a line, with its annotations.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnMove {
+    pub mv: Move,
+    // In standard form, whatever way the file wrote it.
+    pub san: String,
+    // NAGs, e.g. 1 for "!" and 4 for "??". Suffixes written as "!" or "?!"
+    // are stored as their NAG.
+    pub nags: Vec<u8>,
+    // Comments between the previous move and this one. Only used at the
+    // start of a line, where there is no previous move to attach them to.
+    pub comments_before: Vec<String>,
+    // Comments after the move.
+    pub comments: Vec<String>,
+    // Alternatives to this move, each a line starting from the same
+    // position.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(mv: Move, san: String) -> Self {
+        PgnMove { mv, san, nags: Vec::new(), comments_before: Vec::new(), comments: Vec::new(), variations: Vec::new() }
+    }
+}
+
+// The NAGs for the traditional move suffixes.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return None,
+    })
+}
+
+impl PgnGame {
+    // The value of a tag, if present.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // Reads the first game in `text`. Every move, in variations too, is
+    // checked against the position it is played in.
+    pub fn parse(text: &str) -> Result<PgnGame, PgnError> {
+        Ok(Parser::read(text)?.0)
+    }
+
+    // The game as PGN text: tags, a blank line, then the movetext.
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.tags {
+            out.push_str(&format!("[{} {}]\n", name, quote(value)));
+        }
+        out.push('\n');
+
+        let mut tokens = Vec::new();
+        write_line(&mut tokens, &self.moves, &self.start, 0);
+        tokens.push(self.result.clone());
+        out.push_str(&wrap(&tokens));
+        out.push('\n');
+        out
+    }
+}
+
+// A comment as movetext tokens. Split into words so the wrapping can break
+// inside it; PGN allows that.
+fn comment_tokens(tokens: &mut Vec<String>, comment: &str) {
+    let comment = format!("{{{}}}", comment);
+    tokens.extend(comment.split(' ').map(str::to_string));
+}
+
+// Appends a line of moves, the first of which is played at `ply` plies
+// after `start`. Black's move gets its number written out ("3...") when it
+// starts a line or follows a variation; after a comment it doesn't, so clock
+// comments don't double the move numbers.
+fn write_line(tokens: &mut Vec<String>, moves: &[PgnMove], start: &Board, ply: usize) {
+    let first_black = start.side_to_move() == PieceColor::Black;
+    let mut interrupted = true;
+    for (i, pgn_move) in moves.iter().enumerate() {
+        let ply = ply + i + first_black as usize;
+        let number = start.fullmove_number() as usize + ply / 2;
+        for comment in &pgn_move.comments_before {
+            comment_tokens(tokens, comment);
+        }
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", number));
+        } else if interrupted {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        interrupted = false;
+        for comment in &pgn_move.comments {
+            comment_tokens(tokens, comment);
+        }
+        for variation in &pgn_move.variations {
+            // The parentheses go against the moves: "(3... Nf6 4. e5)".
+            let first = tokens.len();
+            write_line(tokens, variation, start, ply - first_black as usize);
+            if tokens.len() == first {
+                tokens.push("()".to_string());
+            } else {
+                tokens[first].insert(0, '(');
+                tokens.last_mut().expect("not empty").push(')');
+            }
+            interrupted = true;
+        }
+    }
+}
+
+// Where in the PGN text something went wrong, counting lines and columns
+// from 1, and what.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnErrorKind {
+    // A character that can't start any PGN token.
+    UnexpectedCharacter(char),
+    // A tag pair that isn't `[Name "value"]`.
+    MalformedTag,
+    // The text ended inside a quoted string or a {comment}.
+    UnterminatedString,
+    UnterminatedComment,
+    // A ')' without a '(' or the other way round.
+    UnbalancedVariation,
+    // The FEN tag doesn't hold a valid position.
+    InvalidFen(FenError),
+    // A move that can't be played in the position it appears in.
+    IllegalMove { san: String, error: MoveError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            PgnErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            PgnErrorKind::MalformedTag => write!(f, "malformed tag pair"),
+            PgnErrorKind::UnterminatedString => write!(f, "string is never closed"),
+            PgnErrorKind::UnterminatedComment => write!(f, "comment is never closed"),
+            PgnErrorKind::UnbalancedVariation => write!(f, "unbalanced parentheses"),
+            PgnErrorKind::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnErrorKind::IllegalMove { san, error } => write!(f, "can't play {}: {}", san, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+// The pieces PGN text is made of.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Tag(String, String),
+    // A move, move number or result: anything made of letters, digits and
+    // SAN punctuation.
+    Symbol(String),
+    Comment(String),
+    // A numeric annotation glyph, "$1".
+    Nag(u8),
+    // "!", "?!" and friends written straight after a move.
+    Suffix(String),
+    OpenVariation,
+    CloseVariation,
+}
+
+// A token and the line and column it starts at.
+type Located = (Token, usize, usize);
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Lexer { chars: text.chars().peekable(), line: 1, column: 1 }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> String {
+        let mut out = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !keep(c) {
+                break;
+            }
+            out.push(c);
+            self.bump();
+        }
+        out
+    }
+
+    // Whether the digits about to be read are followed by a dot.
+    fn starts_move_number(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        while ahead.next_if(char::is_ascii_digit).is_some() {}
+        ahead.peek() == Some(&'.')
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn error(&self, line: usize, column: usize, kind: PgnErrorKind) -> PgnError {
+        PgnError { line, column, kind }
+    }
+
+    // The body of a "quoted string", after the opening quote. A backslash
+    // escapes the next character.
+    fn string(&mut self, line: usize, column: usize) -> Result<String, PgnError> {
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error(line, column, PgnErrorKind::UnterminatedString)),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some(c) => out.push(c),
+                    None => return Err(self.error(line, column, PgnErrorKind::UnterminatedString)),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    // A tag pair, after the opening bracket.
+    fn tag(&mut self, line: usize, column: usize) -> Result<Token, PgnError> {
+        self.skip_whitespace();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        self.skip_whitespace();
+        if name.is_empty() || self.bump() != Some('"') {
+            return Err(self.error(line, column, PgnErrorKind::MalformedTag));
+        }
+        let value = self.string(line, column)?;
+        self.skip_whitespace();
+        if self.bump() != Some(']') {
+            return Err(self.error(line, column, PgnErrorKind::MalformedTag));
+        }
+        Ok(Token::Tag(name, value))
+    }
+
+    fn next_token(&mut self) -> Option<Result<Located, PgnError>> {
+        loop {
+            self.skip_whitespace();
+            // A ';' comments out the rest of the line, and a '%' at the start
+            // of a line escapes it.
+            match self.chars.peek() {
+                Some(';') => {}
+                Some('%') if self.column == 1 => {}
+                _ => break,
+            }
+            self.take_while(|c| c != '\n');
+        }
+        let (line, column) = (self.line, self.column);
+        let c = self.bump()?;
+        let token = match c {
+            '[' => self.tag(line, column),
+            '{' => {
+                let text = self.take_while(|c| c != '}');
+                match self.bump() {
+                    Some('}') => Ok(Token::Comment(text.trim().to_string())),
+                    _ => Err(self.error(line, column, PgnErrorKind::UnterminatedComment)),
+                }
+            }
+            '(' => Ok(Token::OpenVariation),
+            ')' => Ok(Token::CloseVariation),
+            '$' => {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                digits
+                    .parse()
+                    .map(Token::Nag)
+                    .map_err(|_| self.error(line, column, PgnErrorKind::UnexpectedCharacter('$')))
+            }
+            '!' | '?' => {
+                let rest = self.take_while(|c| c == '!' || c == '?');
+                Ok(Token::Suffix(format!("{}{}", c, rest)))
+            }
+            '*' => Ok(Token::Symbol("*".to_string())),
+            // A move number, with its dots, even when the move follows
+            // without a space: "5.O-O".
+            c if c.is_ascii_digit() && self.starts_move_number() => {
+                let rest = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                Ok(Token::Symbol(format!("{}{}", c, rest)))
+            }
+            c if c.is_ascii_alphanumeric() => {
+                let rest = self.take_while(|c| c.is_ascii_alphanumeric() || "_+#=:-/.".contains(c));
+                Ok(Token::Symbol(format!("{}{}", c, rest)))
+            }
+            c => Err(self.error(line, column, PgnErrorKind::UnexpectedCharacter(c))),
+        };
+        Some(token.map(|token| (token, line, column)))
+    }
+}
+
+fn is_result(symbol: &str) -> bool {
+    matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// A move number such as "12." or "12...". Older files leave out the dots.
+fn is_move_number(symbol: &str) -> bool {
+    let digits = symbol.trim_end_matches('.');
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+
+// Turns tokens into a PgnGame, playing the moves as it goes so each one can
+// be checked and put into standard SAN.
+struct Parser {
+    tokens: Vec<Located>,
+    next: usize,
+    // Where the text ends, for errors about things left open.
+    end: (usize, usize),
+}
+
+impl Parser {
+    // The game, and where each main-line move was written.
+    fn read(text: &str) -> Result<(PgnGame, Vec<(usize, usize)>), PgnError> {
+        let mut lexer = Lexer::new(text);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token?);
+        }
+        let mut parser = Parser { tokens, next: 0, end: (lexer.line, lexer.column) };
+
+        let mut tags = Vec::new();
+        while let Some((Token::Tag(name, value), line, column)) = parser.tokens.get(parser.next).cloned() {
+            if name == "FEN" {
+                Board::from_fen(&value).map_err(|error| PgnError { line, column, kind: PgnErrorKind::InvalidFen(error) })?;
+            }
+            tags.push((name, value));
+            parser.next += 1;
+        }
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::from_fen(fen).expect("checked above"),
+            None => Board::new(),
+        };
+
+        let mut locations = Vec::new();
+        let (moves, result) = parser.line(&start, Some(&mut locations))?;
+        let game = PgnGame { tags, start, moves, result: result.unwrap_or_else(|| "*".to_string()) };
+        Ok((game, locations))
+    }
+
+    // Reads moves from `board` until the end of the line: a ')' inside a
+    // variation, or the result, the next game's tags or the end of the text
+    // in the main line. `locations` is only given for the main line, which
+    // is also the only place a result may end. Returns the moves and the
+    // result, if one was read.
+    fn line(
+        &mut self,
+        board: &Board,
+        mut locations: Option<&mut Vec<(usize, usize)>>,
+    ) -> Result<(Vec<PgnMove>, Option<String>), PgnError> {
+        let main_line = locations.is_some();
+        let mut board = board.clone();
+        // The position before the last move, where its variations start.
+        let mut before_last = board.clone();
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut pending_comments = Vec::new();
+
+        while let Some((token, line, column)) = self.tokens.get(self.next).cloned() {
+            let error = |kind| PgnError { line, column, kind };
+            self.next += 1;
+            match token {
+                Token::Tag(..) if main_line => {
+                    // The next game begins.
+                    self.next -= 1;
+                    break;
+                }
+                Token::Tag(..) => return Err(error(PgnErrorKind::UnbalancedVariation)),
+                Token::Symbol(symbol) if is_result(&symbol) => {
+                    if main_line {
+                        return Ok((moves, Some(symbol)));
+                    }
+                }
+                Token::Symbol(symbol) if is_move_number(&symbol) => {}
+                Token::Symbol(san) => {
+                    let mv = Move::from_san(&san, &board)
+                        .map_err(|move_error| error(PgnErrorKind::IllegalMove { san: san.clone(), error: move_error }))?;
+                    let mut pgn_move = PgnMove::new(mv, mv.to_san(&board));
+                    pgn_move.comments_before = std::mem::take(&mut pending_comments);
+                    before_last = board.clone();
+                    board.make_move(mv);
+                    moves.push(pgn_move);
+                    if let Some(locations) = locations.as_deref_mut() {
+                        locations.push((line, column));
+                    }
+                }
+                Token::Comment(text) => match moves.last_mut() {
+                    Some(last) => last.comments.push(text),
+                    None => pending_comments.push(text),
+                },
+                Token::Nag(nag) => {
+                    if let Some(last) = moves.last_mut() {
+                        last.nags.push(nag);
+                    }
+                }
+                Token::Suffix(suffix) => {
+                    if let (Some(last), Some(nag)) = (moves.last_mut(), suffix_nag(&suffix)) {
+                        last.nags.push(nag);
+                    }
+                }
+                Token::OpenVariation => {
+                    if moves.is_empty() {
+                        return Err(error(PgnErrorKind::UnbalancedVariation));
+                    }
+                    let (variation, _) = self.line(&before_last, None)?;
+                    moves.last_mut().expect("checked above").variations.push(variation);
+                }
+                Token::CloseVariation if main_line => return Err(error(PgnErrorKind::UnbalancedVariation)),
+                Token::CloseVariation => return Ok((moves, None)),
+            }
+        }
+        if !main_line {
+            let (line, column) = self.end;
+            return Err(PgnError { line, column, kind: PgnErrorKind::UnbalancedVariation });
+        }
+        Ok((moves, None))
+    }
+}
+
+impl Game {
+    // The game as PGN: the Seven Tag Roster, SetUp and FEN for games that
+    // didn't start from the usual position, a Termination tag once the game
+    // is over, then the moves in SAN. Timed games get each move's remaining
+    // clock as a [%clk] comment.
+    //
+    // Event, site and round aren't tracked, so they are "?" (unknown) and
+    // "-" (not applicable). The date is that of the first move.
+    pub fn to_pgn(&self) -> String {
+        self.to_pgn_game().to_pgn()
+    }
+
+    // The game as a PgnGame, tags and all, for adding annotations to before
+    // writing it out.
+    pub fn to_pgn_game(&self) -> PgnGame {
+        let result = self.result().map_or("*", |result| result.as_str());
+        let date = self.history().first().map_or("????.??.??".to_string(), |entry| pgn_date(entry.played_at()));
+        let mut tags = vec![
+            ("Event", "?".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", date),
+            ("Round", "-".to_string()),
+            ("White", self.player(PieceColor::White).unwrap_or("?").to_string()),
+            ("Black", self.player(PieceColor::Black).unwrap_or("?").to_string()),
+            ("Result", result.to_string()),
+        ];
+        if self.start_board().to_fen() != Board::new().to_fen() {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", self.start_board().to_fen()));
+        }
+        if let Some(termination) = self.termination() {
+            tags.push(("Termination", termination.pgn_tag().to_string()));
+        }
+
+        let moves = self
+            .history()
+            .iter()
+            .map(|entry| {
+                let mut pgn_move = PgnMove::new(entry.mv(), entry.san().to_string());
+                pgn_move.comments.extend(entry.clock_comment());
+                pgn_move
+            })
+            .collect();
+        PgnGame {
+            tags: tags.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+            start: self.start_board().clone(),
+            moves,
+            result: result.to_string(),
+        }
+    }
+
+    // Reads one game from PGN text: the tag pairs, then the moves up to the
+    // result. The players come from the White and Black tags and the start
+    // position from FEN, if present.
+    //
+    // Only the main line is played; comments, NAGs and variations are
+    // checked but left out (PgnGame::parse keeps them). A result the moves
+    // don't explain ("1-0" without a mate, say) ends the game by
+    // resignation, or on time when the Termination tag says so; an
+    // unexplained draw is taken as agreed.
+    pub fn from_pgn(text: &str) -> Result<Game, PgnError> {
+        let (pgn, locations) = Parser::read(text)?;
+        let mut game = Game::from_board(pgn.start.clone());
+        for (color, name) in [(PieceColor::White, "White"), (PieceColor::Black, "Black")] {
+            if let Some(player) = pgn.tag(name).filter(|value| !value.is_empty() && *value != "?") {
+                game.set_player(color, Some(player.to_string()));
+            }
+        }
+        // The moves are legal, but the game may have ended before the file
+        // stops, by fivefold repetition say.
+        for (pgn_move, &(line, column)) in pgn.moves.iter().zip(&locations) {
+            game.make_move(pgn_move.mv).map_err(|error| PgnError {
+                line,
+                column,
+                kind: PgnErrorKind::IllegalMove { san: pgn_move.san.clone(), error },
+            })?;
+        }
+
+        if !game.is_over() {
+            let on_time = pgn.tag("Termination") == Some("time forfeit");
+            let ending = match pgn.result.as_str() {
+                "1-0" => Some(GameResult::WhiteWins),
+                "0-1" => Some(GameResult::BlackWins),
+                "1/2-1/2" => Some(GameResult::Draw),
+                _ => None,
+            };
+            // None of these can fail: the game isn't over yet.
+            let _ = match (ending, on_time) {
+                (None, _) => Ok(()),
+                (Some(GameResult::Draw), _) => game.agree_draw(),
+                (Some(result), true) => game.finish(Some(result), Termination::Timeout),
+                (Some(GameResult::WhiteWins), false) => game.resign(PieceColor::Black),
+                (Some(GameResult::BlackWins), false) => game.resign(PieceColor::White),
+            };
+        }
+        Ok(game)
+    }
+}
@@ -5,6 +5,10 @@
 //! - Traits or methods defining how each piece moves
 //! - Logic for special moves (castling, en passant)
 //! - Utility functions for piece-related operations
+//! - Conversion to and from FEN piece letters
+//! - Serialization of pieces, kinds and colors
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 // The #[repr(u8)] attribute tells Rust to represent this enum using an 8-bit unsigned integer.
 // This is an optimization that ensures each variant of the enum takes up only 1 byte of memory.
@@ -16,6 +20,13 @@
 // Rook   = 011
 // Queen  = 100
 // King   = 101
+//
+// Clone, Copy, PartialEq and Eq let us pass kinds around by value and compare
+// them (e.g. `piece.kind() == PieceKind::King`) without any ceremony. Hash
+// lets a kind be a HashMap key, and the ordering follows the numbers below,
+// from pawn up to king. Serialized by name, e.g. "knight".
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum PieceKind {
     Pawn = 0,   // We assign explicit values to each variant.
@@ -31,18 +42,40 @@ pub enum PieceKind {
 // In binary, this looks like:
 // White = 0
 // Black = 1
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum PieceColor {
     White = 0,
     Black = 1,
 }
 
+impl PieceColor {
+    // Returns the other side. Handy for "whose turn is next" and for asking
+    // whether the enemy attacks a square.
+    pub fn opposite(self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+
+    // The color as an array index (0 for White, 1 for Black), matching the
+    // enum's numeric representation. Used for per-side tables like clocks.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
 // This line automatically implements several traits for our Piece struct:
 // - Clone: Allows us to create a deep copy of a Piece
 // - Copy: Indicates that Piece can be copied by simply copying its bits (no need for deep copy)
 // - PartialEq: Allows us to compare Pieces using == and !=
 // - Eq: Indicates that == is an equivalence relation (reflexive, symmetric, and transitive)
-#[derive(Clone, Copy, PartialEq, Eq)]
+// - Hash: Allows a Piece to be a key in a HashMap or HashSet
+// - PartialOrd/Ord: Compare the packed byte, so all White pieces sort before
+//   all Black ones, and within a color pawns come first and kings last
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Piece {
     // Instead of storing the kind and color as separate fields,
     // we store all the information in a single byte (u8).
@@ -98,11 +131,12 @@ impl Piece {
         //   ---------------
         //   0 0 0 0 0 0 0 1  (result: 1, which corresponds to Knight)
         //
-        // The `unsafe` block is used because `transmute` is an unsafe operation.
-        // `transmute` reinterprets the bits of one type as another type.
-        // It's unsafe because Rust can't guarantee that the conversion is valid.
-        // We know it's safe here because we've ensured that the value is always 0-5.
-        unsafe { std::mem::transmute(self.data & 0b111) }
+        // Three bits can hold 6 and 7 too, which aren't kinds. We could
+        // reinterpret the bits with `std::mem::transmute`, but if a bad byte
+        // ever slipped in that would be undefined behaviour. Instead the
+        // checked conversion is used; Piece::new and Piece::from_raw only
+        // ever store valid kinds, so it can't fail.
+        PieceKind::try_from(self.data & 0b111).expect("a Piece always holds a valid kind")
     }
 
     // This method extracts the color from the data byte.
@@ -118,7 +152,173 @@ impl Piece {
         //
         // After shifting, the value will be either 0 (White) or 1 (Black),
         // which corresponds to our PieceColor enum values.
-        unsafe { std::mem::transmute(self.data >> 7) }
+        PieceColor::try_from(self.data >> 7).expect("one bit is always a valid color")
+    }
+}
+
+impl Piece {
+    // Rebuilds a piece from its packed byte, as returned by `to_raw`. Bytes
+    // that don't describe a piece (a kind of 6 or 7, or any of the unused
+    // bits set) are rejected, so anything read back from storage or the
+    // network is checked before it becomes a Piece.
+    pub fn from_raw(data: u8) -> Option<Piece> {
+        if data & 0b0111_1000 != 0 {
+            return None;
+        }
+        let kind = PieceKind::try_from(data & 0b111).ok()?;
+        let color = PieceColor::try_from(data >> 7).ok()?;
+        Some(Piece::new(kind, color))
+    }
+
+    // The packed byte itself.
+    pub fn to_raw(self) -> u8 {
+        self.data
+    }
+
+    // The piece's letter in FEN: P, N, B, R, Q or K, uppercase for White and
+    // lowercase for Black. FEN, the ASCII board and anything else that writes
+    // pieces as letters should go through this (and `from_fen_char`) so they
+    // all agree.
+    pub fn to_fen_char(self) -> char {
+        let c = match self.kind() {
+            PieceKind::Pawn => 'p',
+            PieceKind::Knight => 'n',
+            PieceKind::Bishop => 'b',
+            PieceKind::Rook => 'r',
+            PieceKind::Queen => 'q',
+            PieceKind::King => 'k',
+        };
+        match self.color() {
+            PieceColor::White => c.to_ascii_uppercase(),
+            PieceColor::Black => c,
+        }
+    }
+
+    // The inverse of `to_fen_char`: the case of the letter gives the color.
+    // Returns None for anything that isn't one of the twelve piece letters.
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let kind = match c.to_ascii_lowercase() {
+            'p' => PieceKind::Pawn,
+            'n' => PieceKind::Knight,
+            'b' => PieceKind::Bishop,
+            'r' => PieceKind::Rook,
+            'q' => PieceKind::Queen,
+            'k' => PieceKind::King,
+            _ => return None,
+        };
+        Some(Piece::new(kind, color))
+    }
+}
+
+// The error from converting a byte that doesn't stand for a kind, color or
+// piece.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InvalidPieceByte(pub u8);
+
+impl std::fmt::Display for InvalidPieceByte {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010b} is not a valid piece encoding", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPieceByte {}
+
+// The inverse of `kind as u8`: 0 is a pawn up to 5 for a king.
+impl TryFrom<u8> for PieceKind {
+    type Error = InvalidPieceByte;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PieceKind::Pawn),
+            1 => Ok(PieceKind::Knight),
+            2 => Ok(PieceKind::Bishop),
+            3 => Ok(PieceKind::Rook),
+            4 => Ok(PieceKind::Queen),
+            5 => Ok(PieceKind::King),
+            _ => Err(InvalidPieceByte(value)),
+        }
+    }
+}
+
+// The inverse of `color as u8`: 0 is White and 1 is Black.
+impl TryFrom<u8> for PieceColor {
+    type Error = InvalidPieceByte;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PieceColor::White),
+            1 => Ok(PieceColor::Black),
+            _ => Err(InvalidPieceByte(value)),
+        }
+    }
+}
+
+// `Piece::try_from(byte)` is `from_raw` with an error instead of None.
+impl TryFrom<u8> for Piece {
+    type Error = InvalidPieceByte;
+
+    fn try_from(data: u8) -> Result<Self, Self::Error> {
+        Piece::from_raw(data).ok_or(InvalidPieceByte(data))
+    }
+}
+
+// The error from `Piece::try_from(c)`: the character that isn't a piece.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InvalidPieceChar(pub char);
+
+impl std::fmt::Display for InvalidPieceChar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a piece letter", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPieceChar {}
+
+// `Piece::try_from('n')` is `from_fen_char` with an error instead of None.
+impl TryFrom<char> for Piece {
+    type Error = InvalidPieceChar;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Piece::from_fen_char(c).ok_or(InvalidPieceChar(c))
+    }
+}
+
+// In JSON a piece is its FEN letter, e.g. "N" for a white knight. Binary
+// formats store the packed byte, which is checked with `from_raw` on the way
+// back in.
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_char(self.to_fen_char())
+        } else {
+            serializer.serialize_u8(self.data)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let c = char::deserialize(deserializer)?;
+            Piece::try_from(c).map_err(de::Error::custom)
+        } else {
+            let data = u8::deserialize(deserializer)?;
+            Piece::try_from(data).map_err(de::Error::custom)
+        }
+    }
+}
+
+// Deriving Debug would print the packed byte, e.g. `Piece { data: 132 }`,
+// which is no help when a test fails. Instead we decode it first and print
+// `Piece(Black Queen)`.
+impl std::fmt::Debug for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Piece({:?} {:?})", self.color(), self.kind())
     }
 }
 
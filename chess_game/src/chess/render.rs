@@ -0,0 +1,71 @@
+//! Text renderings of the board for people.
+//! 
+//! This file includes:
+//! - A Unicode diagram using chess glyphs, for terminals and chat bots
+//! - Drawing the board from either player's side
+
+use std::fmt;
+
+use crate::chess::board::Board;
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::square::Square;
+
+// The glyph for a piece. Unicode has a white and a black set; "white" here
+// means outlined, which is how White's pieces are usually drawn.
+pub fn piece_glyph(piece: Piece) -> char {
+    match (piece.color(), piece.kind()) {
+        (PieceColor::White, PieceKind::King) => '♔',
+        (PieceColor::White, PieceKind::Queen) => '♕',
+        (PieceColor::White, PieceKind::Rook) => '♖',
+        (PieceColor::White, PieceKind::Bishop) => '♗',
+        (PieceColor::White, PieceKind::Knight) => '♘',
+        (PieceColor::White, PieceKind::Pawn) => '♙',
+        (PieceColor::Black, PieceKind::King) => '♚',
+        (PieceColor::Black, PieceKind::Queen) => '♛',
+        (PieceColor::Black, PieceKind::Rook) => '♜',
+        (PieceColor::Black, PieceKind::Bishop) => '♝',
+        (PieceColor::Black, PieceKind::Knight) => '♞',
+        (PieceColor::Black, PieceKind::Pawn) => '♟',
+    }
+}
+
+// Draws a board with Unicode chess glyphs, seen from `perspective`'s side:
+// for Black the 1st rank is at the top and the h-file on the left, the way
+// the board looks from across the table. Build one with Board::unicode and
+// print it with `{}`.
+//
+// This is kept apart from Board's own Display, which stays plain ASCII so it
+// can go into log files and terminals that can't show these characters.
+pub struct UnicodeBoard<'a> {
+    board: &'a Board,
+    perspective: PieceColor,
+}
+
+impl Board {
+    pub fn unicode(&self, perspective: PieceColor) -> UnicodeBoard<'_> {
+        UnicodeBoard { board: self, perspective }
+    }
+}
+
+impl fmt::Display for UnicodeBoard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Ranks top to bottom and files left to right, as this side sees them.
+        let (ranks, files): (Vec<u8>, Vec<u8>) = match self.perspective {
+            PieceColor::White => ((0..8).rev().collect(), (0..8).collect()),
+            PieceColor::Black => ((0..8).collect(), (0..8).rev().collect()),
+        };
+
+        for &rank in &ranks {
+            let row: Vec<String> = files
+                .iter()
+                .map(|&file| {
+                    let square = Square::new(file, rank).expect("in range");
+                    self.board.piece_at(square).map_or('·', piece_glyph).to_string()
+                })
+                .collect();
+            writeln!(f, "{} {}", rank + 1, row.join(" "))?;
+        }
+        let letters: Vec<String> = files.iter().map(|&file| ((b'a' + file) as char).to_string()).collect();
+        writeln!(f, "  {}", letters.join(" "))
+    }
+}
@@ -0,0 +1,153 @@
+//! Board coordinates.
+//! 
+//! This file defines:
+//! - The Square type, a single cell of the 8x8 board
+//! - Conversions between squares, file/rank pairs and algebraic names ("e4")
+//! - Offset, distance and direction helpers used by move generation
+//! - Serialization as a name ("e4") or, in binary formats, an index
+
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// A square is stored as a single index from 0 to 63, just like Piece packs
+// everything into one byte.
+//
+// The numbering goes file by file, then rank by rank, starting at a1:
+//
+//   8 | 56 57 58 59 60 61 62 63
+//   7 | 48 49 50 51 52 53 54 55
+//   ...
+//   2 |  8  9 10 11 12 13 14 15
+//   1 |  0  1  2  3  4  5  6  7
+//     +------------------------
+//        a  b  c  d  e  f  g  h
+//
+// So the file is `index % 8` and the rank is `index / 8`. Squares sort in
+// that same order, a1 first and h8 last.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    // Builds a square from a zero-based file (0 = a) and rank (0 = rank 1).
+    // Returns None if either coordinate is off the board.
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if file < 8 && rank < 8 {
+            Some(Square(rank * 8 + file))
+        } else {
+            None
+        }
+    }
+
+    // Builds a square from its 0..64 index.
+    pub fn from_index(index: usize) -> Option<Square> {
+        if index < 64 {
+            Some(Square(index as u8))
+        } else {
+            None
+        }
+    }
+
+    // Parses an algebraic square name such as "e4". Only lowercase files are
+    // accepted, matching FEN and UCI.
+    pub fn from_algebraic(name: &str) -> Option<Square> {
+        let bytes = name.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        let file = bytes[0].wrapping_sub(b'a');
+        let rank = bytes[1].wrapping_sub(b'1');
+        Square::new(file, rank)
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    pub fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    // Moves the square by a number of files and ranks, returning None if that
+    // would fall off the edge of the board. Move generation leans on this to
+    // avoid wrapping from the h-file onto the a-file.
+    pub fn offset(self, file_delta: i8, rank_delta: i8) -> Option<Square> {
+        let file = self.file() as i8 + file_delta;
+        let rank = self.rank() as i8 + rank_delta;
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            Some(Square((rank * 8 + file) as u8))
+        } else {
+            None
+        }
+    }
+
+    // How many files and ranks separate two squares, each counted as a
+    // positive number.
+    pub fn file_distance(self, other: Square) -> u8 {
+        self.file().abs_diff(other.file())
+    }
+
+    pub fn rank_distance(self, other: Square) -> u8 {
+        self.rank().abs_diff(other.rank())
+    }
+
+    // The number of king moves it takes to get from one square to the other.
+    // Endgames care about this a lot: a king can catch a passed pawn only if
+    // it is close enough to the promotion square.
+    pub fn distance(self, other: Square) -> u8 {
+        self.file_distance(other).max(self.rank_distance(other))
+    }
+
+    // If the two squares share a rank, file or diagonal, the single step
+    // (file_delta, rank_delta) that leads from this square towards `other`,
+    // each part being -1, 0 or 1. Returns None for the square itself and for
+    // squares that aren't on a common line, such as a knight's jump apart.
+    pub fn direction_to(self, other: Square) -> Option<(i8, i8)> {
+        let file_delta = other.file() as i8 - self.file() as i8;
+        let rank_delta = other.rank() as i8 - self.rank() as i8;
+        let on_line = file_delta == 0 || rank_delta == 0 || file_delta.abs() == rank_delta.abs();
+        if self == other || !on_line {
+            return None;
+        }
+        Some((file_delta.signum(), rank_delta.signum()))
+    }
+
+    // Iterates over all 64 squares from a1 to h8.
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file()) as char, (b'1' + self.rank()) as char)
+    }
+}
+
+// In JSON a square is its name, "e4"; binary formats store the index.
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let name = String::deserialize(deserializer)?;
+            Square::from_algebraic(&name).ok_or_else(|| de::Error::custom(format!("invalid square '{}'", name)))
+        } else {
+            let index = u8::deserialize(deserializer)?;
+            Square::from_index(index as usize)
+                .ok_or_else(|| de::Error::custom(format!("square index {} is off the board", index)))
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Tactical motif detection.
+//! 
+//! This file includes:
+//! - The Motif type: forks, pins, skewers, discovered attacks, back-rank
+//!   weaknesses and hanging pieces, with the squares involved
+//! - Board::tactical_motifs, which finds every one of them in a position
+//!
+//! Motifs are read off the position as it stands, for both sides, without
+//! searching: a fork is a piece attacking two targets now, not a move that
+//! would set one up. Whether a motif actually wins anything is for a search
+//! to decide; these are the patterns a puzzle or an annotation points at.
+
+use serde::Serialize;
+
+use crate::chess::bitboard::Bitboard;
+use crate::chess::board::{Board, BISHOP_DIRECTIONS, ROOK_DIRECTIONS};
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::square::Square;
+
+// Each motif names the side that can make use of it, which for a pin, say,
+// is the side doing the pinning.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Motif {
+    // One piece attacking two or more enemy pieces, each of which is the
+    // king, worth more than the attacker or undefended.
+    Fork { side: PieceColor, attacker: Square, targets: Vec<Square> },
+    // A piece that can't move off the line from `pinner` without exposing
+    // the more valuable `behind` (an absolute pin when that is the king).
+    Pin { side: PieceColor, pinner: Square, pinned: Square, behind: Square },
+    // The other way round: a valuable piece in front that has to move,
+    // exposing a lesser piece behind it.
+    Skewer { side: PieceColor, attacker: Square, front: Square, behind: Square },
+    // A piece of `side` standing between its own rook, bishop or queen and
+    // an enemy target: moving it away unveils an attack on the target.
+    DiscoveredAttack { side: PieceColor, blocker: Square, slider: Square, target: Square },
+    // A king on its back rank with no way off it and no rook or queen of its
+    // own on the rank, while the other side has one to give mate along it.
+    BackRankWeakness { side: PieceColor, king: Square },
+    // A piece attacked and either undefended or attacked by something worth
+    // less. `side` is the side that can take it.
+    HangingPiece { side: PieceColor, square: Square },
+}
+
+// Rough piece values for judging who gains from a motif. The king is worth
+// more than anything, as a piece can never be traded for it.
+fn worth(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 1,
+        PieceKind::Knight | PieceKind::Bishop => 3,
+        PieceKind::Rook => 5,
+        PieceKind::Queen => 9,
+        PieceKind::King => 100,
+    }
+}
+
+// The first two pieces along a line from `from`, nearest first.
+fn first_two(board: &Board, from: Square, (df, dr): (i8, i8)) -> [Option<(Square, Piece)>; 2] {
+    let mut pieces = std::iter::successors(from.offset(df, dr), |square| square.offset(df, dr))
+        .filter_map(|square| board.piece_at(square).map(|piece| (square, piece)));
+    [pieces.next(), pieces.next()]
+}
+
+// The directions a slider moves in. Empty for other pieces.
+fn slider_directions(kind: PieceKind) -> Vec<(i8, i8)> {
+    match kind {
+        PieceKind::Rook => ROOK_DIRECTIONS.to_vec(),
+        PieceKind::Bishop => BISHOP_DIRECTIONS.to_vec(),
+        PieceKind::Queen => [ROOK_DIRECTIONS, BISHOP_DIRECTIONS].concat(),
+        _ => Vec::new(),
+    }
+}
+
+impl Board {
+    // Every motif in the position, for both sides. White's come first; each
+    // side's are in the order of the Motif variants.
+    pub fn tactical_motifs(&self) -> Vec<Motif> {
+        let mut motifs = Vec::new();
+        for side in [PieceColor::White, PieceColor::Black] {
+            motifs.extend(self.forks(side));
+            motifs.extend(self.line_motifs(side));
+            motifs.extend(self.back_rank_weakness(side));
+            motifs.extend(self.hanging_pieces(side));
+        }
+        motifs
+    }
+
+    // Whether attacking `target` with a piece worth `attacker` threatens to
+    // win something.
+    fn worth_attacking(&self, target: Square, piece: Piece, attacker: i32) -> bool {
+        piece.kind() == PieceKind::King || worth(piece.kind()) > attacker || !self.is_square_attacked(target, piece.color())
+    }
+
+    fn forks(&self, side: PieceColor) -> Vec<Motif> {
+        let mut forks = Vec::new();
+        for (attacker, piece) in self.piece_list() {
+            if piece.color() != side {
+                continue;
+            }
+            let value = worth(piece.kind());
+            let targets: Vec<Square> = self
+                .attacks_from(attacker)
+                .iter_squares()
+                .filter(|&target| {
+                    self.piece_at(target).is_some_and(|victim| {
+                        victim.color() != side
+                            && victim.kind() != PieceKind::Pawn
+                            && self.worth_attacking(target, victim, value)
+                    })
+                })
+                .collect();
+            if targets.len() >= 2 {
+                forks.push(Motif::Fork { side, attacker, targets });
+            }
+        }
+        forks
+    }
+
+    // Pins, skewers and discovered attacks all come from looking along the
+    // lines of `side`'s sliders at the first two pieces in the way.
+    fn line_motifs(&self, side: PieceColor) -> Vec<Motif> {
+        let mut motifs = Vec::new();
+        for (slider, piece) in self.piece_list() {
+            if piece.color() != side {
+                continue;
+            }
+            let value = worth(piece.kind());
+            for direction in slider_directions(piece.kind()) {
+                let [Some((front, first)), Some((behind, second))] = first_two(self, slider, direction) else {
+                    continue;
+                };
+                if second.color() == side {
+                    continue;
+                }
+                if first.color() == side {
+                    // Moving our own piece out of the way opens the line.
+                    if self.worth_attacking(behind, second, value) && second.kind() != PieceKind::Pawn {
+                        motifs.push(Motif::DiscoveredAttack { side, blocker: front, slider, target: behind });
+                    }
+                } else if worth(first.kind()) < worth(second.kind()) {
+                    motifs.push(Motif::Pin { side, pinner: slider, pinned: front, behind });
+                } else if worth(first.kind()) > worth(second.kind()) && second.kind() != PieceKind::Pawn {
+                    motifs.push(Motif::Skewer { side, attacker: slider, front, behind });
+                }
+            }
+        }
+        motifs
+    }
+
+    // Whether the enemy king is stuck on its back rank where `side` has the
+    // heavy pieces to mate it.
+    fn back_rank_weakness(&self, side: PieceColor) -> Option<Motif> {
+        let enemy = side.opposite();
+        let king = self.find_king(enemy)?;
+        let (back_rank, forward) = match enemy {
+            PieceColor::White => (0, 1),
+            PieceColor::Black => (7, -1),
+        };
+        if king.rank() != back_rank {
+            return None;
+        }
+        let heavy = |piece: Piece| matches!(piece.kind(), PieceKind::Rook | PieceKind::Queen);
+        let pieces = self.piece_list();
+        let can_attack = pieces.iter().any(|&(_, piece)| piece.color() == side && heavy(piece));
+        // A rook or queen of its own on the back rank keeps watch over it.
+        let guarded = pieces
+            .iter()
+            .any(|&(square, piece)| piece.color() == enemy && heavy(piece) && square.rank() == back_rank);
+        if !can_attack || guarded {
+            return None;
+        }
+        // Every square off the back rank next to the king is blocked by its
+        // own pieces or covered by the enemy.
+        let boxed_in = (-1..=1).filter_map(|file| king.offset(file, forward)).all(|square| {
+            self.piece_at(square).is_some_and(|piece| piece.color() == enemy) || self.is_square_attacked(square, side)
+        });
+        boxed_in.then_some(Motif::BackRankWeakness { side, king })
+    }
+
+    fn hanging_pieces(&self, side: PieceColor) -> Vec<Motif> {
+        let enemy = side.opposite();
+        // The cheapest of `side`'s pieces attacking each square.
+        let mut cheapest = [None::<i32>; 64];
+        for (square, piece) in self.piece_list() {
+            if piece.color() != side {
+                continue;
+            }
+            for target in self.attacks_from(square).iter_squares() {
+                let slot = &mut cheapest[target.index()];
+                *slot = Some(slot.map_or(worth(piece.kind()), |value| value.min(worth(piece.kind()))));
+            }
+        }
+
+        let mut hanging = Bitboard::EMPTY;
+        for (square, piece) in self.piece_list() {
+            if piece.color() != enemy || piece.kind() == PieceKind::King {
+                continue;
+            }
+            let Some(attacker) = cheapest[square.index()] else {
+                continue;
+            };
+            if !self.is_square_attacked(square, enemy) || attacker < worth(piece.kind()) {
+                hanging |= square;
+            }
+        }
+        hanging.iter_squares().map(|square| Motif::HangingPiece { side, square }).collect()
+    }
+}
@@ -0,0 +1,270 @@
+//! Branching move trees for analysis.
+//! 
+//! This file includes:
+//! - GameTree, a tree of positions whose first child at each node is the
+//!   main line and whose other children are variations
+//! - Node annotations: comments, NAGs and engine evaluations
+//! - Conversion to and from Game and PgnGame
+//!
+//! Game only knows the line that was played; undo throws the rest away. An
+//! analysis board needs to keep every line that was tried, which is what
+//! this is for.
+
+use crate::chess::board::Board;
+use crate::chess::game::Game;
+use crate::chess::pgn::{PgnGame, PgnMove};
+use crate::chess::r#move::{Move, MoveError};
+
+// Refers to one node of a GameTree. Only meaningful for the tree that handed
+// it out.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+// A position in the tree, with the move that led to it.
+#[derive(Clone)]
+pub struct Node {
+    parent: Option<NodeId>,
+    // None only for the root.
+    mv: Option<Move>,
+    san: String,
+    board: Board,
+    // The first child continues the main line; the rest are variations.
+    children: Vec<NodeId>,
+    // Comments on the move, as PGN would put after it.
+    pub comments: Vec<String>,
+    // Numeric annotation glyphs, e.g. 2 for "?".
+    pub nags: Vec<u8>,
+    // An engine's verdict on the position after the move, in centipawns
+    // from White's point of view (not the side to move's, so evals along a
+    // line can be compared directly).
+    pub eval: Option<i32>,
+}
+
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn mv(&self) -> Option<Move> {
+        self.mv
+    }
+
+    // The move in SAN; empty for the root.
+    pub fn san(&self) -> &str {
+        &self.san
+    }
+
+    // The position after the move.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+// A tree of lines from one start position. Nodes live in a single arena and
+// are never freed: removing a line only unlinks it, so ids held elsewhere
+// stay valid (if unreachable) until the tree is dropped.
+#[derive(Clone)]
+pub struct GameTree {
+    nodes: Vec<Node>,
+}
+
+impl GameTree {
+    const ROOT: NodeId = NodeId(0);
+
+    // A tree with just the start position.
+    pub fn new(start: Board) -> Self {
+        let root = Node {
+            parent: None,
+            mv: None,
+            san: String::new(),
+            board: start,
+            children: Vec::new(),
+            comments: Vec::new(),
+            nags: Vec::new(),
+            eval: None,
+        };
+        GameTree { nodes: vec![root] }
+    }
+
+    // The start position.
+    pub fn root(&self) -> NodeId {
+        Self::ROOT
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    // For editing a node's annotations. The move and position can't change.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    // Plays `mv` from `parent`'s position. If that move has been played
+    // there before, its node is returned rather than a duplicate; otherwise
+    // the move becomes the last variation (or the main line, if it is the
+    // first move tried).
+    //
+    // Like Game::make_move, only the squares (and promotion piece) of `mv`
+    // need to be right.
+    pub fn add_move(&mut self, parent: NodeId, mv: Move) -> Result<NodeId, MoveError> {
+        let board = &self.nodes[parent.0].board;
+        let Some(legal) = board.legal_moves().into_iter().find(|candidate| candidate.same_squares(&mv)) else {
+            return Err(board.explain_illegal(&mv).unwrap_or(MoveError::IllegalMove(mv)));
+        };
+        if let Some(&existing) = self.nodes[parent.0]
+            .children
+            .iter()
+            .find(|child| self.nodes[child.0].mv == Some(legal))
+        {
+            return Ok(existing);
+        }
+
+        let san = legal.to_san(board);
+        let mut board = board.clone();
+        board.make_move(legal);
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            parent: Some(parent),
+            mv: Some(legal),
+            san,
+            board,
+            children: Vec::new(),
+            comments: Vec::new(),
+            nags: Vec::new(),
+            eval: None,
+        });
+        self.nodes[parent.0].children.push(id);
+        Ok(id)
+    }
+
+    // The nodes from the root (exclusive) down to `id` (inclusive).
+    pub fn path_to(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.nodes[current.0].parent {
+            path.push(current);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    // The main line: the first child at each step, starting from the root.
+    pub fn main_line(&self) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut current = Self::ROOT;
+        while let Some(&next) = self.nodes[current.0].children.first() {
+            line.push(next);
+            current = next;
+        }
+        line
+    }
+
+    // True if `id` is on the main line.
+    pub fn is_main_line(&self, id: NodeId) -> bool {
+        self.path_to(id).iter().all(|&node| {
+            let parent = self.nodes[node.0].parent.expect("only the root has no parent");
+            self.nodes[parent.0].children[0] == node
+        })
+    }
+
+    // Makes `id` its parent's first child, so its line takes over from the
+    // one that was there. The old main move becomes the first variation.
+    pub fn promote(&mut self, id: NodeId) {
+        if let Some(parent) = self.nodes[id.0].parent {
+            let children = &mut self.nodes[parent.0].children;
+            if let Some(index) = children.iter().position(|&child| child == id) {
+                children.remove(index);
+                children.insert(0, id);
+            }
+        }
+    }
+
+    // Cuts `id` and everything after it out of the tree. The root can't be
+    // removed.
+    pub fn remove(&mut self, id: NodeId) {
+        if let Some(parent) = self.nodes[id.0].parent {
+            self.nodes[parent.0].children.retain(|&child| child != id);
+        }
+    }
+
+    // The tree of a game: its start position and the moves played, as the
+    // main line.
+    pub fn from_game(game: &Game) -> Self {
+        let mut tree = GameTree::new(game.start_board().clone());
+        let mut current = Self::ROOT;
+        for entry in game.history() {
+            current = tree.add_move(current, entry.mv()).expect("the game's moves are legal");
+        }
+        tree
+    }
+
+    // A game that has played the moves leading to `id`. Only that line is
+    // kept; game-level details such as players and clocks aren't part of
+    // the tree.
+    pub fn to_game(&self, id: NodeId) -> Game {
+        let mut game = Game::from_board(self.nodes[Self::ROOT.0].board.clone());
+        for node in self.path_to(id) {
+            // The line was legal when it was added, though a game can end
+            // (by repetition, say) where the tree goes on.
+            if game.make_move(self.nodes[node.0].mv.expect("only the root has no move")).is_err() {
+                break;
+            }
+        }
+        game
+    }
+
+    // The tree of a parsed PGN game, variations and annotations included.
+    pub fn from_pgn_game(pgn: &PgnGame) -> Self {
+        let mut tree = GameTree::new(pgn.start.clone());
+        tree.add_pgn_line(Self::ROOT, &pgn.moves);
+        tree
+    }
+
+    fn add_pgn_line(&mut self, from: NodeId, moves: &[PgnMove]) {
+        let mut current = from;
+        for pgn_move in moves {
+            let parent = current;
+            current = self.add_move(parent, pgn_move.mv).expect("PgnGame moves are legal");
+            let node = &mut self.nodes[current.0];
+            node.comments.extend(pgn_move.comments_before.iter().cloned());
+            node.comments.extend(pgn_move.comments.iter().cloned());
+            node.nags.extend(&pgn_move.nags);
+            for variation in &pgn_move.variations {
+                self.add_pgn_line(parent, variation);
+            }
+        }
+    }
+
+    // The moves below `from` as a PGN line: the main line, with each move's
+    // alternatives as its variations. Evaluations are left out; PGN has no
+    // standard place for them.
+    pub fn pgn_line(&self, from: NodeId) -> Vec<PgnMove> {
+        let mut line = Vec::new();
+        let mut current = from;
+        while let Some((&main, alternatives)) = self.nodes[current.0].children.split_first() {
+            let mut pgn_move = self.pgn_move(main);
+            for &alternative in alternatives {
+                let mut variation = vec![self.pgn_move(alternative)];
+                variation.extend(self.pgn_line(alternative));
+                pgn_move.variations.push(variation);
+            }
+            line.push(pgn_move);
+            current = main;
+        }
+        line
+    }
+
+    fn pgn_move(&self, id: NodeId) -> PgnMove {
+        let node = &self.nodes[id.0];
+        let mut pgn_move = PgnMove::new(node.mv.expect("only the root has no move"), node.san.clone());
+        pgn_move.comments = node.comments.clone();
+        pgn_move.nags = node.nags.clone();
+        pgn_move
+    }
+}
@@ -0,0 +1,145 @@
+//! Sanity checks for arbitrary positions.
+//! 
+//! This file includes:
+//! - The PositionError type, one variant per kind of impossible setup
+//! - Board::validate, for positions typed in by hand or sent as FEN
+
+use std::fmt;
+
+use crate::chess::board::{Board, CastlingRights};
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::square::Square;
+
+// Why a position can't be played from. FEN parsing only checks the syntax,
+// so "8/8/8/8/8/8/8/8 w - - 0 1" parses fine; these are the rules it breaks.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PositionError {
+    // Each side needs exactly one king.
+    KingCount { color: PieceColor, count: usize },
+    // Pawns can never stand on the 1st or 8th rank.
+    PawnOnBackRank(Square),
+    // The side that just moved can't have left its own king in check.
+    OpponentInCheck,
+    // A castling right whose king or rook isn't on its starting square.
+    CastlingWithoutPieces(CastlingRights),
+    // An en passant square that no double pawn push could have produced.
+    ImpossibleEnPassant(Square),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::KingCount { color, count } => {
+                write!(f, "{:?} has {} kings, expected exactly one", color, count)
+            }
+            PositionError::PawnOnBackRank(square) => write!(f, "pawn on {} is on a back rank", square),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            PositionError::CastlingWithoutPieces(_) => {
+                write!(f, "castling rights don't match the king and rook positions")
+            }
+            PositionError::ImpossibleEnPassant(square) => {
+                write!(f, "en passant square {} doesn't follow a double pawn push", square)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl Board {
+    // Checks that the position makes sense to play from: one king each, no
+    // pawns on the back ranks, the side that just moved not in check,
+    // castling rights backed by a king and rook on their home squares, and an
+    // en passant square that a double pawn push really could have left.
+    //
+    // This doesn't prove the position can be reached from the start (that
+    // would need a retrograde search), but it catches everything that would
+    // confuse move generation or the engine.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            self.validate_king_count(color)?;
+        }
+        self.validate_back_ranks(false)?;
+
+        if self.king_in_check(self.side_to_move().opposite()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        self.validate_castling()?;
+        self.validate_en_passant()
+    }
+
+    // The pieces of `validate` below are also used by variants whose
+    // positions break some of the standard rules.
+
+    pub(crate) fn validate_king_count(&self, color: PieceColor) -> Result<(), PositionError> {
+        let king = Piece::new(PieceKind::King, color);
+        let count = Square::all().filter(|&sq| self.piece_at(sq) == Some(king)).count();
+        if count != 1 {
+            return Err(PositionError::KingCount { color, count });
+        }
+        Ok(())
+    }
+
+    // No pawn on the 1st or 8th rank, though Horde (`white_first_rank`)
+    // starts with White pawns on the 1st.
+    pub(crate) fn validate_back_ranks(&self, white_first_rank: bool) -> Result<(), PositionError> {
+        let allowed = |sq: Square, piece: Piece| {
+            white_first_rank && sq.rank() == 0 && piece.color() == PieceColor::White
+        };
+        if let Some(square) = Square::all().find(|&sq| {
+            (sq.rank() == 0 || sq.rank() == 7)
+                && self
+                    .piece_at(sq)
+                    .is_some_and(|piece| piece.kind() == PieceKind::Pawn && !allowed(sq, piece))
+        }) {
+            return Err(PositionError::PawnOnBackRank(square));
+        }
+        Ok(())
+    }
+
+    // Each castling right needs the king on e1/e8 and the rook in the
+    // matching corner.
+    pub(crate) fn validate_castling(&self) -> Result<(), PositionError> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let rank = if color == PieceColor::White { 0 } else { 7 };
+            let has = |file: u8, kind: PieceKind| {
+                let square = Square::new(file, rank).expect("in range");
+                self.piece_at(square) == Some(Piece::new(kind, color))
+            };
+            for (right, rook_file) in [
+                (CastlingRights::kingside(color), 7),
+                (CastlingRights::queenside(color), 0),
+            ] {
+                if self.castling_rights().contains(right)
+                    && !(has(4, PieceKind::King) && has(rook_file, PieceKind::Rook))
+                {
+                    return Err(PositionError::CastlingWithoutPieces(right));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // After a double push the en passant square is the one the pawn skipped:
+    // on the 3rd rank if White just moved, the 6th if Black did. It and the
+    // pawn's starting square must be empty, and the pawn must be just past it.
+    pub(crate) fn validate_en_passant(&self) -> Result<(), PositionError> {
+        let Some(target) = self.en_passant() else {
+            return Ok(());
+        };
+        let mover = self.side_to_move().opposite();
+        let (rank, forward) = match mover {
+            PieceColor::White => (2, 1),
+            PieceColor::Black => (5, -1),
+        };
+        let pawn_there = target
+            .offset(0, forward)
+            .is_some_and(|sq| self.piece_at(sq) == Some(Piece::new(PieceKind::Pawn, mover)));
+        let start_empty = target.offset(0, -forward).is_some_and(|sq| self.piece_at(sq).is_none());
+        if target.rank() != rank || self.piece_at(target).is_some() || !pawn_there || !start_empty {
+            return Err(PositionError::ImpossibleEnPassant(target));
+        }
+        Ok(())
+    }
+}
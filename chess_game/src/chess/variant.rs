@@ -0,0 +1,470 @@
+//! Chess variants.
+//! 
+//! This file includes:
+//! - The Variant trait: what a variant can change about the rules
+//! - Standard, the usual rules, which every other variant starts from
+//! - King of the Hill, Three-check and Atomic
+//! - Antichess and Horde, which start from their own positions
+//! - Looking variants up by name, as games are created and loaded
+//!
+//! A Game holds its variant and asks it for the start position, the legal
+//! moves and whether the game has ended, so adding a variant doesn't mean
+//! touching Game or Board. The trait is generic over the board
+//! representation like Game is; Standard plays on any of them, the other
+//! variants on Board.
+
+use std::sync::Arc;
+
+use crate::chess::board::{king_attacks, Board, BoardRepr};
+use crate::chess::fen::FenError;
+use crate::chess::game::{Game, GameResult, Termination};
+use crate::chess::piece::{PieceColor, PieceKind};
+use crate::chess::r#move::{Move, MoveFlag, UndoInfo};
+use crate::chess::validate::PositionError;
+
+// The pieces a pawn can become in standard chess.
+pub const STANDARD_PROMOTIONS: [PieceKind; 4] = [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight];
+
+// What `Variant::read_fen` hands back besides the position: the variant set
+// up with any state the FEN carried, or None to keep it as it is.
+pub type ConfiguredVariant<B> = Option<Arc<dyn Variant<B>>>;
+
+// The rules of a kind of chess. Every method has the standard rules as its
+// default, so a variant only overrides what it changes.
+pub trait Variant<B: BoardRepr = Board>: Send + Sync {
+    // A short lowercase name, used in the API and in saved games, e.g.
+    // "standard" or "kingofthehill". Must be unique.
+    fn name(&self) -> &'static str;
+
+    // The position new games start from and go back to on reset.
+    fn start_position(&self) -> B {
+        B::from_board(&Board::new())
+    }
+
+    // The pieces a pawn may promote to.
+    fn promotion_pieces(&self) -> &'static [PieceKind] {
+        &STANDARD_PROMOTIONS
+    }
+
+    // Every move the side to move may play. By default the standard legal
+    // moves, less promotions to pieces the variant doesn't allow.
+    fn legal_moves(&self, board: &B) -> Vec<Move> {
+        let allowed = self.promotion_pieces();
+        board
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.promotion().is_none_or(|kind| allowed.contains(&kind)))
+            .collect()
+    }
+
+    // Plays a move from `legal_moves` on the board. Game keeps the position
+    // from before every move, so a variant whose moves do more than the
+    // board's make_move can undo doesn't have to be able to take them back.
+    fn play(&self, board: &mut B, mv: Move) -> B::Undo {
+        board.make_move(mv)
+    }
+
+    // True if `color`'s king is in check.
+    fn in_check(&self, board: &B, color: PieceColor) -> bool {
+        board.king_in_check(color)
+    }
+
+    // How the game has ended by the rules, if it has, looked at after every
+    // move. Endings decided by the players (resignation, agreement, time)
+    // are handled by Game and never come through here.
+    fn game_end(&self, game: &Game<B>) -> Option<(GameResult, Termination)> {
+        standard_game_end(game)
+    }
+
+    // Reads a position in this variant's FEN. A variant that keeps state
+    // FEN has no field for extends it, and returns itself set up with that
+    // state as well; None means this variant as it is.
+    fn read_fen(&self, fen: &str) -> Result<(B, ConfiguredVariant<B>), FenError> {
+        Ok((B::from_board(&Board::from_fen(fen)?), None))
+    }
+
+    // The game's current position in this variant's FEN.
+    fn write_fen(&self, game: &Game<B>) -> String {
+        game.current_fen()
+    }
+
+    // Checks a position set up by hand can be played from in this variant.
+    fn validate(&self, board: &B) -> Result<(), PositionError> {
+        board.to_board().validate()
+    }
+}
+
+// The usual rules of chess.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Standard;
+
+impl<B: BoardRepr> Variant<B> for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+}
+
+// How a game ends under the standard rules. If the side to move has no
+// legal moves it is checkmate or stalemate. It is also drawn as soon as
+// neither side can mate any more, for lack of material or because
+// everything is locked up, rather than playing on pointlessly. After 75
+// moves each without a pawn move or capture, or when a position occurs for
+// the fifth time, the game is drawn too, unless the last move was mate,
+// which is why that is checked first.
+//
+// Variants that only add a way to win can check for it and fall back on
+// this.
+pub fn standard_game_end<B: BoardRepr>(game: &Game<B>) -> Option<(GameResult, Termination)> {
+    Some(if game.legal_moves().is_empty() {
+        if game.is_in_check() {
+            (GameResult::win_for(game.side_to_move().opposite()), Termination::Checkmate)
+        } else {
+            (GameResult::Draw, Termination::Stalemate)
+        }
+    } else if game.is_insufficient_material() {
+        (GameResult::Draw, Termination::InsufficientMaterial)
+    } else if game.is_dead_position() {
+        (GameResult::Draw, Termination::DeadPosition)
+    } else {
+        return move_count_draw(game);
+    })
+}
+
+// The 75-move rule and fivefold repetition, which every variant keeps even
+// when its other endings are different.
+fn move_count_draw<B: BoardRepr>(game: &Game<B>) -> Option<(GameResult, Termination)> {
+    if game.board().halfmove_clock() >= 150 {
+        Some((GameResult::Draw, Termination::SeventyFiveMoveRule))
+    } else if game.repetition_count() >= 5 {
+        Some((GameResult::Draw, Termination::FivefoldRepetition))
+    } else {
+        None
+    }
+}
+
+// A king on one of these wins King of the Hill.
+const HILL: [(u8, u8); 4] = [(3, 3), (4, 3), (3, 4), (4, 4)];
+
+// Standard chess, but a king that reaches one of the four centre squares
+// wins on the spot.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct KingOfTheHill;
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "kingofthehill"
+    }
+
+    fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let on_hill = game
+                .board()
+                .find_king(color)
+                .is_some_and(|king| HILL.contains(&(king.file(), king.rank())));
+            if on_hill {
+                return Some((GameResult::win_for(color), Termination::VariantEnd));
+            }
+        }
+        standard_game_end(game)
+    }
+}
+
+// Standard chess, but giving check for the third time wins.
+//
+// FEN gets an extra field after the en passant square with the checks each
+// side still needs, White's first: "3+3" at the start of a game. Positions
+// set up with checks already given keep them here; the rest are counted
+// from the moves.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct ThreeCheck {
+    // Checks given before the game's first move, indexed by color.
+    pub initial_checks: [u8; 2],
+}
+
+impl ThreeCheck {
+    pub const CHECKS_TO_WIN: u8 = 3;
+
+    // How many checks `color` has given so far.
+    pub fn checks_given(&self, game: &Game, color: PieceColor) -> u8 {
+        let first_mover = game.start_board().side_to_move();
+        let given = game
+            .history()
+            .iter()
+            .enumerate()
+            .filter(|(ply, entry)| {
+                let mover = if ply % 2 == 0 { first_mover } else { first_mover.opposite() };
+                mover == color && (entry.san().ends_with('+') || entry.san().ends_with('#'))
+            })
+            .count();
+        self.initial_checks[color.index()].saturating_add(given as u8)
+    }
+}
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "threecheck"
+    }
+
+    fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+        // Only the side that just moved can have given a check.
+        let mover = game.side_to_move().opposite();
+        if self.checks_given(game, mover) >= Self::CHECKS_TO_WIN {
+            // A third check that is also mate is still mate.
+            let mate = game.legal_moves().is_empty();
+            return Some((GameResult::win_for(mover), if mate { Termination::Checkmate } else { Termination::VariantEnd }));
+        }
+        standard_game_end(game)
+    }
+
+    // Takes the remaining checks out of the FEN, or assumes none have been
+    // given if the field is missing.
+    fn read_fen(&self, fen: &str) -> Result<(Board, ConfiguredVariant<Board>), FenError> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        let Some(index) = fields.iter().position(|field| field.contains('+')) else {
+            return Ok((Board::from_fen(fen)?, Some(Arc::new(ThreeCheck::default()))));
+        };
+        let remaining = fields.remove(index);
+        let bad = || FenError::WrongFieldCount(fen.split_whitespace().count());
+        let (white, black) = remaining.split_once('+').ok_or_else(bad)?;
+        let given = |text: &str| -> Result<u8, FenError> {
+            let left: u8 = text.parse().map_err(|_| bad())?;
+            Self::CHECKS_TO_WIN.checked_sub(left).ok_or_else(bad)
+        };
+        let variant = ThreeCheck { initial_checks: [given(white)?, given(black)?] };
+        Ok((Board::from_fen(&fields.join(" "))?, Some(Arc::new(variant))))
+    }
+
+    fn write_fen(&self, game: &Game) -> String {
+        let fen = game.board().to_fen();
+        let mut fields: Vec<String> = fen.split(' ').map(str::to_string).collect();
+        let left = |color| Self::CHECKS_TO_WIN.saturating_sub(self.checks_given(game, color));
+        fields.insert(4, format!("{}+{}", left(PieceColor::White), left(PieceColor::Black)));
+        fields.join(" ")
+    }
+}
+
+// Captures explode: the capturing piece, the captured one and every piece
+// other than a pawn next to the capture square are removed. Blowing up the
+// enemy king wins, even out of check; blowing up one's own king is never
+// allowed, so kings can't capture at all. Kings standing next to each other
+// can't give check, since capturing either would destroy both.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Atomic;
+
+impl Atomic {
+    fn kings_touch(board: &Board) -> bool {
+        match (board.find_king(PieceColor::White), board.find_king(PieceColor::Black)) {
+            (Some(white), Some(black)) => king_attacks(white).contains(black),
+            _ => false,
+        }
+    }
+}
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "atomic"
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        let us = board.side_to_move();
+        let mut moves = board.generate_pseudo_legal();
+        moves.retain(|mv| {
+            let king_captures = board.piece_at(mv.from()).is_some_and(|piece| piece.kind() == PieceKind::King);
+            if king_captures && board.piece_at(mv.to()).is_some() {
+                return false;
+            }
+            let mut after = board.clone();
+            self.play(&mut after, *mv);
+            match (after.find_king(us), after.find_king(us.opposite())) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(_), Some(_)) => !self.in_check(&after, us),
+            }
+        });
+        moves
+    }
+
+    fn play(&self, board: &mut Board, mv: Move) -> UndoInfo {
+        let undo = board.make_move(mv);
+        if undo.captured().is_some() {
+            board.remove_piece(mv.to());
+            for square in king_attacks(mv.to()).iter_squares() {
+                if board.piece_at(square).is_some_and(|piece| piece.kind() != PieceKind::Pawn) {
+                    board.remove_piece(square);
+                }
+            }
+        }
+        undo
+    }
+
+    fn in_check(&self, board: &Board, color: PieceColor) -> bool {
+        !Atomic::kings_touch(board) && board.king_in_check(color)
+    }
+
+    fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            if game.board().find_king(color).is_none() {
+                return Some((GameResult::win_for(color.opposite()), Termination::VariantEnd));
+            }
+        }
+        standard_game_end(game)
+    }
+}
+
+// The pieces a pawn can become in Antichess, where the king is just
+// another piece.
+pub const ANTICHESS_PROMOTIONS: [PieceKind; 5] =
+    [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight, PieceKind::King];
+
+// The first player to lose all their pieces, or to be left without a move,
+// wins. Capturing is compulsory: if any capture is possible one of them has
+// to be played. There is no check, so the king can be taken like anything
+// else, a pawn may promote to a king, and there is no castling.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Antichess;
+
+impl Antichess {
+    const START_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+}
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "antichess"
+    }
+
+    fn start_position(&self) -> Board {
+        Board::from_fen(Self::START_FEN).expect("the Antichess start position is valid")
+    }
+
+    fn promotion_pieces(&self) -> &'static [PieceKind] {
+        &ANTICHESS_PROMOTIONS
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for mv in board.generate_pseudo_legal() {
+            if matches!(mv.flag(), MoveFlag::CastleKingside | MoveFlag::CastleQueenside) {
+                continue;
+            }
+            moves.push(mv);
+            // The board only generates the standard promotions.
+            if mv.promotion() == Some(PieceKind::Queen) {
+                moves.push(Move::with_promotion(mv.from(), mv.to(), PieceKind::King));
+            }
+        }
+        let is_capture = |mv: &Move| mv.flag() == MoveFlag::EnPassant || board.piece_at(mv.to()).is_some();
+        if moves.iter().any(is_capture) {
+            moves.retain(is_capture);
+        }
+        moves
+    }
+
+    fn in_check(&self, _board: &Board, _color: PieceColor) -> bool {
+        false
+    }
+
+    // Having no pieces left means having no moves, so one check covers both
+    // ways to win. Neither side can run out of mating material, so the only
+    // draws are by the move count.
+    fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+        if game.legal_moves().is_empty() {
+            return Some((GameResult::win_for(game.side_to_move()), Termination::VariantEnd));
+        }
+        move_count_draw(game)
+    }
+
+    // Any number of kings, including none, and castling rights are ignored.
+    fn validate(&self, board: &Board) -> Result<(), PositionError> {
+        board.validate_back_ranks(false)?;
+        board.validate_en_passant()
+    }
+}
+
+// White has 36 pawns and no king against Black's usual army. Black wins by
+// taking every White piece; White wins by checkmate as usual. White pawns on
+// the first rank may advance two squares, as from the second, though without
+// allowing an en passant capture.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Horde;
+
+impl Horde {
+    const START_FEN: &'static str = "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+}
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "horde"
+    }
+
+    fn start_position(&self) -> Board {
+        Board::from_fen(Self::START_FEN).expect("the Horde start position is valid")
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        let mut moves = board.legal_moves();
+        let us = board.side_to_move();
+        if us != PieceColor::White {
+            return moves;
+        }
+        for (from, piece) in board.piece_list() {
+            if piece.kind() != PieceKind::Pawn || piece.color() != us || from.rank() != 0 {
+                continue;
+            }
+            let path = [from.offset(0, 1), from.offset(0, 2)];
+            if let [Some(over), Some(to)] = path {
+                if board.piece_at(over).is_none() && board.piece_at(to).is_none() {
+                    // Only a position set up with a White king needs this.
+                    let mut after = board.clone();
+                    after.make_move(Move::new(from, to));
+                    if !after.king_in_check(us) {
+                        moves.push(Move::new(from, to));
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+        let board = game.board();
+        if !board.piece_list().iter().any(|(_, piece)| piece.color() == PieceColor::White) {
+            return Some((GameResult::BlackWins, Termination::VariantEnd));
+        }
+        if game.legal_moves().is_empty() {
+            return Some(if game.is_in_check() {
+                (GameResult::win_for(game.side_to_move().opposite()), Termination::Checkmate)
+            } else {
+                (GameResult::Draw, Termination::Stalemate)
+            });
+        }
+        move_count_draw(game)
+    }
+
+    // Black needs its king; White's pawns may stand on the first rank.
+    fn validate(&self, board: &Board) -> Result<(), PositionError> {
+        board.validate_king_count(PieceColor::Black)?;
+        board.validate_back_ranks(true)?;
+        if board.king_in_check(board.side_to_move().opposite()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+        board.validate_castling()?;
+        board.validate_en_passant()
+    }
+}
+
+// Every variant games can be played in, by name.
+pub fn variants() -> Vec<Arc<dyn Variant>> {
+    vec![
+        Arc::new(Standard),
+        Arc::new(KingOfTheHill),
+        Arc::new(ThreeCheck::default()),
+        Arc::new(Atomic),
+        Arc::new(Antichess),
+        Arc::new(Horde),
+    ]
+}
+
+// The variant with the given name, e.g. from a request or a saved game.
+pub fn variant_by_name(name: &str) -> Option<Arc<dyn Variant>> {
+    variants().into_iter().find(|variant| variant.name() == name)
+}
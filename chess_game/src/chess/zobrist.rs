@@ -0,0 +1,70 @@
+//! Zobrist hashing of positions.
+//! 
+//! This file includes:
+//! - Fixed pseudo-random keys for every piece on every square
+//! - Keys for the side to move, castling rights and en passant file
+//! - The keys Board XORs in and out as the position changes
+
+use crate::chess::board::CastlingRights;
+use crate::chess::piece::Piece;
+use crate::chess::square::Square;
+
+// A Zobrist hash gives every (piece, square) pair its own random 64-bit key
+// and XORs together the keys of everything present in the position. Equal
+// positions always get equal hashes, and different positions almost always
+// get different ones, which makes the hash a cheap key for caches.
+//
+// The keys only need to look random, not be secure, so we generate them at
+// compile time with the small "splitmix64" generator from a fixed seed. That
+// way a hash means the same thing on every run.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+// 12 piece types (6 kinds x 2 colors) x 64 squares, then one key for Black to
+// move, 16 for the castling rights combinations and 8 for en passant files.
+const PIECE_KEYS: usize = 12 * 64;
+const SIDE_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 16;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+const KEYS: [u64; KEY_COUNT] = {
+    let mut keys = [0; KEY_COUNT];
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+};
+
+// Since XOR undoes itself, the hash never has to be worked out from scratch.
+// Board XORs a key in when something is added to the position and the same
+// key again when it goes away, so a move only touches the handful of keys for
+// what it changed. These give it the key for each part of the position.
+pub(crate) fn piece_key(piece: Piece, square: Square) -> u64 {
+    let piece_index = piece.color().index() * 6 + piece.kind() as usize;
+    KEYS[piece_index * 64 + square.index()]
+}
+
+// Present in the hash when Black is to move.
+pub(crate) fn side_key() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+pub(crate) fn castling_key(rights: CastlingRights) -> u64 {
+    KEYS[CASTLING_KEYS + rights.bits() as usize]
+}
+
+// Only the file matters: the rank follows from the side to move.
+pub(crate) fn en_passant_key(square: Square) -> u64 {
+    KEYS[EN_PASSANT_KEYS + square.file() as usize]
+}
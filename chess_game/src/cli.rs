@@ -0,0 +1,20 @@
+//! Command-line arguments.
+//! 
+//! This file handles:
+//! - Finding the value given for a `--name` option
+
+// The value given for `--name` among the command-line arguments (without the
+// program name), written either `--name value` or `--name=value`.
+pub fn arg_value(args: impl IntoIterator<Item = String>, name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
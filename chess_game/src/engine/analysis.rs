@@ -0,0 +1,181 @@
+//! Full-game analysis.
+//! 
+//! This file includes:
+//! - Running the engine over every position of a game
+//! - Each move's evaluation before and after, the engine's preferred move
+//!   and how many centipawns the move gave away
+//! - Classifying moves as inaccuracies, mistakes and blunders
+//! - An accuracy percentage for each player
+//!
+//! A move is judged by the difference between the evaluations of the
+//! positions before and after it, both searched to the same depth. Playing
+//! the engine's own choice never counts as a loss, whatever the two
+//! searches make of it.
+
+use serde::Serialize;
+
+use crate::chess::board::Board;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::Move;
+use crate::engine::search::{Engine, SearchLimits, MATE_SCORE};
+
+// How far a forced mate counts, in centipawns, when measuring a loss.
+// Otherwise missing a mate in 3 for a mate in 5 would look like a
+// catastrophe, and walking into one after being a rook down no worse than
+// being a rook down.
+const SCORE_CAP: i32 = 1_000;
+
+// Centipawns lost by a move at which it becomes each kind of error.
+const INACCURACY: i32 = 50;
+const MISTAKE: i32 = 100;
+const BLUNDER: i32 = 300;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveClass {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    pub fn from_loss(loss: i32) -> Self {
+        match loss {
+            loss if loss >= BLUNDER => MoveClass::Blunder,
+            loss if loss >= MISTAKE => MoveClass::Mistake,
+            loss if loss >= INACCURACY => MoveClass::Inaccuracy,
+            _ => MoveClass::Good,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct MoveAnalysis {
+    // 1 for the first move of the game.
+    pub ply: usize,
+    pub color: PieceColor,
+    pub san: String,
+    pub uci: String,
+    // In centipawns from White's point of view, as scores usually are shown.
+    pub eval_before: i32,
+    pub eval_after: i32,
+    // What the engine would have played, in SAN.
+    pub best_move: Option<String>,
+    // Centipawns the move gave away, from its player's point of view.
+    pub loss: i32,
+    pub class: MoveClass,
+    // 0 to 100, as in PlayerSummary.
+    pub accuracy: f64,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct PlayerSummary {
+    // The average of the player's move accuracies, 0 to 100. 100 if the
+    // player hasn't moved.
+    pub accuracy: f64,
+    pub average_loss: i32,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct GameAnalysis {
+    // In plies.
+    pub depth: u32,
+    pub moves: Vec<MoveAnalysis>,
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+// The chance of winning, 0 to 100, that a centipawn score gives the side it
+// favours, on the logistic curve fitted to real games by Lichess.
+fn win_percent(centipawns: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * centipawns as f64).exp()) - 1.0)
+}
+
+// A move's accuracy from how much of the player's winning chances it threw
+// away (Lichess's formula): 100 for no loss, falling off quickly.
+fn move_accuracy(before: i32, after: i32) -> f64 {
+    let drop = (win_percent(before) - win_percent(after)).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+// The position's score from White's point of view, and the engine's move.
+// A position with no moves is mate or stalemate.
+fn evaluate_position(engine: &mut Engine, board: &Board, depth: u32) -> (i32, Option<Move>) {
+    let (score, best) = match engine.search(board, SearchLimits::depth(depth)) {
+        Some(result) => (result.score, Some(result.best_move)),
+        None if board.king_in_check(board.side_to_move()) => (-MATE_SCORE, None),
+        None => (0, None),
+    };
+    match board.side_to_move() {
+        PieceColor::White => (score, best),
+        PieceColor::Black => (-score, best),
+    }
+}
+
+fn summarize(moves: &[MoveAnalysis], color: PieceColor) -> PlayerSummary {
+    let own: Vec<&MoveAnalysis> = moves.iter().filter(|analysis| analysis.color == color).collect();
+    let count = |class| own.iter().filter(|analysis| analysis.class == class).count();
+    let played = own.len().max(1);
+    PlayerSummary {
+        accuracy: if own.is_empty() {
+            100.0
+        } else {
+            own.iter().map(|analysis| analysis.accuracy).sum::<f64>() / own.len() as f64
+        },
+        average_loss: own.iter().map(|analysis| analysis.loss).sum::<i32>() / played as i32,
+        inaccuracies: count(MoveClass::Inaccuracy),
+        mistakes: count(MoveClass::Mistake),
+        blunders: count(MoveClass::Blunder),
+    }
+}
+
+// Analyzes every move of `game` with searches `depth` plies deep.
+pub fn analyze_game(game: &Game, depth: u32) -> GameAnalysis {
+    let mut engine = Engine::new();
+    let mut board = game.start_board().clone();
+    let (mut eval, mut best) = evaluate_position(&mut engine, &board, depth);
+
+    let mut moves = Vec::new();
+    for (i, entry) in game.history().iter().enumerate() {
+        let color = board.side_to_move();
+        let mv = entry.mv();
+        let best_san = best.map(|best| best.to_san(&board));
+        board.make_move(mv);
+        let (eval_after, next_best) = evaluate_position(&mut engine, &board, depth);
+
+        // Both from the mover's point of view.
+        let sign = if color == PieceColor::White { 1 } else { -1 };
+        let before = (sign * eval).clamp(-SCORE_CAP, SCORE_CAP);
+        let after = (sign * eval_after).clamp(-SCORE_CAP, SCORE_CAP);
+        let (loss, accuracy) = if best == Some(mv) {
+            (0, 100.0)
+        } else {
+            ((before - after).max(0), move_accuracy(before, after))
+        };
+        moves.push(MoveAnalysis {
+            ply: i + 1,
+            color,
+            san: entry.san().to_string(),
+            uci: mv.to_uci(),
+            eval_before: eval,
+            eval_after,
+            best_move: best_san,
+            loss,
+            class: MoveClass::from_loss(loss),
+            accuracy,
+        });
+        (eval, best) = (eval_after, next_best);
+    }
+
+    GameAnalysis {
+        depth,
+        white: summarize(&moves, PieceColor::White),
+        black: summarize(&moves, PieceColor::Black),
+        moves,
+    }
+}
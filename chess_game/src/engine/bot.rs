@@ -0,0 +1,118 @@
+//! Computer opponents.
+//! 
+//! This file defines:
+//! - Difficulty levels for the built-in bot, named or as an Elo rating
+//! - How much time the bot allows itself per move
+//! - Choosing the bot's move for a game
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::chess::clock::Clock;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::Move;
+use crate::engine::search::{Engine, SearchLimits, SearchResult};
+use crate::engine::strength::{Strength, MAX_ELO, MIN_ELO};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BotLevel {
+    // Looks one move ahead.
+    Easy,
+    // Looks three plies ahead.
+    Medium,
+    // Thinks for up to a second per move, as deep as it gets.
+    Hard,
+    // Plays at roughly this rating, from 800 to 2400.
+    Elo(Strength),
+}
+
+impl BotLevel {
+    // The longest the bot will think about a single move when it isn't
+    // playing with a clock.
+    pub fn max_think_time(self) -> Duration {
+        match self {
+            BotLevel::Easy => Duration::from_millis(100),
+            BotLevel::Medium => Duration::from_millis(300),
+            BotLevel::Hard | BotLevel::Elo(_) => Duration::from_secs(1),
+        }
+    }
+
+    // How long the bot may spend on its next move. With a clock it budgets
+    // roughly a thirtieth of its remaining time plus half the increment, so it
+    // never flags however long the game goes on.
+    pub fn time_budget(self, clock: Option<&Clock>, color: PieceColor) -> Duration {
+        let limit = self.max_think_time();
+        match clock {
+            Some(clock) => limit.min(clock.remaining(color) / 30 + clock.increment() / 2),
+            None => limit,
+        }
+    }
+
+    // How far the bot searches for its next move.
+    pub fn search_limits(self, clock: Option<&Clock>, color: PieceColor) -> SearchLimits {
+        match self {
+            BotLevel::Easy => SearchLimits::depth(1),
+            BotLevel::Medium => SearchLimits::depth(3),
+            BotLevel::Hard => SearchLimits::time(self.time_budget(clock, color)),
+            BotLevel::Elo(strength) => SearchLimits {
+                depth: Some(strength.depth()),
+                time: Some(self.time_budget(clock, color)),
+                nodes: None,
+            },
+        }
+    }
+
+    // Picks a move for the side to move with `engine`, the one kept for the
+    // game, or None if the game is over.
+    pub fn choose_move(self, engine: &mut Engine, game: &Game) -> Option<Move> {
+        if let BotLevel::Elo(strength) = self {
+            if game.is_over() {
+                return None;
+            }
+            let time = self.time_budget(game.clock(), game.side_to_move());
+            return strength.choose_move(engine, game.board(), Some(time), &mut rand::thread_rng());
+        }
+        self.search(engine, game).map(|result| result.best_move)
+    }
+
+    // The full search behind the bot's move, with the line it expects to
+    // follow. None if the game is over, and for Elo levels, whose move
+    // isn't simply the search's best.
+    pub fn search(self, engine: &mut Engine, game: &Game) -> Option<SearchResult> {
+        if game.is_over() || matches!(self, BotLevel::Elo(_)) {
+            return None;
+        }
+        let limits = self.search_limits(game.clock(), game.side_to_move());
+        engine.search(game.board(), limits)
+    }
+}
+
+impl FromStr for BotLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "easy" => Ok(BotLevel::Easy),
+            "medium" => Ok(BotLevel::Medium),
+            "hard" => Ok(BotLevel::Hard),
+            _ => match s.parse::<u16>() {
+                Ok(elo) if (MIN_ELO..=MAX_ELO).contains(&elo) => Ok(BotLevel::Elo(Strength::new(elo))),
+                Ok(_) => Err(format!("bot rating must be between {} and {}", MIN_ELO, MAX_ELO)),
+                Err(_) => Err(format!("unknown bot level '{}'", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for BotLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotLevel::Easy => write!(f, "easy"),
+            BotLevel::Medium => write!(f, "medium"),
+            BotLevel::Hard => write!(f, "hard"),
+            BotLevel::Elo(strength) => write!(f, "{}", strength.elo()),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Caching of generated moves.
+//! 
+//! This file defines:
+//! - A least-recently-used cache from position hash to legal moves
+//! - Hit and miss counters for judging whether the cache pays off
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::chess::board::Board;
+use crate::chess::r#move::Move;
+
+// Iterative deepening searches the same positions again on every iteration,
+// and generating legal moves (with a king-safety check for each one) is one
+// of the most expensive things the search does. This cache remembers the
+// move lists of recently seen positions, keyed by Zobrist hash.
+//
+// The key covers everything legal moves depend on, so an entry can never go
+// stale: there is nothing to invalidate. When the cache is full, the entry
+// that was used longest ago is dropped.
+pub struct MoveCache {
+    capacity: usize,
+    // Hash -> (moves, when the entry was last used).
+    entries: HashMap<u64, (Vec<Move>, u64)>,
+    // When each entry was last used -> its hash. The first key is always the
+    // least recently used entry.
+    recency: BTreeMap<u64, u64>,
+    // Goes up on every lookup and serves as the "time" of use.
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl MoveCache {
+    // Creates a cache holding at most `capacity` positions (at least one).
+    pub fn new(capacity: usize) -> Self {
+        MoveCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // The legal moves of `board`, generated only if the position isn't
+    // already cached.
+    pub fn legal_moves(&mut self, board: &Board) -> Vec<Move> {
+        let hash = board.zobrist_hash();
+        self.tick += 1;
+
+        if let Some((moves, last_used)) = self.entries.get_mut(&hash) {
+            self.recency.remove(last_used);
+            *last_used = self.tick;
+            self.recency.insert(self.tick, hash);
+            self.hits += 1;
+            return moves.clone();
+        }
+
+        self.misses += 1;
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        let moves = board.legal_moves();
+        self.entries.insert(hash, (moves.clone(), self.tick));
+        self.recency.insert(self.tick, hash);
+        moves
+    }
+
+    // Number of positions currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    // Lookups that had to generate the moves.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
@@ -0,0 +1,209 @@
+//! Static evaluation of chess positions.
+//! 
+//! This file includes:
+//! - Piece values in centipawns (defined in chess::psqt)
+//! - Middlegame and endgame scores for piece placement, mobility, king
+//!   safety and pawn structure
+//! - The game phase, which decides how much each of the two counts
+//! - The evaluation function used at the leaves of the search
+
+use std::ops::{AddAssign, Mul, Neg};
+
+use crate::chess::bitboard::{BitBoards, Bitboard};
+use crate::chess::board::{pawn_attacks, Board};
+use crate::chess::piece::{Piece, PieceColor, PieceKind};
+use crate::chess::psqt::square_bonus;
+use crate::chess::square::Square;
+
+pub use crate::chess::psqt::piece_value;
+
+// A term's worth in the middlegame and in the endgame. Most things matter
+// differently in the two: a passed pawn is a long-term asset while the
+// queens are on, and a winning one once they're off.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+struct Score {
+    middlegame: i32,
+    endgame: i32,
+}
+
+const fn score(middlegame: i32, endgame: i32) -> Score {
+    Score { middlegame, endgame }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        self.middlegame += other.middlegame;
+        self.endgame += other.endgame;
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, count: i32) -> Score {
+        score(self.middlegame * count, self.endgame * count)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        score(-self.middlegame, -self.endgame)
+    }
+}
+
+// The phase with all the pieces on the board. Each knight and bishop counts
+// 1, each rook 2 and each queen 4; pawns and kings don't count.
+pub const MAX_PHASE: i32 = 24;
+
+// Per square a piece can safely move to (not onto its own pieces or where an
+// enemy pawn could take it). Rooks and queens have the most squares anyway,
+// so each one is worth less.
+fn mobility_weight(kind: PieceKind) -> Score {
+    match kind {
+        PieceKind::Knight => score(4, 4),
+        PieceKind::Bishop => score(5, 5),
+        PieceKind::Rook => score(2, 4),
+        PieceKind::Queen => score(1, 2),
+        PieceKind::Pawn | PieceKind::King => score(0, 0),
+    }
+}
+
+// King safety only matters while there are pieces left to attack the king.
+// Each attack on a square next to the enemy king counts, as does each pawn
+// still standing in front of our own.
+const KING_ZONE_ATTACK: Score = score(8, 0);
+const PAWN_SHIELD: Score = score(12, 0);
+
+const DOUBLED_PAWN: Score = score(-10, -20);
+const ISOLATED_PAWN: Score = score(-15, -10);
+const BACKWARD_PAWN: Score = score(-8, -10);
+
+// A passed pawn's bonus by how far it has come, from its own side's point of
+// view: index 1 is its starting rank, 6 one step from promoting.
+const PASSED_PAWN: [Score; 8] = [
+    score(0, 0),
+    score(5, 10),
+    score(10, 20),
+    score(15, 35),
+    score(25, 60),
+    score(40, 100),
+    score(60, 150),
+    score(0, 0),
+];
+
+// In the endgame the king should come to the centre rather than hide; this
+// replaces the middlegame king table in chess::psqt, laid out the same way.
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// How far `square` is up the board as `color` sees it, 0 to 7.
+fn relative_rank(color: PieceColor, square: Square) -> usize {
+    match color {
+        PieceColor::White => square.rank() as usize,
+        PieceColor::Black => 7 - square.rank() as usize,
+    }
+}
+
+// How much non-pawn material is left, from MAX_PHASE at the start of the
+// game down to 0 with only kings and pawns. Promotions can take the count
+// past the maximum, so it is capped there.
+pub fn game_phase(board: &Board) -> i32 {
+    phase(&BitBoards::from(board))
+}
+
+fn phase(position: &BitBoards) -> i32 {
+    let count = |kind| {
+        [PieceColor::White, PieceColor::Black]
+            .into_iter()
+            .map(|color| position.pieces(color, kind).count() as i32)
+            .sum::<i32>()
+    };
+    let total = count(PieceKind::Knight) + count(PieceKind::Bishop) + 2 * count(PieceKind::Rook) + 4 * count(PieceKind::Queen);
+    total.min(MAX_PHASE)
+}
+
+// Scores the position in centipawns from the point of view of the side to
+// move: positive means the side to move is better. This "negamax" convention
+// lets the search treat both sides identically.
+//
+// Each side gets a middlegame and an endgame score for material, piece
+// placement, mobility, king safety and pawn structure. The two are blended by
+// the game phase, so the evaluation slides from one to the other as pieces
+// come off rather than jumping at some arbitrary point.
+pub fn evaluate(board: &Board) -> i32 {
+    let position = BitBoards::from(board);
+    let mut total = side_score(board, &position, PieceColor::White);
+    total += -side_score(board, &position, PieceColor::Black);
+
+    let phase = phase(&position);
+    let blended = (total.middlegame * phase + total.endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+    match board.side_to_move() {
+        PieceColor::White => blended,
+        PieceColor::Black => -blended,
+    }
+}
+
+// Everything `color` has going for it, not yet compared with the other side.
+fn side_score(board: &Board, position: &BitBoards, color: PieceColor) -> Score {
+    let enemy = color.opposite();
+
+    // The board keeps running totals of material and the piece-square
+    // bonuses. Only the king's table differs in the endgame.
+    let placed = board.material(color) + board.positional_score(color);
+    let mut total = score(placed, placed);
+    if let Some(king) = position.pieces(color, PieceKind::King).lsb() {
+        let row = 7 - relative_rank(color, king);
+        let endgame_king = KING_ENDGAME_TABLE[row * 8 + king.file() as usize];
+        total.endgame += endgame_king - square_bonus(Piece::new(PieceKind::King, color), king);
+    }
+
+    // Mobility, and on the way how hard the pieces hit the enemy king.
+    let enemy_pawn_cover = position
+        .pieces(enemy, PieceKind::Pawn)
+        .iter_squares()
+        .fold(Bitboard::EMPTY, |covered, pawn| covered | pawn_attacks(enemy, pawn));
+    let safe = !position.occupancy(color) & !enemy_pawn_cover;
+    let enemy_king_zone = position
+        .pieces(enemy, PieceKind::King)
+        .lsb()
+        .map_or(Bitboard::EMPTY, |king| position.attacks_from(king) | king);
+    for kind in [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen] {
+        for square in position.pieces(color, kind).iter_squares() {
+            let attacks = position.attacks_from(square);
+            total += mobility_weight(kind) * (attacks & safe).count() as i32;
+            total += KING_ZONE_ATTACK * (attacks & enemy_king_zone).count() as i32;
+        }
+    }
+
+    // Pawns on the three files around the king, one or two squares in front.
+    if let Some(king) = position.pieces(color, PieceKind::King).lsb() {
+        let forward = if color == PieceColor::White { 1 } else { -1 };
+        let pawns = position.pieces(color, PieceKind::Pawn);
+        let shield = (-1..=1)
+            .flat_map(|file| [1, 2].map(|ranks| king.offset(file, ranks * forward)))
+            .flatten()
+            .filter(|&square| pawns.contains(square))
+            .count();
+        total += PAWN_SHIELD * shield as i32;
+    }
+
+    let pawns = board.pawn_structure(color);
+    total += DOUBLED_PAWN * pawns.doubled.count() as i32;
+    total += ISOLATED_PAWN * pawns.isolated.count() as i32;
+    total += BACKWARD_PAWN * pawns.backward.count() as i32;
+    for pawn in pawns.passed.iter_squares() {
+        total += PASSED_PAWN[relative_rank(color, pawn)];
+    }
+    total
+}
@@ -0,0 +1,103 @@
+//! Hints for human players.
+//! 
+//! This file includes:
+//! - A short search suggesting a move for the side to move
+//! - A one-line explanation of why the move is good, such as "wins
+//!   material", worked out from the move and the line the search expects
+
+use std::time::Duration;
+
+use crate::chess::board::Board;
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::{Move, MoveFlag};
+use crate::engine::ordering::is_capture;
+use crate::engine::search::{Engine, SearchLimits, SearchResult, MATE_SCORE};
+
+// A hint is meant to come back quickly, so the search stops at whichever of
+// these comes first.
+pub const HINT_DEPTH: u32 = 5;
+pub const HINT_TIME: Duration = Duration::from_millis(500);
+
+// Scores at least this close to MATE_SCORE are forced mates.
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hint {
+    pub mv: Move,
+    pub san: String,
+    pub explanation: String,
+    // Centipawns from the side to move's point of view.
+    pub score: i32,
+}
+
+// Suggests a move for the side to move, searching with `engine`, or None if
+// there are no legal moves.
+pub fn hint(engine: &mut Engine, board: &Board) -> Option<Hint> {
+    let limits = SearchLimits { depth: Some(HINT_DEPTH), time: Some(HINT_TIME), nodes: None };
+    let result = engine.search(board, limits)?;
+    Some(Hint {
+        mv: result.best_move,
+        san: result.best_move.to_san(board),
+        explanation: explain(board, &result),
+        score: result.score,
+    })
+}
+
+// The material `color` is ahead by.
+fn balance(board: &Board, color: PieceColor) -> i32 {
+    board.material(color) - board.material(color.opposite())
+}
+
+// Says in a few words what the search's best move does. The first thing
+// that applies wins: mate, then material, then what the move itself does.
+pub fn explain(board: &Board, result: &SearchResult) -> String {
+    let us = board.side_to_move();
+    let them = us.opposite();
+    let mv = result.best_move;
+    let mut after = board.clone();
+    after.make_move(mv);
+
+    if after.king_in_check(them) && after.legal_moves().is_empty() {
+        return "delivers checkmate".to_string();
+    }
+    if result.score >= MATE_THRESHOLD {
+        let moves = (MATE_SCORE - result.score + 1) / 2;
+        return format!("forces mate in {}", moves);
+    }
+    if result.score <= -MATE_THRESHOLD {
+        return "holds out longest against a forced mate".to_string();
+    }
+
+    // How the material stands at the end of the expected line.
+    let mut line_end = board.clone();
+    for &step in &result.pv {
+        line_end.make_move(step);
+    }
+    let gain = balance(&line_end, us) - balance(board, us);
+    if gain >= 100 {
+        return "wins material".to_string();
+    }
+    if gain <= -100 {
+        return "gives up the least material".to_string();
+    }
+
+    if board.king_in_check(us) {
+        return "gets the king out of check".to_string();
+    }
+    if mv.promotion().is_some() {
+        return "promotes a pawn".to_string();
+    }
+    if matches!(mv.flag(), MoveFlag::CastleKingside | MoveFlag::CastleQueenside) {
+        return "castles, tucking the king away".to_string();
+    }
+    if board.is_square_attacked(mv.from(), them) && !after.is_square_attacked(mv.to(), them) {
+        return "moves an attacked piece to safety".to_string();
+    }
+    if after.king_in_check(them) {
+        return "gives check".to_string();
+    }
+    if is_capture(board, &mv) {
+        return "trades pieces".to_string();
+    }
+    "improves the position".to_string()
+}
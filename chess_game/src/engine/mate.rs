@@ -0,0 +1,77 @@
+//! Forced mate search.
+//! 
+//! This file includes:
+//! - An exact "mate in N" solver for puzzles
+//! - Extraction of the full mating line
+//! - A check that a position is lost to a forced mate
+
+use crate::chess::board::Board;
+use crate::chess::game::Game;
+use crate::chess::r#move::Move;
+
+// Looks for a forced checkmate by the side to move in at most `max_moves`
+// of its own moves, and returns the quickest one as a list of moves: ours,
+// the defender's reply, ours, ... ending with the mating move. A mate in N
+// is therefore 2N - 1 plies long.
+//
+// Unlike the alpha-beta search, this is exhaustive: a line is only returned
+// if every defence loses, and the defender's replies in it are the ones that
+// hold out longest. The cost grows very quickly with depth, so keep
+// `max_moves` small (3 or so).
+pub fn find_mate(game: &Game, max_moves: u32) -> Option<Vec<Move>> {
+    if game.is_over() {
+        return None;
+    }
+    let mut board = game.board().clone();
+    (1..=max_moves).find_map(|moves| mate_in(&mut board, moves))
+}
+
+// True if the side to move is already checkmated, or will be within
+// `max_moves` of the opponent's moves whatever it plays. Used to judge a
+// puzzle answer: a move is as good as the solution if the defender is still
+// lost in the same number of moves after it.
+pub fn mated_within(game: &Game, max_moves: u32) -> bool {
+    let mut board = game.board().clone();
+    defend(&mut board, max_moves).is_some()
+}
+
+// A mating line of at most `moves` moves for the side to move, if one exists.
+fn mate_in(board: &mut Board, moves: u32) -> Option<Vec<Move>> {
+    if moves == 0 {
+        return None;
+    }
+    for mv in board.legal_moves() {
+        let undo = board.make_move(mv);
+        let line = defend(board, moves - 1);
+        board.unmake_move(mv, undo);
+        if let Some(mut line) = line {
+            line.insert(0, mv);
+            return Some(line);
+        }
+    }
+    None
+}
+
+// With the defender to move, returns the rest of the line if every reply
+// still loses within `moves` more attacking moves. The reply kept is the one
+// whose mate takes longest.
+fn defend(board: &mut Board, moves: u32) -> Option<Vec<Move>> {
+    let replies = board.legal_moves();
+    if replies.is_empty() {
+        // Checkmate if in check; stalemate is not a win.
+        return board.king_in_check(board.side_to_move()).then(Vec::new);
+    }
+
+    let mut longest: Option<Vec<Move>> = None;
+    for reply in replies {
+        let undo = board.make_move(reply);
+        let line = mate_in(board, moves);
+        board.unmake_move(reply, undo);
+        let mut line = line?;
+        line.insert(0, reply);
+        if longest.as_ref().is_none_or(|longest| line.len() > longest.len()) {
+            longest = Some(line);
+        }
+    }
+    longest
+}
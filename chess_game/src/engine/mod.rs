@@ -0,0 +1,42 @@
+//! Engine module powering the computer opponent.
+//! 
+//! This module includes:
+//! - Static evaluation of positions
+//! - Analysis of whole games: evaluations, mistakes and accuracy
+//! - A cache of generated moves for the search
+//! - Alpha-beta search for the best move
+//! - Move ordering: MVV-LVA, killer moves and the history heuristic
+//! - A transposition table shared by the searches of one engine
+//! - An exact forced-mate solver for puzzles
+//! - Suggested moves with a short explanation, for hints
+//! - Polyglot opening books for the bot's first moves
+//! - Strength-limited play at a chosen Elo rating
+//! - Bot difficulty levels built on the search
+//! - Pondering on the opponent's time in games against the bot
+//! - The UCI protocol, for running the engine in chess GUIs
+
+pub mod analysis;
+pub mod book;
+pub mod bot;
+pub mod cache;
+pub mod eval;
+pub mod hint;
+pub mod mate;
+pub mod ordering;
+pub mod ponder;
+pub mod search;
+pub mod strength;
+pub mod tt;
+pub mod uci;
+
+pub use analysis::{analyze_game, GameAnalysis, MoveAnalysis, MoveClass, PlayerSummary};
+pub use book::{polyglot_hash, Book, BookError, BookMove, DEFAULT_BOOK_DEPTH};
+pub use bot::BotLevel;
+pub use cache::MoveCache;
+pub use hint::{hint, Hint};
+pub use mate::{find_mate, mated_within};
+pub use ordering::MoveOrdering;
+pub use ponder::{Ponder, MAX_PONDER_TIME};
+pub use search::{search, search_timed, Engine, EngineOptions, SearchLimits, SearchResult};
+pub use strength::{Strength, MAX_ELO, MIN_ELO};
+pub use tt::{Bound, TranspositionTable, TtEntry};
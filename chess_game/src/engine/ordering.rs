@@ -0,0 +1,134 @@
+//! Move ordering for the search.
+//! 
+//! This file defines:
+//! - MVV-LVA scores for captures and promotions
+//! - Killer moves: quiet moves that caused a cutoff at the same ply
+//! - The history heuristic: quiet moves that have caused cutoffs anywhere
+//! - MoveOrdering, which holds both and sorts a node's moves with them
+//!
+//! Alpha-beta prunes the most when the best move is searched first. The
+//! order is: the transposition table's move, then captures and promotions,
+//! then the killers, then the other quiet moves by their history.
+
+use crate::chess::board::Board;
+use crate::chess::r#move::{Move, MoveFlag};
+use crate::engine::eval::piece_value;
+
+// Where each kind of move starts out in the order. Captures' MVV-LVA scores
+// and the history scores stay below the gap to the tier above.
+const HASH_MOVE: i32 = 4_000_000;
+const TACTICAL: i32 = 3_000_000;
+const KILLERS: [i32; 2] = [2_000_010, 2_000_000];
+// Once a history score reaches this, they are all halved, so recent
+// cutoffs count for more than old ones.
+const HISTORY_MAX: i32 = 1_000_000;
+
+pub fn is_capture(board: &Board, mv: &Move) -> bool {
+    mv.flag() == MoveFlag::EnPassant || board.piece_at(mv.to()).is_some()
+}
+
+// Captures and promotions, the moves quiescence plays out.
+pub fn is_tactical(board: &Board, mv: &Move) -> bool {
+    is_capture(board, mv) || mv.promotion().is_some()
+}
+
+// "Most valuable victim, least valuable attacker": taking a queen with a
+// pawn first, a pawn with a queen last. A promotion adds the new piece.
+pub fn mvv_lva(board: &Board, mv: &Move) -> i32 {
+    let mut score = 0;
+    if let Some(victim) = board.piece_at(mv.to()) {
+        let attacker = board.piece_at(mv.from()).map_or(0, |p| piece_value(p.kind()));
+        score += 10 * piece_value(victim.kind()) - attacker;
+    } else if mv.flag() == MoveFlag::EnPassant {
+        score += 10 * 100 - 100;
+    }
+    if let Some(kind) = mv.promotion() {
+        score += piece_value(kind);
+    }
+    score
+}
+
+// Sorts captures and promotions for quiescence, best first.
+pub fn order_captures(board: &Board, moves: &mut [Move]) {
+    moves.sort_by_cached_key(|mv| -mvv_lva(board, mv));
+}
+
+// What one search has learned about which quiet moves tend to be good.
+// Start a new one for every search.
+pub struct MoveOrdering {
+    // Two per ply, the most recent first.
+    killers: Vec<[Option<Move>; 2]>,
+    // By side to move, from-square and to-square.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering::new()
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering { killers: Vec::new(), history: Box::new([[[0; 64]; 64]; 2]) }
+    }
+
+    pub fn killers(&self, ply: u32) -> [Option<Move>; 2] {
+        self.killers.get(ply as usize).copied().unwrap_or_default()
+    }
+
+    pub fn history(&self, board: &Board, mv: &Move) -> i32 {
+        self.history[board.side_to_move().index()][mv.from().index()][mv.to().index()]
+    }
+
+    fn score(&self, board: &Board, mv: &Move, hash_move: Option<Move>, ply: u32) -> i32 {
+        if hash_move == Some(*mv) {
+            return HASH_MOVE;
+        }
+        if is_tactical(board, mv) {
+            return TACTICAL + mvv_lva(board, mv);
+        }
+        let killers = self.killers(ply);
+        match killers.iter().position(|killer| *killer == Some(*mv)) {
+            Some(slot) => KILLERS[slot],
+            None => self.history(board, mv),
+        }
+    }
+
+    // Sorts a node's moves, the likeliest to be best first.
+    pub fn order(&self, board: &Board, moves: &mut [Move], hash_move: Option<Move>, ply: u32) {
+        moves.sort_by_cached_key(|mv| -self.score(board, mv, hash_move, ply));
+    }
+
+    // `mv`, a quiet move, caused a beta cutoff at `depth` plies from the
+    // leaves. It becomes a killer for this ply, and its history goes up by
+    // more the deeper the cutoff; the quiet moves tried before it, which
+    // didn't cut off, go down by the same amount.
+    pub fn record_cutoff(&mut self, board: &Board, mv: Move, tried: &[Move], depth: u32, ply: u32) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        let bonus = (depth * depth) as i32;
+        let side = &mut self.history[board.side_to_move().index()];
+        for other in tried {
+            let entry = &mut side[other.from().index()][other.to().index()];
+            *entry = (*entry - bonus).max(-HISTORY_MAX);
+        }
+        let entry = &mut side[mv.from().index()][mv.to().index()];
+        *entry += bonus;
+        if *entry >= HISTORY_MAX {
+            for row in self.history.iter_mut().flatten() {
+                for score in row.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Pondering: thinking on the opponent's time.
+//! 
+//! This file defines:
+//! - Ponder, a search running in the background on the position after the
+//!   reply the engine expects
+//! - What happens when the opponent moves: on a "ponder hit" the search
+//!   carries on and its result is used, on a miss it is thrown away
+//!
+//! After the engine moves, its principal variation already says what it
+//! expects the opponent to play. Searching the position after that reply
+//! while the opponent thinks means that, whenever the guess is right, the
+//! engine has a head start it didn't pay for with its own clock.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::chess::board::Board;
+use crate::chess::r#move::Move;
+use crate::engine::search::{Engine, SearchLimits, SearchResult};
+
+// However long the opponent takes, a ponder search stops after this, so a
+// player who walks away doesn't leave a core busy. Its result is kept.
+pub const MAX_PONDER_TIME: Duration = Duration::from_secs(60);
+
+// How often `finish` checks whether a search has ended on its own.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub struct Ponder {
+    // The opponent's move the search is betting on.
+    expected: Move,
+    stop: Arc<AtomicBool>,
+    started: Instant,
+    // None once joined.
+    search: Option<JoinHandle<Option<SearchResult>>>,
+}
+
+impl Ponder {
+    // Starts searching the position after `expected` is played on `board`,
+    // down to `depth` plies if given. None if `expected` isn't legal there
+    // or leaves the engine nothing to search.
+    pub fn start(board: &Board, expected: Move, depth: Option<u32>) -> Option<Ponder> {
+        let mut after = board.clone();
+        if !after.legal_moves().contains(&expected) {
+            return None;
+        }
+        after.make_move(expected);
+        if after.legal_moves().is_empty() {
+            return None;
+        }
+
+        let engine = Engine::new();
+        let stop = engine.stop_signal();
+        let limits = SearchLimits { depth, time: Some(MAX_PONDER_TIME), nodes: None };
+        let search = std::thread::spawn(move || {
+            let mut engine = engine;
+            engine.search(&after, limits)
+        });
+        Some(Ponder { expected, stop, started: Instant::now(), search: Some(search) })
+    }
+
+    pub fn expected(&self) -> Move {
+        self.expected
+    }
+
+    // The opponent has played `played`. On a miss the search is stopped and
+    // None is returned. On a hit the search gets `budget` in all, counting
+    // the time it has already had, or runs to its depth without a budget;
+    // then its result is returned. Blocks until then.
+    pub fn finish(mut self, played: Move, budget: Option<Duration>) -> Option<SearchResult> {
+        if played != self.expected {
+            return None;
+        }
+        let search = self.search.take()?;
+        if let Some(budget) = budget {
+            let deadline = self.started + budget;
+            while !search.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        search.join().ok()?
+    }
+}
+
+// A ponder that is dropped, because the opponent missed or the game went
+// away, stops searching. The thread winds down on its own.
+impl Drop for Ponder {
+    fn drop(&mut self) {
+        if self.search.is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Game-tree search for the computer opponent.
+//! 
+//! This file includes:
+//! - Negamax alpha-beta search with iterative deepening
+//! - A quiescence search so captures are played out before evaluating
+//! - A principal variation collected as the search goes
+//! - Null-move pruning and late-move reductions, which can be turned off
+//! - Engine options, such as the transposition table's size and whether
+//!   generated moves are cached
+//! - The Engine API, with depth, time and node limits, a way to stop a
+//!   search from another thread, and shortcuts for searching to a depth or
+//!   for a time
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::chess::board::Board;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::Move;
+use crate::engine::cache::MoveCache;
+use crate::engine::eval::evaluate;
+use crate::engine::ordering::{is_tactical, order_captures, MoveOrdering};
+use crate::engine::tt::{Bound, TranspositionTable};
+
+// Score for delivering checkmate right now. Mates further away score a little
+// less (MATE_SCORE - plies), so the engine prefers the quickest mate.
+pub const MATE_SCORE: i32 = 30_000;
+
+// Larger than any real score, used as the initial alpha-beta window.
+const INFINITY: i32 = 32_000;
+
+// A hard cap on iterative deepening; time runs out long before this.
+const MAX_DEPTH: u32 = 64;
+
+// Null-move pruning gives the opponent a free move and searches the reply
+// this many plies shallower than usual (one more in deeper searches). Below
+// NULL_MOVE_MIN_DEPTH there is too little left to reduce; from
+// NULL_MOVE_VERIFY_DEPTH a null-move cutoff is checked with a real search.
+const NULL_MOVE_REDUCTION: u32 = 2;
+const NULL_MOVE_MIN_DEPTH: u32 = 3;
+const NULL_MOVE_VERIFY_DEPTH: u32 = 6;
+
+// Late-move reductions search quiet moves this far down the ordering one ply
+// shallower, from this depth on; two plies for moves much further down.
+const LMR_MIN_MOVES: usize = 3;
+const LMR_MIN_DEPTH: u32 = 3;
+const LMR_DEEP_MOVES: usize = 8;
+
+// How many positions' moves the search remembers. Move lists are small, so
+// this is a few megabytes at most.
+const MOVE_CACHE_CAPACITY: usize = 16_384;
+
+// When to stop searching. Whichever limit is reached first ends the search;
+// with none set it runs to MAX_DEPTH, which in practice means until it finds
+// a forced mate.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct SearchLimits {
+    // In plies.
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+    // Positions visited, quiescence included.
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    // Search exactly `depth` plies.
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    // Search for as long as `time` allows.
+    pub fn time(time: Duration) -> Self {
+        SearchLimits { time: Some(time), ..SearchLimits::default() }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    // Centipawns from the side to move's point of view.
+    pub score: i32,
+    // The deepest iteration that finished.
+    pub depth: u32,
+    // The principal variation: the line both sides are expected to play,
+    // starting with best_move. Quiescence captures aren't included, so it is
+    // at most `depth` moves long.
+    pub pv: Vec<Move>,
+    // Positions visited over the whole search.
+    pub nodes: u64,
+}
+
+// Settings that stay the same from one search to the next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EngineOptions {
+    // How much memory the transposition table may use, in megabytes.
+    pub hash_size_mb: usize,
+    // Skip positions where even passing (a "null move") keeps the side to
+    // move above beta. Much faster, but it can miss zugzwang, where having
+    // to move is the problem; it is never tried without pieces, where
+    // zugzwang is most common.
+    pub null_move_pruning: bool,
+    // Search moves late in the ordering less deeply, and only search again
+    // at full depth those that turn out better than expected.
+    pub late_move_reductions: bool,
+    // Remember the legal moves of recently searched positions instead of
+    // generating them again on every visit. Costs a few megabytes.
+    pub move_cache: bool,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            hash_size_mb: TranspositionTable::DEFAULT_SIZE_MB,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            move_cache: true,
+        }
+    }
+}
+
+// The computer player. It keeps what it learns about positions from one
+// search to the next, so one Engine per game plays faster than a new one
+// for every move.
+pub struct Engine {
+    options: EngineOptions,
+    moves: MoveCache,
+    tt: TranspositionTable,
+    // Set from outside to end the current search early.
+    stop: Arc<AtomicBool>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::with_options(EngineOptions::default())
+    }
+
+    pub fn with_options(options: EngineOptions) -> Self {
+        Engine {
+            options,
+            moves: MoveCache::new(MOVE_CACHE_CAPACITY),
+            tt: TranspositionTable::new(options.hash_size_mb),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn options(&self) -> EngineOptions {
+        self.options
+    }
+
+    // Changes the options between searches. Resizing the transposition
+    // table empties it.
+    pub fn set_options(&mut self, options: EngineOptions) {
+        if options.hash_size_mb != self.options.hash_size_mb {
+            self.tt = TranspositionTable::new(options.hash_size_mb);
+        }
+        self.options = options;
+    }
+
+    pub fn transposition_table(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
+    // Stays empty while the move_cache option is off.
+    pub fn move_cache(&self) -> &MoveCache {
+        &self.moves
+    }
+
+    // Forgets everything learned in earlier searches, e.g. for a new game.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.moves = MoveCache::new(MOVE_CACHE_CAPACITY);
+    }
+
+    // A flag that stops the running search when set, from any thread. The
+    // search still returns its best move so far (the first iteration always
+    // finishes), and clears the flag as it returns.
+    pub fn stop_signal(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    // Searches `position` within `limits`, deepening one ply at a time and
+    // keeping the result of the last iteration that finished. Depth 1 always
+    // completes, so there is an answer even with a tiny time or node limit.
+    // Returns None if there are no legal moves.
+    pub fn search(&mut self, position: &Board, limits: SearchLimits) -> Option<SearchResult> {
+        self.search_with(position, limits, |_| {})
+    }
+
+    // Like `search`, but calls `on_iteration` with the result of every
+    // iteration as it finishes, e.g. to report progress.
+    pub fn search_with(
+        &mut self,
+        position: &Board,
+        limits: SearchLimits,
+        on_iteration: impl FnMut(&SearchResult),
+    ) -> Option<SearchResult> {
+        self.tt.new_search();
+        let mut searcher = Searcher {
+            options: self.options,
+            deadline: limits.time.map(|time| Instant::now() + time),
+            node_limit: limits.nodes,
+            nodes: 0,
+            stopped: false,
+            stop: &self.stop,
+            interruptible: false,
+            moves: &mut self.moves,
+            tt: &mut self.tt,
+            ordering: MoveOrdering::new(),
+            skip_null: false,
+            pv: Vec::new(),
+        };
+        let result = searcher.iterate(position, limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH), on_iteration);
+        self.stop.store(false, Ordering::Relaxed);
+        result
+    }
+}
+
+// Searches to a fixed depth (in plies). Returns None if there are no legal
+// moves.
+pub fn search(board: &Board, depth: u32) -> Option<SearchResult> {
+    Engine::new().search(board, SearchLimits::depth(depth))
+}
+
+// Searches for as long as `limit` allows.
+pub fn search_timed(board: &Board, limit: Duration) -> Option<SearchResult> {
+    Engine::new().search(board, SearchLimits::time(limit))
+}
+
+// True if the side to move's king is attacked.
+fn in_check(board: &Board) -> bool {
+    board.king_in_check(board.side_to_move())
+}
+
+// True if the side to move has something besides pawns and its king. With
+// only pawns left, zugzwang is common and passing isn't a safe guess.
+fn has_pieces(board: &Board) -> bool {
+    let us = board.side_to_move();
+    board
+        .piece_list()
+        .iter()
+        .any(|(_, piece)| piece.color() == us && !matches!(piece.kind(), PieceKind::Pawn | PieceKind::King))
+}
+
+struct Searcher<'a> {
+    options: EngineOptions,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    nodes: u64,
+    stopped: bool,
+    stop: &'a AtomicBool,
+    // False during the first iteration, which must finish so that there is
+    // always a move to return.
+    interruptible: bool,
+    moves: &'a mut MoveCache,
+    tt: &'a mut TranspositionTable,
+    ordering: MoveOrdering,
+    // Set just before searching a node that mustn't try a null move: the
+    // reply to one (two passes in a row prove nothing) or a verification
+    // search. Only the next node sees it.
+    skip_null: bool,
+    // The best line found from each ply of the current path, indexed by
+    // ply. A node's line is its best move followed by its child's line.
+    pv: Vec<Vec<Move>>,
+}
+
+impl Searcher<'_> {
+    fn iterate(
+        &mut self,
+        board: &Board,
+        max_depth: u32,
+        mut on_iteration: impl FnMut(&SearchResult),
+    ) -> Option<SearchResult> {
+        let mut board = board.clone();
+        let mut moves = board.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        self.ordering.order(&board, &mut moves, None, 0);
+
+        let mut best = SearchResult { best_move: moves[0], score: -INFINITY, depth: 0, pv: Vec::new(), nodes: 0 };
+        for depth in 1..=max_depth {
+            // Search last iteration's best move first.
+            if let Some(i) = moves.iter().position(|&mv| mv == best.best_move) {
+                moves[..=i].rotate_right(1);
+            }
+            self.interruptible = depth > 1;
+            let Some(score) = self.search_root(&mut board, &moves, depth) else {
+                break;
+            };
+            let pv = self.pv[0].clone();
+            self.tt.store(board.zobrist_hash(), 0, depth, score, Bound::Exact, Some(pv[0]));
+            best = SearchResult { best_move: pv[0], score, depth, pv, nodes: self.nodes };
+            on_iteration(&best);
+            // Once a forced mate is found, searching deeper can't improve it.
+            if score.abs() >= MATE_SCORE - MAX_DEPTH as i32 {
+                break;
+            }
+        }
+        best.nodes = self.nodes;
+        Some(best)
+    }
+
+    // Leaves the best line in pv[0]. Returns None if the search was stopped
+    // before every root move was searched.
+    fn search_root(&mut self, board: &mut Board, moves: &[Move], depth: u32) -> Option<i32> {
+        let mut alpha = -INFINITY;
+        self.clear_pv(0);
+        for &mv in moves {
+            let undo = board.make_move(mv);
+            let score = -self.negamax(board, depth - 1, -INFINITY, -alpha, 1);
+            board.unmake_move(mv, undo);
+            if self.stopped {
+                return None;
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(0, mv);
+            }
+        }
+        Some(alpha)
+    }
+
+    // Starts the line at `ply` afresh, as a node is entered.
+    fn clear_pv(&mut self, ply: u32) {
+        let ply = ply as usize;
+        if self.pv.len() <= ply + 1 {
+            self.pv.resize(ply + 2, Vec::new());
+        }
+        self.pv[ply].clear();
+        self.pv[ply + 1].clear();
+    }
+
+    // `mv` is the best move found so far at `ply`; its line continues with
+    // the one just found below it.
+    fn update_pv(&mut self, ply: u32, mv: Move) {
+        let ply = ply as usize;
+        let (line, rest) = self.pv[ply..].split_first_mut().expect("cleared on entry");
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&rest[0]);
+    }
+
+    // From the move cache if the engine has one turned on.
+    fn legal_moves(&mut self, board: &Board) -> Vec<Move> {
+        if self.options.move_cache {
+            self.moves.legal_moves(board)
+        } else {
+            board.legal_moves()
+        }
+    }
+
+    // Counts the node and says whether the search has to stop, for time, for
+    // the node limit or because it was told to.
+    fn out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.interruptible {
+            return self.stopped;
+        }
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.stopped = true;
+        }
+        // Reading the clock is relatively slow, so only do it every so often.
+        if self.nodes.is_multiple_of(1024) {
+            if self.stop.load(Ordering::Relaxed) {
+                self.stopped = true;
+            }
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    self.stopped = true;
+                }
+            }
+        }
+        self.stopped
+    }
+
+    fn negamax(&mut self, board: &mut Board, depth: u32, mut alpha: i32, beta: i32, ply: u32) -> i32 {
+        let skip_null = std::mem::take(&mut self.skip_null);
+        self.clear_pv(ply);
+        if self.out_of_time() {
+            return 0;
+        }
+        if board.halfmove_clock() >= 100 {
+            return 0;
+        }
+
+        let mut moves = self.legal_moves(board);
+        if moves.is_empty() {
+            return if in_check(board) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        if depth == 0 {
+            return self.quiescence(board, alpha, beta, ply);
+        }
+
+        // A deep enough result from earlier settles it; otherwise the move
+        // that was best then is the likeliest to be best now.
+        let key = board.zobrist_hash();
+        let entry = self.tt.probe(key, ply);
+        if let Some(score) = entry.and_then(|entry| entry.cutoff(depth, alpha, beta)) {
+            return score.clamp(alpha, beta);
+        }
+
+        let checked = in_check(board);
+        if self.options.null_move_pruning
+            && !skip_null
+            && !checked
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && has_pieces(board)
+            && evaluate(board) >= beta
+            && self.null_move_cutoff(board, depth, beta, ply)
+        {
+            return beta;
+        }
+
+        let hash_move = entry.and_then(|entry| entry.best_move);
+        self.ordering.order(board, &mut moves, hash_move, ply);
+        let killers = self.ordering.killers(ply);
+
+        let mut best_move = None;
+        let mut quiets_tried = Vec::new();
+        for (index, mv) in moves.into_iter().enumerate() {
+            let quiet = !is_tactical(board, &mv);
+            let undo = board.make_move(mv);
+            let reduction = if self.options.late_move_reductions
+                && quiet
+                && !checked
+                && depth >= LMR_MIN_DEPTH
+                && index >= LMR_MIN_MOVES
+                && !killers.contains(&Some(mv))
+                && !in_check(board)
+            {
+                if index >= LMR_DEEP_MOVES && depth > LMR_MIN_DEPTH { 2 } else { 1 }
+            } else {
+                0
+            };
+            let mut score = -self.negamax(board, depth - 1 - reduction, -beta, -alpha, ply + 1);
+            // The reduced search thought it worth a look after all.
+            if reduction > 0 && score > alpha && !self.stopped {
+                score = -self.negamax(board, depth - 1, -beta, -alpha, ply + 1);
+            }
+            board.unmake_move(mv, undo);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                if quiet {
+                    self.ordering.record_cutoff(board, mv, &quiets_tried, depth, ply);
+                }
+                self.tt.store(key, ply, depth, beta, Bound::Lower, Some(mv));
+                return beta;
+            }
+            if quiet {
+                quiets_tried.push(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+                self.update_pv(ply, mv);
+            }
+        }
+        let bound = if best_move.is_some() { Bound::Exact } else { Bound::Upper };
+        self.tt.store(key, ply, depth, alpha, bound, best_move);
+        alpha
+    }
+
+    // Lets the opponent move twice and searches their reply with a reduced
+    // depth and the narrowest window at beta. If we still come out at beta
+    // or better, a real move would almost certainly do at least as well, so
+    // the node can be cut off. In deep searches that is confirmed with a
+    // reduced search of the node itself, in case of zugzwang.
+    fn null_move_cutoff(&mut self, board: &mut Board, depth: u32, beta: i32, ply: u32) -> bool {
+        let reduction = NULL_MOVE_REDUCTION + depth / 6;
+        let remaining = depth.saturating_sub(1 + reduction);
+
+        let en_passant = board.en_passant();
+        board.set_en_passant(None);
+        board.set_side_to_move(board.side_to_move().opposite());
+        self.skip_null = true;
+        let score = -self.negamax(board, remaining, -beta, -beta + 1, ply + 1);
+        board.set_side_to_move(board.side_to_move().opposite());
+        board.set_en_passant(en_passant);
+
+        if score < beta || self.stopped {
+            return false;
+        }
+        if depth < NULL_MOVE_VERIFY_DEPTH {
+            return true;
+        }
+        self.skip_null = true;
+        self.negamax(board, remaining, beta - 1, beta, ply) >= beta
+    }
+
+    // At the end of the main search, keep playing captures until the position
+    // is quiet. Otherwise the engine would happily evaluate a position where
+    // its queen is about to be taken as "a queen up".
+    fn quiescence(&mut self, board: &mut Board, mut alpha: i32, beta: i32, ply: u32) -> i32 {
+        self.clear_pv(ply);
+        if self.out_of_time() {
+            return 0;
+        }
+
+        let moves = self.legal_moves(board);
+        if moves.is_empty() {
+            return if in_check(board) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+
+        // "Stand pat": the side to move can usually decline to capture, so the
+        // static evaluation is a lower bound on the score.
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut tactical: Vec<Move> = moves.into_iter().filter(|mv| is_tactical(board, mv)).collect();
+        order_captures(board, &mut tactical);
+        for mv in tactical {
+            let undo = board.make_move(mv);
+            let score = -self.quiescence(board, -beta, -alpha, ply + 1);
+            board.unmake_move(mv, undo);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
@@ -0,0 +1,123 @@
+//! Strength-limited play.
+//! 
+//! This file defines:
+//! - Strength, a playing strength on the Elo scale from 800 to 2400
+//! - What that strength means for the search: how deep it looks, how much
+//!   noise is added to its scores and how often it settles for a move that
+//!   is merely good enough
+//! - Choosing a move at that strength
+//!
+//! The numbers are rough. Nothing here has been measured against rated
+//! players; they are only meant to go up in sensible steps.
+
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::chess::board::Board;
+use crate::chess::r#move::Move;
+use crate::engine::ordering::MoveOrdering;
+use crate::engine::search::{Engine, SearchLimits, MATE_SCORE};
+
+pub const MIN_ELO: u16 = 800;
+pub const MAX_ELO: u16 = 2400;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Strength {
+    elo: u16,
+}
+
+impl Strength {
+    // A strength of `elo`, clamped to MIN_ELO..=MAX_ELO.
+    pub fn new(elo: u16) -> Self {
+        Strength { elo: elo.clamp(MIN_ELO, MAX_ELO) }
+    }
+
+    pub fn elo(self) -> u16 {
+        self.elo
+    }
+
+    // How far below full strength this is, in Elo.
+    fn handicap(self) -> i32 {
+        (MAX_ELO - self.elo) as i32
+    }
+
+    // How many plies the search looks ahead: 2 at 800, one more every 400
+    // Elo, 6 at 2400.
+    pub fn depth(self) -> u32 {
+        2 + (self.elo - MIN_ELO) as u32 / 400
+    }
+
+    // Each move's score is moved by up to this many centipawns either way
+    // before the best is picked: 200 at 800, none at 2400.
+    pub fn eval_noise(self) -> i32 {
+        self.handicap() / 8
+    }
+
+    // How often, in percent, the move is picked at random from every move
+    // within `candidate_window` of the best instead of being the best: 20%
+    // at 800, never at 2400.
+    pub fn randomness(self) -> u32 {
+        self.handicap() as u32 / 80
+    }
+
+    // In centipawns: 100 at 800, nothing at 2400.
+    pub fn candidate_window(self) -> i32 {
+        self.handicap() / 16
+    }
+
+    // Picks a move for the side to move within `time`, if given, or None if
+    // there are no legal moves.
+    //
+    // Every legal move is searched to the strength's depth and its score
+    // blurred by the noise. Usually the best blurred score wins; sometimes
+    // any move close enough to it does. At full strength that is just a
+    // normal search.
+    //
+    // Moves are scored captures first. If time runs out before they have
+    // all been scored, the choice is made from those that were, so there is
+    // always at least one.
+    pub fn choose_move(
+        self,
+        engine: &mut Engine,
+        board: &Board,
+        time: Option<Duration>,
+        rng: &mut impl Rng,
+    ) -> Option<Move> {
+        if self.elo == MAX_ELO {
+            let limits = SearchLimits { depth: Some(self.depth()), time, nodes: None };
+            return engine.search(board, limits).map(|result| result.best_move);
+        }
+
+        let deadline = time.map(|time| Instant::now() + time);
+        let mut board = board.clone();
+        let mut moves = board.legal_moves();
+        MoveOrdering::new().order(&board, &mut moves, None, 0);
+        let mut scored = Vec::new();
+        for mv in moves {
+            let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if !scored.is_empty() && left == Some(Duration::ZERO) {
+                break;
+            }
+            let undo = board.make_move(mv);
+            let limits = SearchLimits { depth: Some(self.depth() - 1), time: left, nodes: None };
+            let score = match engine.search(&board, limits) {
+                Some(reply) => -reply.score,
+                // The opponent can't move: we mate them, or it's stalemate.
+                None if board.king_in_check(board.side_to_move()) => MATE_SCORE,
+                None => 0,
+            };
+            board.unmake_move(mv, undo);
+            let noise = self.eval_noise();
+            scored.push((mv, score + rng.gen_range(-noise..=noise)));
+        }
+
+        let best = scored.iter().map(|&(_, score)| score).max()?;
+        let candidates: Vec<Move> = if rng.gen_range(0..100) < self.randomness() {
+            scored.iter().filter(|&&(_, score)| score >= best - self.candidate_window()).map(|&(mv, _)| mv).collect()
+        } else {
+            scored.iter().filter(|&&(_, score)| score == best).map(|&(mv, _)| mv).collect()
+        };
+        Some(candidates[rng.gen_range(0..candidates.len())])
+    }
+}
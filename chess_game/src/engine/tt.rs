@@ -0,0 +1,180 @@
+//! The transposition table.
+//! 
+//! This file defines:
+//! - Bound, saying how a stored score relates to the true one
+//! - TtEntry, what the search remembers about one position
+//! - TranspositionTable, a fixed-size table of entries keyed by Zobrist hash,
+//!   with its replacement policy and aging between searches
+//!
+//! The same position is reached again and again, both by different move
+//! orders within one search and from one iteration to the next. Remembering
+//! what was found there lets the search skip positions it has already seen
+//! to enough depth, and try the move that was best last time first.
+
+use crate::chess::r#move::Move;
+use crate::engine::search::MATE_SCORE;
+
+// Scores this close to MATE_SCORE are mates, whose distance depends on where
+// in the tree the position was found.
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+
+// Alpha-beta only finds a position's exact score when it falls inside the
+// window; otherwise it only learns which side of the window it is on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    // The score is at least this much (the search failed high).
+    Lower,
+    // The score is at most this much (no move reached alpha).
+    Upper,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TtEntry {
+    // The full hash, to tell the position apart from others sharing its slot.
+    key: u64,
+    // The move that was best, or caused the cutoff. None if every move
+    // failed low, when no move stood out.
+    pub best_move: Option<Move>,
+    // From the side to move's point of view. Mates are counted from this
+    // position rather than the root; `probe` converts them back.
+    pub score: i32,
+    // How many plies deep the position was searched.
+    pub depth: u8,
+    pub bound: Bound,
+    // The search that stored the entry.
+    generation: u8,
+}
+
+impl TtEntry {
+    // The score if it settles the search of this position with this window
+    // at this depth, as it does when it is deep enough and on the right side
+    // of the window.
+    pub fn cutoff(&self, depth: u32, alpha: i32, beta: i32) -> Option<i32> {
+        if (self.depth as u32) < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+// A hash table with a fixed number of slots, one entry each. When two
+// positions want the same slot, the entry kept is:
+// - the same position's, always replaced by the newer result;
+// - otherwise one from an earlier search is replaced, as it is stale;
+// - otherwise the deeper one, as it saved more work to find.
+pub struct TranspositionTable {
+    slots: Vec<Option<TtEntry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    // The table the engine uses unless told otherwise.
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    // A table taking up to `size_mb` megabytes. The slot count is rounded
+    // down to a power of two, and there is always at least one slot.
+    pub fn new(size_mb: usize) -> Self {
+        let slot_size = std::mem::size_of::<Option<TtEntry>>();
+        let wanted = (size_mb * 1024 * 1024 / slot_size).max(1);
+        let slots = 1 << wanted.ilog2();
+        TranspositionTable { slots: vec![None; slots], generation: 0 }
+    }
+
+    // How many entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn index(&self, key: u64) -> usize {
+        // The slot count is a power of two, so this is key % len.
+        (key as usize) & (self.slots.len() - 1)
+    }
+
+    // Empties the table, e.g. for a new game.
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.generation = 0;
+    }
+
+    // Marks the start of a new search. Entries from earlier searches are
+    // still used, but give way to new ones.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    // The entry for the position with this hash, if there is one, with any
+    // mate score counted from `ply` plies below the root.
+    pub fn probe(&self, key: u64, ply: u32) -> Option<TtEntry> {
+        let mut entry = self.slots[self.index(key)].filter(|entry| entry.key == key)?;
+        entry.score = from_table(entry.score, ply);
+        Some(entry)
+    }
+
+    // Remembers what the search found for the position with this hash,
+    // `ply` plies below the root.
+    pub fn store(&mut self, key: u64, ply: u32, depth: u32, score: i32, bound: Bound, best_move: Option<Move>) {
+        let index = self.index(key);
+        let generation = self.generation;
+        if let Some(old) = self.slots[index] {
+            if old.key != key && old.generation == generation && old.depth as u32 > depth {
+                return;
+            }
+        }
+        // A fail-low result has no move of its own, so keep the old one.
+        let best_move = best_move.or_else(|| self.slots[index].filter(|old| old.key == key)?.best_move);
+        self.slots[index] = Some(TtEntry {
+            key,
+            best_move,
+            score: to_table(score, ply),
+            depth: depth.min(u8::MAX as u32) as u8,
+            bound,
+            generation,
+        });
+    }
+
+    // Roughly how full the table is, in thousandths, from a sample of slots
+    // (as UCI's "hashfull" reports it). Only entries from the current search
+    // count.
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| slot.is_some_and(|entry| entry.generation == self.generation))
+            .count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(Self::DEFAULT_SIZE_MB)
+    }
+}
+
+// A mate score counts plies from the root, but the same position can be
+// reached at any ply. The table counts from the position itself instead.
+fn to_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
@@ -0,0 +1,260 @@
+//! The Universal Chess Interface.
+//! 
+//! This file includes:
+//! - UciSession, which reads UCI commands and answers them as an engine
+//! - Turning `go`'s clock arguments into search limits
+//! - `run`, the read-answer loop behind the `chess_uci` binary
+//!
+//! UCI is the text protocol chess GUIs and tournament managers (Arena,
+//! cutechess-cli and so on) use to talk to engines over stdin and stdout.
+//! Only the engine side's essentials are supported: `uci`, `isready`,
+//! `ucinewgame`, `position`, `go`, `stop`, `setoption` and `quit`.
+
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::chess::board::Board;
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::Move;
+use crate::engine::search::{Engine, EngineOptions, SearchLimits, SearchResult, MATE_SCORE};
+
+// The largest transposition table `setoption name Hash` accepts, in MB.
+const MAX_HASH_MB: usize = 1024;
+
+// Without `movestogo`, the engine budgets as if this many moves were left.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// Time kept back from every move so that the GUI's own overhead can't make
+// the engine lose on time.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// How long to think for, given the arguments of `go` and the side to move.
+// An explicit depth, node count or move time is used as given; otherwise the
+// clock decides, as in BotLevel::time_budget. `go infinite`, or `go` with
+// nothing after it, searches until `stop`.
+pub fn go_limits(args: &[&str], side: PieceColor) -> SearchLimits {
+    if args.contains(&"infinite") {
+        return SearchLimits::default();
+    }
+    let value = |name: &str| {
+        let at = args.iter().position(|arg| *arg == name)?;
+        args.get(at + 1)?.parse::<u64>().ok()
+    };
+    let mut limits = SearchLimits {
+        depth: value("depth").map(|depth| depth as u32),
+        nodes: value("nodes"),
+        time: value("movetime").map(Duration::from_millis),
+    };
+
+    let (time, increment) = match side {
+        PieceColor::White => ("wtime", "winc"),
+        PieceColor::Black => ("btime", "binc"),
+    };
+    if let (None, Some(remaining)) = (limits.time, value(time)) {
+        let remaining = Duration::from_millis(remaining);
+        let increment = Duration::from_millis(value(increment).unwrap_or(0));
+        let moves_to_go = value("movestogo").map_or(DEFAULT_MOVES_TO_GO, |moves| moves.max(1) as u32);
+        let budget = remaining / moves_to_go + increment / 2;
+        let most = remaining.saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1));
+        limits.time = Some(budget.min(most));
+    }
+    limits
+}
+
+// A score as UCI writes it: "cp" and centipawns, or "mate" and how many
+// moves (not plies) until mate, negative if the engine is being mated.
+fn uci_score(score: i32) -> String {
+    let plies = MATE_SCORE - score.abs();
+    if plies > 1_000 {
+        return format!("cp {}", score);
+    }
+    let moves = (plies + 1) / 2;
+    format!("mate {}", if score > 0 { moves } else { -moves })
+}
+
+fn info_line(result: &SearchResult, started: Instant) -> String {
+    let elapsed = started.elapsed();
+    let nps = result.nodes * 1000 / (elapsed.as_millis() as u64).max(1);
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_uci()).collect();
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        uci_score(result.score),
+        result.nodes,
+        nps,
+        elapsed.as_millis(),
+        pv.join(" ")
+    )
+}
+
+// One conversation with a GUI. Searches run on their own thread so that
+// `stop` and `isready` are answered while the engine thinks; the engine is
+// handed back when the search ends.
+pub struct UciSession<W: Write + Send + 'static> {
+    // None while a search has it.
+    engine: Option<Engine>,
+    search: Option<JoinHandle<Engine>>,
+    stop: Arc<AtomicBool>,
+    board: Board,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send + 'static> UciSession<W> {
+    pub fn new(output: W) -> Self {
+        let engine = Engine::new();
+        UciSession {
+            stop: engine.stop_signal(),
+            engine: Some(engine),
+            search: None,
+            board: Board::new(),
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    // Waits for the running search, if any, and takes the engine back.
+    fn engine(&mut self) -> &mut Engine {
+        if let Some(search) = self.search.take() {
+            self.engine = Some(search.join().expect("search thread panicked"));
+        }
+        self.engine.as_mut().expect("the engine is back once the search has ended")
+    }
+
+    // Stops the running search, which still reports its best move.
+    pub fn stop(&mut self) {
+        if self.search.is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.engine();
+        }
+    }
+
+    // Handles one line from the GUI. Returns false once it says `quit`.
+    // Unknown commands are ignored, as the protocol asks.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return true;
+        };
+        match command {
+            "uci" => {
+                self.send(concat!("id name chess_game ", env!("CARGO_PKG_VERSION")));
+                self.send("id author the chess_game authors");
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    EngineOptions::default().hash_size_mb,
+                    MAX_HASH_MB
+                ));
+                self.send("option name Clear Hash type button");
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => self.engine().clear(),
+            "setoption" => self.set_option(args),
+            "position" => {
+                if let Some(board) = parse_position(args) {
+                    self.board = board;
+                }
+            }
+            "go" => self.go(args),
+            "stop" => self.stop(),
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    // `setoption name <name> [value <value>]`. Names may contain spaces and
+    // aren't case-sensitive.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|arg| *arg == "value").unwrap_or(args.len());
+        let name = args.get(1..value_at).unwrap_or_default().join(" ").to_ascii_lowercase();
+        let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+        match name.as_str() {
+            "hash" => {
+                if let Ok(size) = value.parse::<usize>() {
+                    let engine = self.engine();
+                    let options = EngineOptions { hash_size_mb: size.clamp(1, MAX_HASH_MB), ..engine.options() };
+                    engine.set_options(options);
+                }
+            }
+            "clear hash" => self.engine().clear(),
+            _ => {}
+        }
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        let limits = go_limits(args, self.board.side_to_move());
+        self.engine();
+        let mut engine = self.engine.take().expect("no search is running");
+        self.stop.store(false, Ordering::Relaxed);
+        let board = self.board.clone();
+        let output = Arc::clone(&self.output);
+        self.search = Some(std::thread::spawn(move || {
+            let started = Instant::now();
+            let result = engine.search_with(&board, limits, |result| {
+                send(&output, &info_line(result, started));
+            });
+            match result {
+                Some(result) => match result.pv.get(1) {
+                    Some(reply) => send(&output, &format!("bestmove {} ponder {}", result.best_move, reply)),
+                    None => send(&output, &format!("bestmove {}", result.best_move)),
+                },
+                // UCI's way of saying there is no move.
+                None => send(&output, "bestmove 0000"),
+            }
+            engine
+        }));
+    }
+
+    // Stops any search and hands back the output.
+    pub fn finish(mut self) -> W {
+        self.stop();
+        let output = Arc::try_unwrap(self.output).ok().expect("no search is running");
+        output.into_inner().unwrap()
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    // If the GUI has gone away there is no one to tell.
+    let _ = writeln!(output, "{}", line).and_then(|_| output.flush());
+}
+
+// `position startpos [moves ...]` or `position fen <fen> [moves ...]`. None
+// if the position or one of the moves isn't valid.
+fn parse_position(args: &[&str]) -> Option<Board> {
+    let moves_at = args.iter().position(|arg| *arg == "moves").unwrap_or(args.len());
+    let mut board = match *args.first()? {
+        "startpos" => Board::new(),
+        "fen" => Board::from_fen(&args[1..moves_at].join(" ")).ok()?,
+        _ => return None,
+    };
+    for uci in args.get(moves_at + 1..).unwrap_or_default() {
+        let wanted = Move::from_uci(uci)?;
+        let mv = board.legal_moves().into_iter().find(|mv| mv.same_squares(&wanted))?;
+        board.make_move(mv);
+    }
+    Some(board)
+}
+
+// Answers UCI commands from `input` until `quit` or the end of the input,
+// then returns the output.
+pub fn run<W: Write + Send + 'static>(input: impl BufRead, output: W) -> W {
+    let mut session = UciSession::new(output);
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if !session.handle(&line) {
+            break;
+        }
+    }
+    session.finish()
+}
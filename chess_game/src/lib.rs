@@ -2,9 +2,15 @@
 //! 
 //! This file exports the main modules of the application:
 //! - chess: Contains the core chess game logic
+//! - cli: Reading options from the command line
+//! - engine: Evaluation and search for the computer opponent
+//! - logging: Choosing the log verbosity and installing the subscriber
 //! - network: Handles networking and WebSocket communication
 //! - web: Manages HTTP routes and request handlers
 
 pub mod chess;
+pub mod cli;
+pub mod engine;
+pub mod logging;
 pub mod network;
 pub mod web;
@@ -0,0 +1,33 @@
+//! Logging setup for the server.
+//! 
+//! This file handles:
+//! - Picking the log level from `--log-level` or `RUST_LOG`
+//! - Installing the `tracing` subscriber that prints log events
+
+use tracing::level_filters::LevelFilter;
+
+// Used when neither the argument nor the environment names a level.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::INFO;
+
+// Maps a level name ("error", "warn", "info", "debug", "trace" or "off", in
+// any case) to a filter. Anything else, including an empty string, falls back
+// to DEFAULT_LEVEL rather than stopping the server from starting over a typo.
+pub fn parse_level(value: &str) -> LevelFilter {
+    match value.trim() {
+        // `LevelFilter`'s own parser reads "" as ERROR.
+        "" => DEFAULT_LEVEL,
+        value => value.parse().unwrap_or(DEFAULT_LEVEL),
+    }
+}
+
+// The level to log at: the command-line argument wins over `RUST_LOG`, and
+// with neither set we log at DEFAULT_LEVEL.
+pub fn choose_level(arg: Option<&str>, env: Option<&str>) -> LevelFilter {
+    arg.or(env).map_or(DEFAULT_LEVEL, parse_level)
+}
+
+// Installs a subscriber that prints events at `level` and above to stdout.
+// Only the first call in a process has any effect.
+pub fn init(level: LevelFilter) {
+    let _ = tracing_subscriber::fmt().with_max_level(level).try_init();
+}
@@ -3,10 +3,50 @@
 //! This file is responsible for:
 //! - Setting up and initializing the web server
 //! - Creating the initial game state
-//! - Handling command-line arguments (if any)
+//! - Handling command-line arguments (`--log-level`, and `--book` and
+//!   `--book-depth` for the bot's opening book)
 //! - Coordinating between the chess logic, networking, and web components
 
-fn main() {
-    println!("Chess game server starting...");
-    // TODO: Initialize server and game state
+use chess_game::cli;
+use chess_game::engine::book::{Book, BookError};
+use chess_game::logging;
+use chess_game::network::server;
+use chess_game::network::store::GameStore;
+
+// The value given for `--name` on this process's command line.
+fn arg_value(name: &str) -> Option<String> {
+    cli::arg_value(std::env::args().skip(1), name)
+}
+
+// The opening book named on the command line.
+fn load_book(path: &str) -> Result<Book, BookError> {
+    let mut book = Book::load(path)?;
+    if let Some(depth) = arg_value("book-depth").and_then(|depth| depth.parse().ok()) {
+        book = book.with_depth(depth);
+    }
+    Ok(book)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let arg = arg_value("log-level");
+    let env = std::env::var("RUST_LOG").ok();
+    logging::init(logging::choose_level(arg.as_deref(), env.as_deref()));
+
+    let store = match arg_value("book") {
+        Some(path) => match load_book(&path) {
+            Ok(book) => {
+                tracing::info!("Loaded opening book {} ({} entries)", path, book.len());
+                GameStore::with_book(book)
+            }
+            Err(error) => {
+                tracing::warn!("Playing without an opening book: {}", error);
+                GameStore::new()
+            }
+        },
+        None => GameStore::new(),
+    };
+
+    tracing::info!("Chess game server starting on {}...", server::DEFAULT_ADDRESS);
+    server::run_server_with(store).await
 }
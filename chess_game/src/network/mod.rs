@@ -6,4 +6,5 @@
 //! - Real-time game state synchronization
 
 pub mod server;
+pub mod store;
 pub mod websocket;
@@ -7,7 +7,26 @@
 
 use actix_web::{web, App, HttpServer};
 
+use crate::network::store::GameStore;
+use crate::web::routes;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
 pub async fn run_server() -> std::io::Result<()> {
-    // TODO: Implement server setup and run loop
-    Ok(())
+    run_server_with(GameStore::new()).await
+}
+
+// Serves the games in `store`, e.g. one set up with an opening book.
+pub async fn run_server_with(store: GameStore) -> std::io::Result<()> {
+    // One store shared by every worker thread; `web::Data` wraps it in an Arc.
+    let store = web::Data::new(store);
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(store.clone())
+            .configure(routes::config)
+    })
+    .bind(DEFAULT_ADDRESS)?
+    .run()
+    .await
 }
@@ -0,0 +1,462 @@
+//! In-memory registry of live games.
+//! 
+//! This file manages:
+//! - Creating games and handing out their ids
+//! - Looking up and mutating a game by id
+//! - Tracking which WebSocket sessions are watching each game
+//! - Broadcasting updates to those sessions, including the opening played
+//! - Archiving finished games so their results outlive the live game
+//! - Counting activity for the metrics endpoint
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix_web::web;
+use actix_ws::Session;
+use serde::{Deserialize, Serialize};
+
+use crate::chess::game::{Game, GameError};
+use crate::chess::openings::{Opening, Openings};
+use crate::chess::piece::PieceColor;
+use crate::chess::r#move::{Move, MoveError};
+use crate::engine::book::Book;
+use crate::engine::bot::BotLevel;
+use crate::engine::hint::{hint, Hint};
+use crate::engine::ponder::Ponder;
+use crate::engine::search::Engine;
+use crate::network::websocket::{GameState, ServerMessage};
+
+pub type GameId = u64;
+
+// Identifies one WebSocket connection, so it can be dropped from the games
+// it watches without relying on its position in a list that may have changed
+// meanwhile.
+pub type SessionId = u64;
+
+// The computer's side in a game against the bot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bot {
+    pub level: BotLevel,
+    pub color: PieceColor,
+    // Whether the bot keeps thinking while its opponent does, on the reply
+    // it expects.
+    pub ponder: bool,
+}
+
+// The bot's move, and the reply it expects if it knows one. A book move
+// comes first; then the result of pondering, if the opponent played the
+// reply it was expecting; then a search from scratch.
+fn bot_reply(
+    game: &Game,
+    bot: Bot,
+    engine: &mut Engine,
+    book: Option<&Book>,
+    ponder: Option<Ponder>,
+) -> Option<(Move, Option<Move>)> {
+    if let Some(mv) = book.and_then(|book| book.choose_for_game(game, &mut rand::thread_rng())) {
+        return Some((mv, None));
+    }
+    // Pondered time was the opponent's, so on a hit the bot's own clock
+    // only pays for whatever is left of its usual budget.
+    let budget = bot.level.search_limits(game.clock(), bot.color).time;
+    let played = game.history().last().map(|entry| entry.mv());
+    let pondered = ponder.zip(played).and_then(|(ponder, played)| ponder.finish(played, budget));
+    match pondered.or_else(|| bot.level.search(engine, game)) {
+        Some(result) => Some((result.best_move, result.pv.get(1).copied())),
+        None => bot.level.choose_move(engine, game).map(|mv| (mv, None)),
+    }
+}
+
+// What is kept of a game once it has finished.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GameRecord {
+    pub id: GameId,
+    // "1-0", "0-1" or "1/2-1/2".
+    pub result: String,
+    // How it ended, e.g. "checkmate" or "resignation".
+    pub reason: String,
+    pub final_fen: String,
+    // The moves in SAN, oldest first.
+    pub moves: Vec<String>,
+}
+
+impl GameRecord {
+    fn from_game(id: GameId, game: &Game) -> Option<Self> {
+        Some(GameRecord {
+            id,
+            result: game.result()?.as_str().to_string(),
+            reason: game.termination()?.as_str().to_string(),
+            final_fen: game.current_fen(),
+            moves: game.history().iter().map(|entry| entry.san().to_string()).collect(),
+        })
+    }
+}
+
+// A snapshot of server activity, as served by `GET /metrics`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Metrics {
+    // Games currently held in memory.
+    pub active_games: usize,
+    // Open WebSocket connections.
+    pub connected_clients: usize,
+    // Moves played in any game since the server started.
+    pub total_moves: u64,
+    pub uptime_seconds: u64,
+}
+
+struct LiveGame {
+    game: Game,
+    sessions: HashMap<SessionId, Session>,
+    bot: Option<Bot>,
+    // The bot thinking on its opponent's time, if it is.
+    ponder: Option<Ponder>,
+    // The engine behind the bot's moves and hints, made the first time one
+    // is needed and kept so that what it learns carries over from move to
+    // move. Taken out while it searches.
+    engine: Option<Engine>,
+    // Whether the current result has been written to the archive. Cleared
+    // again if the game is reset and played on.
+    archived: bool,
+    // Whether the game has left opening theory and watchers have been told
+    // its opening. Cleared again if a reset or undo takes it back into
+    // theory.
+    opening_announced: bool,
+}
+
+// Shared by every request handler through `web::Data`, so all access goes
+// through a mutex. Handlers only hold the lock for as long as it takes to
+// read or update a game; network sends happen after it is released.
+//
+// When both locks are needed, `games` is always taken before `archive`.
+pub struct GameStore {
+    games: Mutex<HashMap<GameId, LiveGame>>,
+    archive: Mutex<HashMap<GameId, GameRecord>>,
+    next_id: AtomicU64,
+    next_session: AtomicU64,
+    // WebSocket connections opened and not yet closed.
+    connected: AtomicUsize,
+    total_moves: AtomicU64,
+    started: Instant,
+    // The opening book the bot plays from, if the server was given one.
+    book: Option<Arc<Book>>,
+}
+
+impl GameStore {
+    pub fn new() -> Self {
+        GameStore {
+            games: Mutex::new(HashMap::new()),
+            archive: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            next_session: AtomicU64::new(0),
+            connected: AtomicUsize::new(0),
+            total_moves: AtomicU64::new(0),
+            started: Instant::now(),
+            book: None,
+        }
+    }
+
+    // A store whose bots play their openings from `book`.
+    pub fn with_book(book: Book) -> Self {
+        GameStore { book: Some(Arc::new(book)), ..GameStore::new() }
+    }
+
+    // Registers a game and returns its id. Ids start at 1.
+    pub fn create(&self, game: Game) -> GameId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let live = LiveGame {
+            game,
+            sessions: HashMap::new(),
+            bot: None,
+            ponder: None,
+            engine: None,
+            archived: false,
+            opening_announced: false,
+        };
+        self.games.lock().unwrap().insert(id, live);
+        id
+    }
+
+    // Runs `f` against the game with the given id, returning None if there is
+    // no such game.
+    //
+    // Every change to a game goes through here, so this is also where a game
+    // that has just finished gets archived.
+    pub fn with_game<R>(&self, id: GameId, f: impl FnOnce(&mut Game) -> R) -> Option<R> {
+        let mut games = self.games.lock().unwrap();
+        let live = games.get_mut(&id)?;
+        let output = f(&mut live.game);
+
+        match (GameRecord::from_game(id, &live.game), live.archived) {
+            (Some(record), false) => {
+                self.archive.lock().unwrap().insert(id, record);
+                live.archived = true;
+            }
+            (None, true) => live.archived = false,
+            _ => {}
+        }
+        Some(output)
+    }
+
+    // Plays a move in the game with the given id and returns the new state.
+    // Only moves played here count towards the metrics, so stepping back and
+    // forth through the history doesn't look like new moves.
+    pub fn play_move(&self, id: GameId, mv: Move) -> Option<Result<GameState, MoveError>> {
+        self.play_move_at(id, None, mv)
+    }
+
+    // Like `play_move`, but with `ply` set it plays nothing and returns None
+    // unless the game is still at that ply.
+    fn play_move_at(&self, id: GameId, ply: Option<usize>, mv: Move) -> Option<Result<GameState, MoveError>> {
+        let played = self.with_game(id, |game| {
+            if ply.is_some_and(|ply| game.history().len() != ply) {
+                return None;
+            }
+            Some(game.make_move(mv).map(|()| GameState::from_game(id, game)))
+        })??;
+        if played.is_ok() {
+            self.total_moves.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(played)
+    }
+
+    // Drops a live game from memory. Its archived result, if any, is kept.
+    pub fn remove(&self, id: GameId) -> bool {
+        self.games.lock().unwrap().remove(&id).is_some()
+    }
+
+    // The archived record of a finished game, whether or not it is still live.
+    pub fn record(&self, id: GameId) -> Option<GameRecord> {
+        self.archive.lock().unwrap().get(&id).cloned()
+    }
+
+    // Hands one side of the game to the computer. Returns false if the game
+    // doesn't exist.
+    pub fn set_bot(&self, id: GameId, bot: Bot) -> bool {
+        let mut games = self.games.lock().unwrap();
+        match games.get_mut(&id) {
+            Some(live) => {
+                live.bot = Some(bot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Hands back a game's engine after a search. If another search took a
+    // fresh one in the meantime, the first to come back is kept.
+    fn return_engine(&self, id: GameId, engine: Engine) {
+        if let Some(live) = self.games.lock().unwrap().get_mut(&id) {
+            live.engine.get_or_insert(engine);
+        }
+    }
+
+    // If it is the bot's turn, lets it think, plays its move and broadcasts
+    // the new state. Returns that state, or None if the bot didn't move.
+    //
+    // The search runs on a copy of the game in a blocking thread, so the store
+    // stays unlocked and other games keep running while the bot thinks.
+    //
+    // With pondering on, the bot then starts thinking about the reply it
+    // expects, and picks that search up again next turn.
+    pub async fn play_bot_turn(&self, id: GameId) -> Option<GameState> {
+        let (game, bot, mut engine, ponder) = {
+            let mut games = self.games.lock().unwrap();
+            let live = games.get_mut(&id)?;
+            let bot = live.bot?;
+            if live.game.is_over() || live.game.side_to_move() != bot.color {
+                return None;
+            }
+            (live.game.clone(), bot, live.engine.take().unwrap_or_default(), live.ponder.take())
+        };
+
+        let ply = game.history().len();
+        let book = self.book.clone();
+        let (reply, engine) = web::block(move || {
+            let reply = bot_reply(&game, bot, &mut engine, book.as_deref(), ponder);
+            (reply, engine)
+        })
+        .await
+        .ok()?;
+        self.return_engine(id, engine);
+        let (mv, expected) = reply?;
+
+        // The game may have moved on (say, been reset) while we thought.
+        let state = match self.play_move_at(id, Some(ply), mv)? {
+            Ok(state) => state,
+            Err(error) => {
+                // The bot's search and the game disagree on the rules, so
+                // it would never get a move in; tell the players.
+                tracing::error!(game_id = id, mv = %mv, "bot move rejected: {}", error);
+                self.broadcast(id, &ServerMessage::move_error(&error)).await;
+                return None;
+            }
+        };
+
+        if let (true, Some(expected)) = (bot.ponder, expected) {
+            self.start_pondering(id, bot, expected);
+        }
+        self.broadcast_state(&state).await;
+        Some(state)
+    }
+
+    // Has the bot think about the position after `expected` while its
+    // opponent is to move.
+    fn start_pondering(&self, id: GameId, bot: Bot, expected: Move) {
+        let Some(board) = self.with_game(id, |game| game.board().clone()) else {
+            return;
+        };
+        // The depth the bot's level searches to; the time limit only starts
+        // to matter when the opponent has moved.
+        let depth = bot.level.search_limits(None, bot.color).depth;
+        let ponder = Ponder::start(&board, expected, depth);
+
+        let mut games = self.games.lock().unwrap();
+        if let Some(live) = games.get_mut(&id) {
+            // Only if nothing has happened in the game in the meantime.
+            if !live.game.is_over() && live.game.board().zobrist_hash() == board.zobrist_hash() {
+                live.ponder = ponder;
+            }
+        }
+    }
+
+    // Turns pondering on or off for a game against the bot. Returns false if
+    // there is no such game or it has no bot.
+    pub fn set_ponder(&self, id: GameId, ponder: bool) -> bool {
+        let mut games = self.games.lock().unwrap();
+        match games.get_mut(&id) {
+            Some(LiveGame { bot: Some(bot), ponder: thinking, .. }) => {
+                bot.ponder = ponder;
+                if !ponder {
+                    *thinking = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Asks the game's engine for a move for the side to move. Like the bot,
+    // the search runs on a copy of the position off the async worker. None if
+    // there is no such game.
+    pub async fn hint(&self, id: GameId) -> Option<Result<Hint, GameError>> {
+        let (board, mut engine) = {
+            let mut games = self.games.lock().unwrap();
+            let live = games.get_mut(&id)?;
+            if let Err(error) = live.game.check_hint_allowed() {
+                return Some(Err(error));
+            }
+            (live.game.board().clone(), live.engine.take().unwrap_or_default())
+        };
+        let (hint, engine) = web::block(move || (hint(&mut engine, &board), engine)).await.ok()?;
+        self.return_engine(id, engine);
+        // A game that isn't over always has a legal move.
+        Some(hint.ok_or(GameError::GameOver))
+    }
+
+    // A snapshot of the game as sent to clients.
+    pub fn state(&self, id: GameId) -> Option<GameState> {
+        self.with_game(id, |game| GameState::from_game(id, game))
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        let games = self.games.lock().unwrap();
+        Metrics {
+            active_games: games.len(),
+            connected_clients: self.connected.load(Ordering::Relaxed),
+            total_moves: self.total_moves.load(Ordering::Relaxed),
+            uptime_seconds: self.started.elapsed().as_secs(),
+        }
+    }
+
+    // Counts a newly opened WebSocket connection and hands out the id it
+    // watches games under.
+    pub fn connect(&self) -> SessionId {
+        self.connected.fetch_add(1, Ordering::Relaxed);
+        self.next_session.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Forgets a closed connection, dropping it from every game it watched.
+    pub fn disconnect(&self, session_id: SessionId) {
+        self.connected.fetch_sub(1, Ordering::Relaxed);
+        for live in self.games.lock().unwrap().values_mut() {
+            live.sessions.remove(&session_id);
+        }
+    }
+
+    // Adds a WebSocket session to the game's watchers. Joining the same game
+    // again from the same connection doesn't add it twice. Returns false if
+    // the game doesn't exist.
+    pub fn subscribe(&self, id: GameId, session_id: SessionId, session: Session) -> bool {
+        let mut games = self.games.lock().unwrap();
+        match games.get_mut(&id) {
+            Some(live) => {
+                live.sessions.insert(session_id, session);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Sends a new state to everyone watching the game, followed by an
+    // Opening message if the game has just left opening theory and a
+    // GameOver message if that state ends the game.
+    pub async fn broadcast_state(&self, state: &GameState) {
+        self.broadcast(state.id, &ServerMessage::GameState(Box::new(state.clone()))).await;
+        if let Some(opening) = self.opening_left(state.id) {
+            let message = ServerMessage::Opening {
+                game_id: state.id,
+                eco: opening.eco.to_string(),
+                name: opening.name.to_string(),
+            };
+            self.broadcast(state.id, &message).await;
+        }
+        if let Some(game_over) = ServerMessage::game_over(state) {
+            self.broadcast(state.id, &game_over).await;
+        }
+    }
+
+    // The game's opening, the first time this is asked after the game has
+    // left theory. Games that never followed a named line have nothing to
+    // announce.
+    fn opening_left(&self, id: GameId) -> Option<Opening> {
+        let mut games = self.games.lock().unwrap();
+        let live = games.get_mut(&id)?;
+        let found = Openings::standard().classify_game(&live.game);
+        let announce = !found.in_theory && !live.opening_announced;
+        live.opening_announced = !found.in_theory;
+        found.opening.filter(|_| announce)
+    }
+
+    // Sends a message to every session watching the game. Sessions that have
+    // gone away are dropped from the list.
+    pub async fn broadcast(&self, id: GameId, message: &ServerMessage) {
+        let sessions = match self.games.lock().unwrap().get(&id) {
+            Some(live) => live.sessions.clone(),
+            None => return,
+        };
+        let text = message.to_json();
+
+        let mut closed = Vec::new();
+        for (session_id, mut session) in sessions {
+            if session.text(text.clone()).await.is_err() {
+                closed.push(session_id);
+            }
+        }
+
+        // The list may have changed while the lock was released, so closed
+        // sessions are dropped by id rather than by where they used to be.
+        if !closed.is_empty() {
+            if let Some(live) = self.games.lock().unwrap().get_mut(&id) {
+                live.sessions.retain(|session_id, _| !closed.contains(session_id));
+            }
+        }
+    }
+}
+
+impl Default for GameStore {
+    fn default() -> Self {
+        GameStore::new()
+    }
+}
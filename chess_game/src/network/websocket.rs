@@ -5,9 +5,364 @@
 //! - Real-time message passing between clients and server
 //! - Serialization and deserialization of game state updates
 
-use actix_web::web;
-use actix_ws::Message;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::{Message, Session};
+use serde::{Deserialize, Serialize};
 
-pub async fn websocket_route(req: web::HttpRequest, stream: web::Payload) {
-    // TODO: Implement WebSocket connection handler
+use crate::chess::game::{DrawClaim, Game, GameError};
+use crate::chess::piece::{PieceColor, PieceKind};
+use crate::chess::r#move::{Move, MoveError};
+use crate::network::store::{GameId, GameStore, SessionId};
+
+// Messages a client can send. On the wire they are JSON objects tagged by
+// "type", e.g. {"type": "move", "game_id": 1, "uci": "e2e4"}.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // Start receiving updates for a game.
+    Join { game_id: GameId },
+    // Play a move in UCI notation. A pawn move to the last rank may name its
+    // piece either in the UCI ("e7e8n") or with a separate "promotion" field,
+    // e.g. {"type": "move", "game_id": 1, "uci": "e7e8", "promotion": "knight"}.
+    Move {
+        game_id: GameId,
+        uci: String,
+        #[serde(default)]
+        promotion: Option<Promotion>,
+    },
+    // Give up the game on behalf of `color`.
+    Resign { game_id: GameId, color: PieceColor },
+    // Offer a draw on behalf of `color`.
+    OfferDraw { game_id: GameId, color: PieceColor },
+    // Answer the open draw offer.
+    RespondDraw { game_id: GameId, accept: bool },
+    // Claim a draw by repetition or the fifty-move rule. The server checks
+    // the claim before accepting it.
+    ClaimDraw { game_id: GameId, claim: DrawClaim },
+    // Ask the engine to suggest a move. Only the asker is sent the answer.
+    Hint { game_id: GameId },
+}
+
+// A piece a pawn can promote to, as a client names it.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Promotion {
+    Queen,
+    Rook,
+    Bishop,
+    Knight,
+}
+
+impl From<Promotion> for PieceKind {
+    fn from(promotion: Promotion) -> Self {
+        match promotion {
+            Promotion::Queen => PieceKind::Queen,
+            Promotion::Rook => PieceKind::Rook,
+            Promotion::Bishop => PieceKind::Bishop,
+            Promotion::Knight => PieceKind::Knight,
+        }
+    }
+}
+
+// The move a Move message asks for. A promotion piece in the UCI itself takes
+// precedence over the separate field.
+pub fn client_move(uci: &str, promotion: Option<Promotion>) -> Result<Move, MoveError> {
+    let mv = Move::from_uci(uci).ok_or_else(|| MoveError::InvalidNotation(uci.to_string()))?;
+    Ok(match (mv.promotion(), promotion) {
+        (None, Some(choice)) => Move::with_promotion(mv.from(), mv.to(), choice.into()),
+        _ => mv,
+    })
+}
+
+// Messages the server sends, tagged the same way.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    // Boxed, being much bigger than the other messages.
+    GameState(Box<GameState>),
+    // Sent once, after the final GameState, when a game ends.
+    GameOver { game_id: GameId, result: String, reason: String },
+    // Sent once, after the GameState for the move that took the game out of
+    // known opening theory, naming the last opening it followed.
+    Opening { game_id: GameId, eco: String, name: String },
+    // The answer to a Hint request: a suggested move for the side to move
+    // and why it is good.
+    Hint { game_id: GameId, uci: String, san: String, explanation: String, score: i32 },
+    // Something the client asked for went wrong. The code is for programs to
+    // act on, the message is for people to read. A rejected move also says
+    // exactly why, e.g. "blocked_path" or "leaves_king_in_check".
+    Error {
+        code: ErrorCode,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<&'static str>,
+    },
+}
+
+impl ServerMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("server messages always serialize")
+    }
+
+    // Reports a rejected move, picking the code from the reason.
+    pub fn move_error(error: &MoveError) -> Self {
+        ServerMessage::Error {
+            code: ErrorCode::from(error),
+            message: error.to_string(),
+            reason: Some(error.reason()),
+        }
+    }
+
+    // The GameOver message for a state, if the game in it has finished.
+    pub fn game_over(state: &GameState) -> Option<Self> {
+        Some(ServerMessage::GameOver {
+            game_id: state.id,
+            result: state.result.clone()?,
+            reason: state.reason.clone()?,
+        })
+    }
+
+    // Reports a game action, such as resigning, that couldn't be done.
+    pub fn game_error(error: &GameError) -> Self {
+        ServerMessage::Error { code: ErrorCode::NotAllowed, message: error.to_string(), reason: None }
+    }
+
+    // Reports text that isn't a valid ClientMessage.
+    pub fn malformed(error: &serde_json::Error) -> Self {
+        ServerMessage::Error {
+            code: ErrorCode::MalformedMessage,
+            message: format!("malformed message: {}", error),
+            reason: None,
+        }
+    }
+
+    pub fn game_not_found(game_id: GameId) -> Self {
+        ServerMessage::Error {
+            code: ErrorCode::GameNotFound,
+            message: format!("game {} not found", game_id),
+            reason: None,
+        }
+    }
+}
+
+// What kind of error a ServerMessage::Error reports. Sent as a snake_case
+// string, e.g. "not_your_turn".
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    IllegalMove,
+    NotYourTurn,
+    GameNotFound,
+    Unauthorized,
+    RateLimited,
+    MalformedMessage,
+    // A game action that the state of the game rules out, like resigning a
+    // finished game.
+    NotAllowed,
+}
+
+impl From<&MoveError> for ErrorCode {
+    fn from(error: &MoveError) -> Self {
+        match error {
+            MoveError::NotYourTurn => ErrorCode::NotYourTurn,
+            MoveError::InvalidNotation(_) | MoveError::Ambiguous(_) => ErrorCode::MalformedMessage,
+            // Everything else is a move the rules don't allow, including
+            // moves played after the game has ended.
+            _ => ErrorCode::IllegalMove,
+        }
+    }
+}
+
+// Everything a client needs to draw the game.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GameState {
+    pub id: GameId,
+    pub fen: String,
+    // Moves played so far in UCI notation.
+    pub moves: Vec<String>,
+    // "white" or "black".
+    pub turn: String,
+    // Whether the side to move's king is attacked, so the client can
+    // highlight it.
+    pub in_check: bool,
+    // "1-0", "0-1" or "1/2-1/2" once the game is over.
+    pub result: Option<String>,
+    // Why the game ended, e.g. "checkmate" or "insufficient_material".
+    pub reason: Option<String>,
+    pub white_time_ms: Option<u64>,
+    pub black_time_ms: Option<u64>,
+    // The players' names, if they gave them.
+    pub white_player: Option<String>,
+    pub black_player: Option<String>,
+    // Who has offered a draw that is still open: "white" or "black".
+    pub draw_offer: Option<PieceColor>,
+    // The rules being played, e.g. "standard" or "atomic".
+    pub variant: String,
+    // The position in the variant's FEN, for variants that extend it. None
+    // when it would be the same as `fen`.
+    pub variant_fen: Option<String>,
+}
+
+impl GameState {
+    pub fn from_game(id: GameId, game: &Game) -> Self {
+        let time_ms = |color| game.clock().map(|clock| clock.remaining(color).as_millis() as u64);
+        GameState {
+            id,
+            fen: game.current_fen(),
+            moves: game.uci_history(),
+            turn: match game.side_to_move() {
+                PieceColor::White => "white".to_string(),
+                PieceColor::Black => "black".to_string(),
+            },
+            in_check: game.is_in_check(),
+            result: game.result().map(|result| result.as_str().to_string()),
+            reason: game.termination().map(|termination| termination.as_str().to_string()),
+            white_time_ms: time_ms(PieceColor::White),
+            black_time_ms: time_ms(PieceColor::Black),
+            white_player: game.player(PieceColor::White).map(str::to_string),
+            black_player: game.player(PieceColor::Black).map(str::to_string),
+            draw_offer: game.draw_offer(),
+            variant: game.variant().name().to_string(),
+            variant_fen: Some(game.variant_fen()).filter(|fen| *fen != game.current_fen()),
+        }
+    }
+}
+
+async fn send(session: &mut Session, message: &ServerMessage) -> Result<(), actix_ws::Closed> {
+    session.text(message.to_json()).await
+}
+
+// Reports the outcome of a game action other than a move: every watcher
+// sees the new state, or only the asker hears why it was refused.
+async fn game_action(
+    store: &GameStore,
+    session: &mut Session,
+    game_id: GameId,
+    outcome: Option<Result<(), GameError>>,
+) -> Result<(), actix_ws::Closed> {
+    match outcome {
+        None => send(session, &ServerMessage::game_not_found(game_id)).await,
+        Some(Err(error)) => send(session, &ServerMessage::game_error(&error)).await,
+        Some(Ok(())) => {
+            if let Some(state) = store.state(game_id) {
+                store.broadcast_state(&state).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub async fn websocket_route(
+    req: HttpRequest,
+    stream: web::Payload,
+    store: web::Data<GameStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, mut session, mut messages) = actix_ws::handle(&req, stream)?;
+    let session_id = store.connect();
+
+    actix_web::rt::spawn(async move {
+        while let Some(Ok(message)) = messages.recv().await {
+            match message {
+                Message::Text(text) => {
+                    // A message we can't make sense of is answered with an
+                    // error, but the connection stays open: one bad message
+                    // from a buggy client shouldn't cost the player the game.
+                    let outcome = match serde_json::from_str::<ClientMessage>(&text) {
+                        Ok(client_message) => {
+                            handle_message(&store, session_id, &mut session, client_message).await
+                        }
+                        Err(error) => {
+                            tracing::debug!("malformed WebSocket message: {}", error);
+                            send(&mut session, &ServerMessage::malformed(&error)).await
+                        }
+                    };
+                    if outcome.is_err() {
+                        break;
+                    }
+                }
+                Message::Ping(bytes) if session.pong(&bytes).await.is_err() => break,
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        store.disconnect(session_id);
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+// Acts on one client message. Only fails if the client's connection is gone.
+async fn handle_message(
+    store: &GameStore,
+    session_id: SessionId,
+    session: &mut Session,
+    message: ClientMessage,
+) -> Result<(), actix_ws::Closed> {
+    match message {
+        ClientMessage::Join { game_id } => {
+            tracing::debug!(game_id, "client joined");
+            if !store.subscribe(game_id, session_id, session.clone()) {
+                return send(session, &ServerMessage::game_not_found(game_id)).await;
+            }
+            if let Some(state) = store.state(game_id) {
+                send(session, &ServerMessage::GameState(Box::new(state))).await?;
+            }
+        }
+        ClientMessage::Move { game_id, uci, promotion } => {
+            tracing::debug!(game_id, uci = %uci, "move received");
+            let outcome = match client_move(&uci, promotion) {
+                Ok(mv) => store.play_move(game_id, mv),
+                Err(error) => Some(Err(error)),
+            };
+            match outcome {
+                None => send(session, &ServerMessage::game_not_found(game_id)).await?,
+                Some(Err(error)) => {
+                    tracing::debug!(game_id, uci = %uci, "move rejected: {}", error);
+                    send(session, &ServerMessage::move_error(&error)).await?
+                }
+                Some(Ok(state)) => {
+                    store.broadcast_state(&state).await;
+                    // In a game against the computer, answer straight away.
+                    store.play_bot_turn(game_id).await;
+                }
+            }
+        }
+        ClientMessage::Resign { game_id, color } => {
+            tracing::debug!(game_id, ?color, "resignation received");
+            let outcome = store.with_game(game_id, |game| game.resign(color));
+            game_action(store, session, game_id, outcome).await?
+        }
+        ClientMessage::OfferDraw { game_id, color } => {
+            tracing::debug!(game_id, ?color, "draw offered");
+            let outcome = store.with_game(game_id, |game| game.offer_draw(color));
+            game_action(store, session, game_id, outcome).await?
+        }
+        ClientMessage::RespondDraw { game_id, accept } => {
+            tracing::debug!(game_id, accept, "draw offer answered");
+            let outcome = store.with_game(game_id, |game| game.respond_draw(accept));
+            game_action(store, session, game_id, outcome).await?
+        }
+        ClientMessage::ClaimDraw { game_id, claim } => {
+            tracing::debug!(game_id, ?claim, "draw claimed");
+            let outcome = store.with_game(game_id, |game| game.claim_draw(claim));
+            game_action(store, session, game_id, outcome).await?
+        }
+        ClientMessage::Hint { game_id } => {
+            tracing::debug!(game_id, "hint requested");
+            let reply = match store.hint(game_id).await {
+                None => ServerMessage::game_not_found(game_id),
+                Some(Err(error)) => ServerMessage::game_error(&error),
+                Some(Ok(hint)) => ServerMessage::Hint {
+                    game_id,
+                    uci: hint.mv.to_uci(),
+                    san: hint.san,
+                    explanation: hint.explanation,
+                    score: hint.score,
+                },
+            };
+            send(session, &reply).await?
+        }
+    }
+    Ok(())
 }
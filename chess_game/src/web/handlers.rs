@@ -4,10 +4,609 @@
 //! - Handler for serving the main game page
 //! - API handlers for game state queries and updates
 //! - Integration between HTTP requests and game logic
+//! - Checking answers to tactics puzzles
+//! - Building positions from a board editor
+//! - Attack counts for threat heatmaps
+//! - Legal moves from a single square
+//! - Per-move timestamps and clock readings
+//! - PGN uploads and downloads
+//! - Tactical motifs in a position
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
 use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::chess::board::{Board, CastlingRights};
+use crate::chess::builder::BoardBuilder;
+use crate::chess::clock::Clock;
+use crate::chess::game::{Game, GameError, GameMode};
+use crate::chess::piece::{Piece, PieceColor};
+use crate::chess::r#move::Move;
+use crate::chess::square::Square;
+use crate::chess::tactics::Motif;
+use crate::chess::variant::{variant_by_name, Standard, Variant};
+use crate::engine::analysis::analyze_game;
+use crate::engine::bot::BotLevel;
+use crate::engine::hint::Hint;
+use crate::engine::mate::{find_mate, mated_within};
+use crate::engine::search::{search, Engine, SearchLimits};
+use crate::network::store::{Bot, GameId, GameStore};
 
 pub async fn index() -> impl Responder {
     // TODO: Implement main page handler
     HttpResponse::Ok().body("Chess Game")
 }
+
+// A player's side as it appears in request bodies: "white" or "black".
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    White,
+    Black,
+}
+
+impl From<Side> for PieceColor {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::White => PieceColor::White,
+            Side::Black => PieceColor::Black,
+        }
+    }
+}
+
+// Body of `POST /games`. Every field is optional; an empty body creates a
+// standard game from the usual starting position without a clock.
+#[derive(Deserialize, Default, Debug)]
+pub struct CreateGameRequest {
+    // Starting position for puzzles and analysis.
+    pub fen: Option<String>,
+    #[serde(default)]
+    pub analysis: bool,
+    // Promote to a queen when a move to the last rank names no piece.
+    #[serde(default)]
+    pub auto_queen: bool,
+    pub initial_seconds: Option<u64>,
+    #[serde(default)]
+    pub increment_seconds: u64,
+    // Display names for the players.
+    pub white: Option<String>,
+    pub black: Option<String>,
+    // The rules to play by, e.g. "atomic". Standard chess if left out. A
+    // `fen` is read in the variant's own FEN.
+    pub variant: Option<String>,
+    // Rated games can't ask the engine for hints.
+    #[serde(default)]
+    pub rated: bool,
+}
+
+// Query string of `POST /games`, e.g. `?bot=hard` to play Black's moves
+// with the engine, or `?bot=1500` for an engine playing at about 1500 Elo.
+#[derive(Deserialize, Default, Debug)]
+pub struct CreateGameQuery {
+    pub bot: Option<String>,
+    // `&ponder=true` lets the bot think on its opponent's time.
+    #[serde(default)]
+    pub ponder: bool,
+}
+
+// Body of `POST /games/{id}/reset`: which player is asking.
+#[derive(Deserialize, Debug)]
+pub struct ResetRequest {
+    pub color: Side,
+}
+
+fn error_response(mut builder: actix_web::HttpResponseBuilder, message: String) -> HttpResponse {
+    builder.json(json!({ "error": message }))
+}
+
+// Reads a position sent by a client. Besides being valid FEN it has to be a
+// position that can be played from (one king each and so on), or the engine
+// and move generator could be handed nonsense.
+fn parse_position(fen: &str) -> Result<Board, String> {
+    let board = Board::from_fen(fen).map_err(|error| error.to_string())?;
+    board.validate().map_err(|error| error.to_string())?;
+    Ok(board)
+}
+
+// Like `parse_position`, for a game of the given variant, whose FEN may
+// have extra fields.
+fn parse_variant_position(fen: &str, variant: Arc<dyn Variant>) -> Result<Game, String> {
+    let game = Game::from_fen_with_variant(fen, variant).map_err(|error| error.to_string())?;
+    game.variant().validate(game.board()).map_err(|error| error.to_string())?;
+    Ok(game)
+}
+
+// Lets the bot move in the background if it is on move, as in a game set up
+// with the bot to play first, or after a reset. Like a reply to a WebSocket
+// move, the move reaches clients as a broadcast.
+fn start_bot_turn(store: &web::Data<GameStore>, id: GameId) {
+    let store = store.clone();
+    actix_web::rt::spawn(async move {
+        store.play_bot_turn(id).await;
+    });
+}
+
+fn game_not_found(id: GameId) -> HttpResponse {
+    error_response(HttpResponse::NotFound(), format!("game {} not found", id))
+}
+
+pub async fn create_game(
+    store: web::Data<GameStore>,
+    query: web::Query<CreateGameQuery>,
+    body: Option<web::Json<CreateGameRequest>>,
+) -> impl Responder {
+    let request = body.map(|json| json.into_inner()).unwrap_or_default();
+    let bot_level = match query.bot.as_deref().map(str::parse::<BotLevel>) {
+        Some(Err(message)) => return error_response(HttpResponse::BadRequest(), message),
+        Some(Ok(level)) => Some(level),
+        None => None,
+    };
+
+    let variant: Arc<dyn Variant> = match request.variant.as_deref() {
+        Some(name) => match variant_by_name(name) {
+            Some(variant) => variant,
+            None => return error_response(HttpResponse::BadRequest(), format!("unknown variant '{}'", name)),
+        },
+        None => Arc::new(Standard),
+    };
+    // The bot, like the engine behind it, only knows the standard rules.
+    if bot_level.is_some() && variant.name() != Variant::<Board>::name(&Standard) {
+        return error_response(
+            HttpResponse::BadRequest(),
+            format!("the bot can't play {}", variant.name()),
+        );
+    }
+    let mut game = match request.fen.as_deref() {
+        Some(fen) => match parse_variant_position(fen, variant) {
+            Ok(game) => game,
+            Err(message) => return error_response(HttpResponse::BadRequest(), message),
+        },
+        None => Game::with_variant(variant),
+    };
+    if request.analysis {
+        game.set_mode(GameMode::Analysis);
+    }
+    game.set_auto_queen(request.auto_queen);
+    game.set_rated(request.rated);
+    game.set_player(PieceColor::White, request.white);
+    game.set_player(PieceColor::Black, request.black);
+    if let Some(seconds) = request.initial_seconds {
+        let clock = Clock::new(
+            Duration::from_secs(seconds),
+            Duration::from_secs(request.increment_seconds),
+        );
+        game.set_clock(Some(clock));
+    }
+
+    let id = store.create(game);
+    if let Some(level) = bot_level {
+        // The human always takes White against the computer.
+        store.set_bot(id, Bot { level, color: PieceColor::Black, ponder: query.ponder });
+        start_bot_turn(&store, id);
+    }
+    match store.state(id) {
+        Some(state) => HttpResponse::Created().json(state),
+        None => game_not_found(id),
+    }
+}
+
+pub async fn get_game(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    match store.state(id) {
+        Some(state) => HttpResponse::Ok().json(state),
+        None => game_not_found(id),
+    }
+}
+
+// Body of `GET /games/{id}/attacks`: for each color, how many of its pieces
+// attack each square, listed a1, b1, ... h8.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct AttacksResponse {
+    pub white: Vec<u8>,
+    pub black: Vec<u8>,
+}
+
+// Attack counts for the current position, for drawing threat heatmaps.
+pub async fn get_attacks(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    let attacks = store.with_game(id, |game| AttacksResponse {
+        white: game.board().attack_map(PieceColor::White).to_vec(),
+        black: game.board().attack_map(PieceColor::Black).to_vec(),
+    });
+    match attacks {
+        Some(attacks) => HttpResponse::Ok().json(attacks),
+        None => game_not_found(id),
+    }
+}
+
+// One move in the body of `GET /games/{id}/timeline`. Times are in
+// milliseconds; `played_at_ms` counts from the Unix epoch.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MoveTiming {
+    pub ply: usize,
+    pub uci: String,
+    pub san: String,
+    pub played_at_ms: u64,
+    // How long the move took and what the mover had left afterwards. None
+    // for games without a clock.
+    pub think_ms: Option<u64>,
+    pub clock_ms: Option<u64>,
+}
+
+// When each move was played and how the clocks stood, for time-usage graphs.
+pub async fn get_timeline(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    let timeline = store.with_game(id, |game| {
+        game.history()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| MoveTiming {
+                ply: i + 1,
+                uci: entry.mv().to_uci(),
+                san: entry.san().to_string(),
+                played_at_ms: entry
+                    .played_at()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_millis() as u64),
+                think_ms: entry.time_taken().map(|time| time.as_millis() as u64),
+                clock_ms: entry.clock_remaining().map(|time| time.as_millis() as u64),
+            })
+            .collect::<Vec<_>>()
+    });
+    match timeline {
+        Some(timeline) => HttpResponse::Ok().json(timeline),
+        None => game_not_found(id),
+    }
+}
+
+// `POST /games/import`: starts a game from an uploaded PGN file, moves and
+// all. The body is the PGN text itself.
+pub async fn import_pgn(store: web::Data<GameStore>, body: String) -> impl Responder {
+    let game = match Game::from_pgn(&body) {
+        Ok(game) => game,
+        Err(error) => return error_response(HttpResponse::BadRequest(), error.to_string()),
+    };
+    let id = store.create(game);
+    match store.state(id) {
+        Some(state) => HttpResponse::Created().json(state),
+        None => game_not_found(id),
+    }
+}
+
+// The game as a PGN file download.
+pub async fn get_pgn(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    match store.with_game(id, |game| game.to_pgn()) {
+        Some(pgn) => HttpResponse::Ok()
+            .content_type("application/x-chess-pgn")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"game-{}.pgn\"", id)))
+            .body(pgn),
+        None => game_not_found(id),
+    }
+}
+
+// The legal moves of the piece on one square, for click-to-highlight. Empty
+// if the square is empty or holds a piece of the side not to move.
+pub async fn get_square_moves(
+    store: web::Data<GameStore>,
+    path: web::Path<(GameId, Square)>,
+) -> impl Responder {
+    let (id, square) = path.into_inner();
+    match store.with_game(id, |game| game.legal_move_details_from(square)) {
+        Some(moves) => HttpResponse::Ok().json(moves),
+        None => game_not_found(id),
+    }
+}
+
+// Returns the game to the starting position. Mid-game this needs both players
+// to ask; until then the request is recorded and answered with 409 Conflict.
+pub async fn reset_game(
+    store: web::Data<GameStore>,
+    path: web::Path<GameId>,
+    body: web::Json<ResetRequest>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let color = PieceColor::from(body.color);
+
+    match store.with_game(id, |game| game.request_reset(color)) {
+        None => game_not_found(id),
+        Some(Err(error)) => {
+            error_response(HttpResponse::Conflict(), error.to_string())
+        }
+        Some(Ok(())) => match store.state(id) {
+            Some(state) => {
+                store.broadcast_state(&state).await;
+                start_bot_turn(&store, id);
+                HttpResponse::Ok().json(state)
+            }
+            None => game_not_found(id),
+        },
+    }
+}
+
+// The archived outcome of a finished game. Still available after the live
+// game has been removed from the store.
+pub async fn get_result(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    match store.record(id) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => error_response(HttpResponse::NotFound(), format!("game {} has no result", id)),
+    }
+}
+
+// Counts of games, watchers and moves, for monitoring.
+pub async fn metrics(store: web::Data<GameStore>) -> impl Responder {
+    HttpResponse::Ok().json(store.metrics())
+}
+
+// Deepest search `/analyze` will run. Each extra ply multiplies the work, so
+// this keeps one request from tying up a worker.
+pub const MAX_ANALYZE_DEPTH: u32 = 6;
+
+// Body of `POST /analyze`.
+#[derive(Deserialize, Debug)]
+pub struct AnalyzeRequest {
+    pub fen: String,
+    pub depth: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct AnalyzeResponse {
+    pub best_move: String,
+    pub san: String,
+    // Centipawns from the side to move's point of view.
+    pub score: i32,
+    pub depth: u32,
+    // The expected line, in UCI, starting with best_move.
+    pub pv: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HintResponse {
+    pub uci: String,
+    pub san: String,
+    // Why the move is suggested, e.g. "wins material".
+    pub explanation: String,
+    // Centipawns from the side to move's point of view.
+    pub score: i32,
+}
+
+impl From<Hint> for HintResponse {
+    fn from(hint: Hint) -> Self {
+        HintResponse { uci: hint.mv.to_uci(), san: hint.san, explanation: hint.explanation, score: hint.score }
+    }
+}
+
+// A suggested move for the side to move in a game, from a short search.
+// Refused in rated games and once the game is over.
+pub async fn get_hint(store: web::Data<GameStore>, path: web::Path<GameId>) -> impl Responder {
+    let id = path.into_inner();
+    match store.hint(id).await {
+        None => game_not_found(id),
+        Some(Err(GameError::HintsDisabled)) => {
+            error_response(HttpResponse::Forbidden(), GameError::HintsDisabled.to_string())
+        }
+        Some(Err(error)) => error_response(HttpResponse::Conflict(), error.to_string()),
+        Some(Ok(hint)) => HttpResponse::Ok().json(HintResponse::from(hint)),
+    }
+}
+
+// Query string of `GET /games/{id}/analysis`.
+#[derive(Deserialize, Debug)]
+pub struct AnalysisQuery {
+    // Plies per position, 4 if left out and capped at MAX_ANALYZE_DEPTH.
+    pub depth: Option<u32>,
+}
+
+// Runs the engine over every position of a finished game: each move's
+// evaluation and classification, and each player's accuracy. Every position
+// gets its own search, so this takes a while for a long game.
+pub async fn get_analysis(
+    store: web::Data<GameStore>,
+    path: web::Path<GameId>,
+    query: web::Query<AnalysisQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let Some(game) = store.with_game(id, |game| game.clone()) else {
+        return game_not_found(id);
+    };
+    if !game.is_over() {
+        return error_response(HttpResponse::Conflict(), "the game isn't over yet".to_string());
+    }
+    // The engine only knows the standard rules.
+    if game.variant().name() != Variant::<Board>::name(&Standard) {
+        return error_response(
+            HttpResponse::UnprocessableEntity(),
+            format!("games of {} can't be analyzed", game.variant().name()),
+        );
+    }
+    let depth = query.depth.unwrap_or(4).clamp(1, MAX_ANALYZE_DEPTH);
+    match web::block(move || analyze_game(&game, depth)).await {
+        Ok(analysis) => HttpResponse::Ok().json(analysis),
+        Err(error) => error_response(HttpResponse::InternalServerError(), error.to_string()),
+    }
+}
+
+// Evaluates an arbitrary position without creating a game. The depth
+// defaults to 4 plies and is capped at MAX_ANALYZE_DEPTH.
+pub async fn analyze(body: web::Json<AnalyzeRequest>) -> impl Responder {
+    let request = body.into_inner();
+    let board = match parse_position(&request.fen) {
+        Ok(board) => board,
+        Err(message) => return error_response(HttpResponse::BadRequest(), message),
+    };
+    let depth = request.depth.unwrap_or(4).clamp(1, MAX_ANALYZE_DEPTH);
+
+    // The search is CPU-bound, so keep it off the async worker.
+    let result = web::block(move || {
+        Engine::new().search(&board, SearchLimits::depth(depth)).map(|result| (board, result))
+    })
+    .await;
+    match result {
+        Ok(Some((board, result))) => HttpResponse::Ok().json(AnalyzeResponse {
+            best_move: result.best_move.to_uci(),
+            san: result.best_move.to_san(&board),
+            score: result.score,
+            depth: result.depth,
+            pv: result.pv.iter().map(Move::to_uci).collect(),
+        }),
+        Ok(None) => error_response(
+            HttpResponse::UnprocessableEntity(),
+            "the position has no legal moves".to_string(),
+        ),
+        Err(error) => error_response(HttpResponse::InternalServerError(), error.to_string()),
+    }
+}
+
+// Body of `POST /tactics`.
+#[derive(Deserialize, Debug)]
+pub struct TacticsRequest {
+    pub fen: String,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct TacticsResponse {
+    pub motifs: Vec<Motif>,
+}
+
+// Lists the forks, pins and other motifs in a position, for both sides.
+pub async fn find_tactics(body: web::Json<TacticsRequest>) -> impl Responder {
+    match parse_position(&body.fen) {
+        Ok(board) => HttpResponse::Ok().json(TacticsResponse { motifs: board.tactical_motifs() }),
+        Err(message) => error_response(HttpResponse::BadRequest(), message),
+    }
+}
+
+// Longest forced mate `/puzzles/check` looks for, in moves by the solver.
+// The mate search is exhaustive, so this has to stay small.
+pub const PUZZLE_MATE_MOVES: u32 = 2;
+
+// Search depth for judging puzzles that aren't a forced mate.
+pub const PUZZLE_DEPTH: u32 = 4;
+
+// Body of `POST /puzzles/check`: a position and the move the solver chose,
+// in UCI notation.
+#[derive(Deserialize, Debug)]
+pub struct PuzzleCheckRequest {
+    pub fen: String,
+    pub r#move: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PuzzleCheckResponse {
+    // Whether the move is the solution, or just as good as it.
+    pub correct: bool,
+    // The engine's solution, in UCI and SAN.
+    pub best_move: String,
+    pub san: String,
+    // Set when the puzzle is a forced mate: the number of moves it takes.
+    pub mate_in: Option<u32>,
+    // For a wrong answer, the opponent's best reply to it, in UCI.
+    pub refutation: Option<String>,
+}
+
+// Judges one puzzle answer. Runs on a blocking thread, since both the mate
+// search and the regular search can take a while.
+fn judge_puzzle(game: &Game, answer: &Game) -> Option<PuzzleCheckResponse> {
+    let board = game.board();
+    let answer_move = answer.history().last()?.mv();
+
+    let (best, mate_in, correct) = match find_mate(game, PUZZLE_MATE_MOVES) {
+        Some(line) => {
+            let moves = line.len().div_ceil(2) as u32;
+            // Any move that still mates just as fast counts.
+            (line[0], Some(moves), mated_within(answer, moves - 1))
+        }
+        None => {
+            let result = search(board, PUZZLE_DEPTH)?;
+            // Otherwise the answer has to score as well as the engine's move.
+            let answer_score = search(answer.board(), PUZZLE_DEPTH - 1)
+                .map_or(0, |reply| -reply.score);
+            let correct = answer_move == result.best_move || answer_score >= result.score;
+            (result.best_move, None, correct)
+        }
+    };
+
+    let refutation = if correct {
+        None
+    } else {
+        search(answer.board(), PUZZLE_DEPTH - 1).map(|reply| reply.best_move.to_uci())
+    };
+    Some(PuzzleCheckResponse {
+        correct,
+        best_move: best.to_uci(),
+        san: best.to_san(board),
+        mate_in,
+        refutation,
+    })
+}
+
+// Checks a tactics-trainer answer: is `move` the best move in `fen`? A wrong
+// answer gets the solution and the reply that refutes it.
+pub async fn check_puzzle(body: web::Json<PuzzleCheckRequest>) -> impl Responder {
+    let request = body.into_inner();
+    let game = match parse_position(&request.fen) {
+        Ok(board) => Game::from_board(board),
+        Err(message) => return error_response(HttpResponse::BadRequest(), message),
+    };
+    let mut answer = game.clone();
+    if let Err(error) = answer.make_move_uci(&request.r#move) {
+        return error_response(HttpResponse::BadRequest(), error.to_string());
+    }
+
+    match web::block(move || judge_puzzle(&game, &answer)).await {
+        Ok(Some(response)) => HttpResponse::Ok().json(response),
+        Ok(None) => error_response(
+            HttpResponse::UnprocessableEntity(),
+            "the position has no legal moves".to_string(),
+        ),
+        Err(error) => error_response(HttpResponse::InternalServerError(), error.to_string()),
+    }
+}
+
+// Body of `POST /positions`, sent by the "set up position" editor: every
+// piece keyed by its square, e.g. `{"e1": "K", "e8": "k"}`, plus the rest of
+// the position. Castling is written as in FEN ("KQkq", or "-" for none).
+#[derive(Deserialize, Debug)]
+pub struct PositionRequest {
+    pub pieces: BTreeMap<Square, Piece>,
+    pub side_to_move: Option<Side>,
+    pub castling: Option<String>,
+    pub en_passant: Option<Square>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PositionResponse {
+    pub fen: String,
+}
+
+// Turns an edited position into FEN, or explains with a 400 why it can't be
+// played from. The FEN can then be used to start a game with `POST /games`.
+pub async fn build_position(body: web::Json<PositionRequest>) -> impl Responder {
+    let request = body.into_inner();
+    let castling_text = request.castling.as_deref().unwrap_or("-");
+    let Some(castling) = CastlingRights::from_fen(castling_text) else {
+        return error_response(
+            HttpResponse::BadRequest(),
+            format!("invalid castling rights '{}'", castling_text),
+        );
+    };
+
+    let mut builder = BoardBuilder::new();
+    for (square, piece) in request.pieces {
+        builder.place(square, piece);
+    }
+    builder
+        .side_to_move(request.side_to_move.map_or(PieceColor::White, PieceColor::from))
+        .castling_rights(castling)
+        .en_passant(request.en_passant);
+
+    match builder.build() {
+        Ok(board) => HttpResponse::Ok().json(PositionResponse { fen: board.to_fen() }),
+        Err(error) => error_response(HttpResponse::BadRequest(), error.to_string()),
+    }
+}
@@ -6,8 +6,26 @@
 //! - WebSocket upgrade route
 
 use actix_web::web;
+use crate::network::websocket;
 use crate::web::handlers;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    // TODO: Define routes
+    cfg.route("/", web::get().to(handlers::index))
+        .route("/ws", web::get().to(websocket::websocket_route))
+        .route("/games", web::post().to(handlers::create_game))
+        .route("/games/import", web::post().to(handlers::import_pgn))
+        .route("/games/{id}", web::get().to(handlers::get_game))
+        .route("/games/{id}/reset", web::post().to(handlers::reset_game))
+        .route("/games/{id}/result", web::get().to(handlers::get_result))
+        .route("/games/{id}/attacks", web::get().to(handlers::get_attacks))
+        .route("/games/{id}/moves/{square}", web::get().to(handlers::get_square_moves))
+        .route("/games/{id}/timeline", web::get().to(handlers::get_timeline))
+        .route("/games/{id}/pgn", web::get().to(handlers::get_pgn))
+        .route("/games/{id}/hint", web::get().to(handlers::get_hint))
+        .route("/games/{id}/analysis", web::get().to(handlers::get_analysis))
+        .route("/analyze", web::post().to(handlers::analyze))
+        .route("/puzzles/check", web::post().to(handlers::check_puzzle))
+        .route("/tactics", web::post().to(handlers::find_tactics))
+        .route("/positions", web::post().to(handlers::build_position))
+        .route("/metrics", web::get().to(handlers::metrics));
 }
@@ -7,10 +7,3295 @@
 
 #[cfg(test)]
 mod tests {
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use actix_web::{web, App};
+    use serde_json::json;
+
+    use chess_game::chess::{
+        bishop_attacks, king_attacks, knight_attacks, normalize_fen, pawn_attacks, perft, perft_divide, rook_attacks, BitBoards, Bitboard, Board, BoardBuilder, BoardRepr, CastlingRights, KING_ATTACKS, KNIGHT_ATTACKS, Clock, DrawClaim, Epd, EpdError, FenError, Game, GameEvent, GameError, GameMode, GameResult, GameStatus, Termination, Move, MoveDetail, MoveError, MoveFlag, PawnStructure, PgnError, PgnErrorKind, PgnGame, Piece, GameTree, Openings, Variant, variant_by_name, standard_game_end, Antichess, Atomic, Horde, KingOfTheHill, ThreeCheck, PieceColor,
+        PieceKind, InvalidPieceByte, InvalidPieceChar, Motif, PositionError, Square, START_FEN,
+    };
+    use chess_game::cli::arg_value;
+    use chess_game::engine::eval::{evaluate, game_phase, MAX_PHASE};
+    use chess_game::engine::{find_mate, polyglot_hash, Book, BookError, Bound, Engine, EngineOptions, MoveCache, MoveOrdering, SearchLimits, TranspositionTable};
+    use chess_game::logging::{choose_level, parse_level, DEFAULT_LEVEL};
+    use chess_game::network::store::{GameRecord, GameStore, Metrics};
+    use chess_game::network::websocket::{client_move, ClientMessage, ErrorCode, GameState, Promotion, ServerMessage};
+    use chess_game::web::handlers::{AnalyzeResponse, AttacksResponse, HintResponse, MoveTiming, PositionResponse, PuzzleCheckResponse, MAX_ANALYZE_DEPTH};
+    use chess_game::web::routes;
+
+    // Plays a sequence of UCI moves, panicking on the first illegal one.
+    fn play(game: &mut Game, moves: &[&str]) {
+        for uci in moves {
+            game.make_move_uci(uci).unwrap_or_else(|e| panic!("{}: {}", uci, e));
+        }
+    }
+
+    // Creating a bot game starts the bot's turn in the background. Letting
+    // that task run first keeps it from racing a test's own `play_bot_turn`.
+    async fn let_bot_start() {
+        actix_web::rt::task::yield_now().await;
+    }
+
     #[test]
     fn test_game_initialization() {
-        // TODO: Implement game initialization test
+        let game = Game::new();
+        assert_eq!(game.board().to_fen(), START_FEN);
+        assert_eq!(game.side_to_move(), PieceColor::White);
+        assert_eq!(game.legal_moves().len(), 20);
+        assert!(game.history().is_empty());
+        assert!(game.result().is_none());
+    }
+
+    #[test]
+    fn test_reset_restores_start_position() {
+        let mut game = Game::new();
+        game.set_mode(GameMode::Analysis);
+        play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(game.history().len(), 3);
+
+        game.request_reset(PieceColor::White).unwrap();
+        assert_eq!(game.board().to_fen(), START_FEN);
+        assert!(game.history().is_empty());
+        assert!(game.result().is_none());
+    }
+
+    #[test]
+    fn test_reset_mid_game_needs_both_players() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5"]);
+
+        assert!(game.request_reset(PieceColor::White).is_err());
+        assert_eq!(game.history().len(), 2);
+
+        game.request_reset(PieceColor::Black).unwrap();
+        assert_eq!(game.board().to_fen(), START_FEN);
+        assert!(game.history().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_reset_endpoint() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(
+            App::new().app_data(store.clone()).configure(routes::config),
+        )
+        .await;
+
+        let req = TestRequest::post().uri("/games").to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        store.with_game(created.id, |game| play(game, &["d2d4", "d7d5"]));
+
+        // One player alone can't reset a game in progress.
+        let req = TestRequest::post()
+            .uri(&format!("/games/{}/reset", created.id))
+            .set_json(json!({ "color": "white" }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 409);
+
+        let req = TestRequest::post()
+            .uri(&format!("/games/{}/reset", created.id))
+            .set_json(json!({ "color": "black" }))
+            .to_request();
+        let state: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(state.fen, START_FEN);
+        assert!(state.moves.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_bot_replies_to_e4() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        // A short clock keeps the bot's thinking time (and this test) brief.
+        let req = TestRequest::post()
+            .uri("/games?bot=hard")
+            .set_json(json!({ "initial_seconds": 6 }))
+            .to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let_bot_start().await;
+
+        store.with_game(created.id, |game| play(game, &["e2e4"]));
+        let state = store.play_bot_turn(created.id).await.expect("bot should move");
+        assert_eq!(state.moves.len(), 2);
+        assert_eq!(state.turn, "white");
+
+        let mut after_e4 = Game::new();
+        play(&mut after_e4, &["e2e4"]);
+        let reply = Move::from_uci(&state.moves[1]).unwrap();
+        assert!(after_e4.legal_moves().iter().any(|mv| mv.same_squares(&reply)));
+    }
+
+    #[actix_web::test]
+    async fn test_bot_moves_first_when_on_move() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        // Black to move, and the bot plays Black: nobody else will move, so
+        // the bot has to start by itself.
+        let req = TestRequest::post()
+            .uri("/games?bot=easy")
+            .set_json(json!({ "fen": "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1" }))
+            .to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let started = Instant::now();
+        while store.state(created.id).unwrap().moves.is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(10), "the bot never moved");
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        let state = store.state(created.id).unwrap();
+        assert_eq!(state.moves.len(), 1);
+        assert_eq!(state.turn, "white");
+    }
+
+    #[actix_web::test]
+    async fn test_pondering() {
+        use chess_game::engine::Ponder;
+
+        let board = Board::new();
+        let legal = |board: &Board, text: &str| board.legal_moves().into_iter().find(|mv| mv.same_squares(&uci(text))).unwrap();
+        let e4 = legal(&board, "e2e4");
+        assert!(Ponder::start(&board, uci("e2e5"), Some(2)).is_none());
+
+        // A miss throws the search away; a hit hands it over.
+        let ponder = Ponder::start(&board, e4, Some(2)).unwrap();
+        assert_eq!(ponder.expected(), e4);
+        assert_eq!(ponder.finish(legal(&board, "d2d4"), None), None);
+        let result = Ponder::start(&board, e4, Some(2)).unwrap().finish(e4, None).unwrap();
+        assert_eq!(result.depth, 2);
+        let mut after = board.clone();
+        after.make_move(e4);
+        assert!(after.legal_moves().contains(&result.best_move));
+
+        // Without a depth it thinks until its budget runs out.
+        let started = Instant::now();
+        let result = Ponder::start(&board, e4, None).unwrap().finish(e4, Some(Duration::from_millis(200))).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(result.depth >= 1);
+
+        // In a game, whether the guess comes true or not, the bot moves.
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::post().uri("/games?bot=medium&ponder=true").to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let_bot_start().await;
+        for reply in ["e2e4", "d2d4"] {
+            store.with_game(created.id, |game| play(game, &[reply]));
+            store.play_bot_turn(created.id).await.expect("bot should move");
+        }
+        assert_eq!(store.state(created.id).unwrap().moves.len(), 4);
+        assert!(store.set_ponder(created.id, false));
+        assert!(!store.set_ponder(999, true));
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_bot_level_is_rejected() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::post().uri("/games?bot=grandmaster").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let req = TestRequest::post().uri("/games?bot=3000").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn test_strength_limited_bot() {
+        use chess_game::engine::{BotLevel, Strength};
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::post().uri("/games?bot=1200").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 201);
+
+        let level: BotLevel = "1200".parse().unwrap();
+        assert_eq!(level, BotLevel::Elo(Strength::new(1200)));
+        assert_eq!(level.to_string(), "1200");
+        assert_eq!(Strength::new(100).elo(), 800);
+
+        // Weaker play looks less far ahead and is noisier and more random.
+        let (weak, strong) = (Strength::new(800), Strength::new(2400));
+        assert_eq!((weak.depth(), strong.depth()), (2, 6));
+        assert_eq!((weak.eval_noise(), strong.eval_noise()), (200, 0));
+        assert_eq!((weak.randomness(), strong.randomness()), (20, 0));
+        assert_eq!((weak.candidate_window(), strong.candidate_window()), (100, 0));
+
+        // The noise varies the moves from the start, but even at 800 a free
+        // queen is never left on the board.
+        let mut engine = Engine::new();
+        let mut rng = StdRng::seed_from_u64(3);
+        let start = Board::new();
+        let openings: std::collections::HashSet<Move> =
+            (0..8).map(|_| weak.choose_move(&mut engine, &start, None, &mut rng).unwrap()).collect();
+        assert!(openings.len() > 1);
+        let hanging = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        for _ in 0..5 {
+            assert_eq!(weak.choose_move(&mut engine, &hanging, None, &mut rng), Some(uci("d2d5")));
+        }
+
+        // Out of time, a move still comes back: the capture, scored first.
+        for strength in [weak, Strength::new(1600), strong] {
+            let chosen = strength.choose_move(&mut engine, &hanging, Some(Duration::ZERO), &mut rng).unwrap();
+            assert!(hanging.legal_moves().contains(&chosen));
+        }
+        assert_eq!(
+            Strength::new(1600).choose_move(&mut engine, &hanging, Some(Duration::ZERO), &mut rng),
+            Some(uci("d2d5"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_elo_bot_respects_its_clock() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        // Searching every reply four plies deep would take far longer than
+        // the ~30ms a one-second clock allows per move.
+        let req = TestRequest::post()
+            .uri("/games?bot=2000")
+            .set_json(json!({ "initial_seconds": 1 }))
+            .to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let_bot_start().await;
+
+        store.with_game(created.id, |game| play(game, &["e2e4"]));
+        let started = Instant::now();
+        let state = store.play_bot_turn(created.id).await.expect("bot should move");
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(state.moves.len(), 2);
+        assert_eq!(state.result, None);
+        assert!(state.black_time_ms.unwrap() > 500);
+    }
+
+    #[test]
+    fn test_normalize_fen_equivalent_forms() {
+        let canonical = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let variants = [
+            canonical,
+            "  r3k2r/8/8/8/8/8/8/R3K2R   w  KQkq  -  0  1 ",
+            "r3k2r/8/8/8/8/8/8/R3K2R w qkQK - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R\tw kKqQ -\n0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq -",
+        ];
+        for fen in variants {
+            assert_eq!(normalize_fen(fen).unwrap(), canonical, "{:?}", fen);
+        }
+    }
+
+    #[test]
+    fn test_normalize_fen_rejects_garbage() {
+        assert!(matches!(normalize_fen("not a fen"), Err(FenError::WrongFieldCount(3))));
+        assert!(matches!(
+            normalize_fen("8/8/8/8/8/8/8/9 w - - 0 1"),
+            Err(FenError::InvalidPiecePlacement(_))
+        ));
+    }
+
+    fn uci(text: &str) -> Move {
+        Move::from_uci(text).expect("valid UCI")
+    }
+
+    #[test]
+    fn test_explain_illegal_leaves_king_in_check() {
+        // The e2 knight is pinned against the king by the e8 rook.
+        let game = Game::from_fen("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.explain_illegal(&uci("e2c3")), Some(MoveError::LeavesKingInCheck));
+        assert_eq!(game.explain_illegal(&uci("e1d1")), None);
+    }
+
+    #[test]
+    fn test_explain_illegal_wrong_side() {
+        let mut game = Game::new();
+        assert_eq!(game.explain_illegal(&uci("e7e5")), Some(MoveError::NotYourTurn));
+        assert_eq!(game.make_move(uci("e7e5")), Err(MoveError::NotYourTurn));
+    }
+
+    #[test]
+    fn test_explain_illegal_other_reasons() {
+        let game = Game::new();
+        let empty = uci("e4e5").from();
+        assert_eq!(game.explain_illegal(&uci("e4e5")), Some(MoveError::NoPieceOnSquare(empty)));
+        assert_eq!(game.explain_illegal(&uci("f1c4")), Some(MoveError::BlockedPath));
+        assert_eq!(game.explain_illegal(&uci("g1g3")), Some(MoveError::PieceCannotReach));
+        assert_eq!(game.explain_illegal(&uci("d1d2")), Some(MoveError::OccupiedByOwnPiece));
+        assert_eq!(game.explain_illegal(&uci("e2e4")), None);
+
+        // The black rook covers f1, so White can't castle through it.
+        let game = Game::from_fen("4k3/8/8/8/8/8/5r2/4K2R w K - 0 1").unwrap();
+        assert_eq!(game.explain_illegal(&uci("e1g1")), Some(MoveError::CastlingNotAllowed));
+    }
+
+    fn square(name: &str) -> Square {
+        Square::from_algebraic(name).expect("valid square")
+    }
+
+    #[test]
+    fn test_flip_horizontal_twice_is_identity() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w - f6 0 3",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.flip_horizontal().flip_horizontal().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_flip_horizontal_moves_a1_to_h1() {
+        let mut board = Board::empty();
+        let rook = Piece::new(PieceKind::Rook, PieceColor::White);
+        board.put_piece(square("a1"), rook);
+        let flipped = board.flip_horizontal();
+        assert!(flipped.piece_at(square("a1")).is_none());
+        assert!(flipped.piece_at(square("h1")) == Some(rook));
+
+        let start = Board::new().flip_horizontal();
+        assert_eq!(start.to_fen(), "rnbkqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKQBNR w - - 0 1");
+    }
+
+    #[actix_web::test]
+    async fn test_result_survives_game_removal() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::post().uri("/games").to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let uri = format!("/games/{}/result", created.id);
+
+        // No result while the game is still going.
+        store.with_game(created.id, |game| play(game, &["f2f3", "e7e5"]));
+        let resp = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(resp.status(), 404);
+
+        // Fool's mate finishes the game, which archives it.
+        store.with_game(created.id, |game| play(game, &["g2g4", "d8h4"]));
+        assert!(store.remove(created.id));
+        assert!(store.state(created.id).is_none());
+
+        let record: GameRecord = call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(record.result, "0-1");
+        assert_eq!(record.moves, ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(record.final_fen, "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    }
+
+    #[test]
+    fn test_san_disambiguation_and_suffixes() {
+        // Knights on b1 and f1 can both reach d2; rooks on a1 and a5 both reach a3.
+        let board = Board::from_fen("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1").unwrap();
+        assert_eq!(uci("b1d2").to_san(&board), "Nbd2");
+        assert_eq!(uci("a1a3").to_san(&board), "R1a3");
+        assert_eq!(uci("a5a8").to_san(&board), "Ra8+");
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(uci("e1g1").to_san(&board), "O-O");
+        assert_eq!(uci("b7b8q").to_san(&board), "b8=Q+");
+    }
+
+    #[test]
+    fn test_move_times_follow_the_clock() {
+        let mut game = Game::new();
+        game.set_clock(Some(Clock::new(Duration::from_secs(300), Duration::ZERO)));
+
+        let start = Instant::now();
+        game.make_move_at(uci("e2e4"), start).unwrap();
+        game.make_move_at(uci("e7e5"), start + Duration::from_millis(3000)).unwrap();
+        game.make_move_at(uci("g1f3"), start + Duration::from_millis(4500)).unwrap();
+        game.make_move_at(uci("b8c6"), start + Duration::from_millis(4750)).unwrap();
+
+        assert_eq!(game.move_times(), [Some(0), Some(3000), Some(1500), Some(250)]);
+        let black_left = game.clock().unwrap().remaining(PieceColor::Black);
+        assert_eq!(black_left, Duration::from_millis(300_000 - 3250));
+    }
+
+    #[test]
+    fn test_move_times_without_clock() {
+        let mut game = Game::new();
+        play(&mut game, &["d2d4", "d7d5"]);
+        assert_eq!(game.move_times(), [None, None]);
+    }
+
+    #[actix_web::test]
+    async fn test_analyze_finds_back_rank_mate() {
+        let app = init_service(App::new().configure(routes::config)).await;
+        let req = TestRequest::post()
+            .uri("/analyze")
+            .set_json(json!({ "fen": "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "depth": 3 }))
+            .to_request();
+        let analysis: AnalyzeResponse = call_and_read_body_json(&app, req).await;
+        assert_eq!(analysis.best_move, "d1d8");
+        assert_eq!(analysis.san, "Rd8#");
+        assert!(analysis.score > 10_000);
+        assert_eq!(analysis.pv, ["d1d8"]);
+    }
+
+    #[actix_web::test]
+    async fn test_hints() {
+        use chess_game::engine::hint;
+
+        let mut engine = Engine::new();
+        let mate = hint(&mut engine, &Board::from_fen("6k1/5ppp/8/8/8/8/8/R6K w - - 0 1").unwrap()).unwrap();
+        assert_eq!((mate.san.as_str(), mate.explanation.as_str()), ("Ra8#", "delivers checkmate"));
+        let capture = hint(&mut engine, &Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap()).unwrap();
+        assert_eq!((capture.san.as_str(), capture.explanation.as_str()), ("Rxd5", "wins material"));
+        assert_eq!(hint(&mut engine, &Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap()), None);
+
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::post().uri("/games").to_request();
+        let casual: GameState = call_and_read_body_json(&app, req).await;
+        let req = TestRequest::get().uri(&format!("/games/{}/hint", casual.id)).to_request();
+        let suggestion: HintResponse = call_and_read_body_json(&app, req).await;
+        let mv = Move::from_uci(&suggestion.uci).unwrap();
+        assert!(Board::new().legal_moves().iter().any(|legal| legal.same_squares(&mv)));
+        assert!(!suggestion.explanation.is_empty());
+
+        let req = TestRequest::post().uri("/games").set_json(json!({ "rated": true })).to_request();
+        let rated: GameState = call_and_read_body_json(&app, req).await;
+        let req = TestRequest::get().uri(&format!("/games/{}/hint", rated.id)).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 403);
+        let req = TestRequest::get().uri("/games/999/hint").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404);
+
+        let message: ClientMessage = serde_json::from_value(json!({ "type": "hint", "game_id": 3 })).unwrap();
+        assert!(matches!(message, ClientMessage::Hint { game_id: 3 }));
+    }
+
+    #[actix_web::test]
+    async fn test_game_analysis() {
+        use chess_game::engine::{analyze_game, MoveClass};
+
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let analysis = analyze_game(&game, 2);
+        assert_eq!(analysis.moves.len(), 4);
+        let (blunder, mate) = (&analysis.moves[2], &analysis.moves[3]);
+        assert_eq!((blunder.san.as_str(), blunder.class), ("g4", MoveClass::Blunder));
+        assert_eq!(blunder.color, PieceColor::White);
+        assert_eq!((mate.best_move.as_deref(), mate.loss, mate.class), (Some("Qh4#"), 0, MoveClass::Good));
+        assert!(mate.eval_after < -10_000);
+        assert!(analysis.white.blunders >= 1);
+        assert!(analysis.white.accuracy < analysis.black.accuracy);
+        assert!((0.0..=100.0).contains(&analysis.white.accuracy));
+
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::post().uri("/games").to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let uri = format!("/games/{}/analysis?depth=2", created.id);
+        store.with_game(created.id, |game| play(game, &["f2f3", "e7e5", "g2g4"]));
+        let req = TestRequest::get().uri(&uri).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 409);
+
+        store.with_game(created.id, |game| play(game, &["d8h4"]));
+        let req = TestRequest::get().uri(&uri).to_request();
+        let report: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(report["depth"], 2);
+        assert_eq!(report["moves"][2]["class"], "blunder");
+        assert_eq!(report["moves"][3]["color"], "black");
+        assert!(report["white"]["accuracy"].is_number());
+    }
+
+    #[actix_web::test]
+    async fn test_tactical_motifs() {
+        let motifs = |fen: &str| Board::from_fen(fen).unwrap().tactical_motifs();
+        assert_eq!(motifs(START_FEN), []);
+
+        // The knight forks king and rook; the rook has nothing guarding it.
+        assert_eq!(
+            motifs("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1"),
+            [
+                Motif::Fork { side: PieceColor::White, attacker: square("c7"), targets: vec![square("a8"), square("e8")] },
+                Motif::HangingPiece { side: PieceColor::White, square: square("a8") },
+            ]
+        );
+        assert!(motifs("4k3/8/2n5/1B6/8/8/8/4K3 w - - 0 1").contains(&Motif::Pin {
+            side: PieceColor::White,
+            pinner: square("b5"),
+            pinned: square("c6"),
+            behind: square("e8"),
+        }));
+        assert!(motifs("8/8/8/8/R3k2q/8/8/4K3 b - - 0 1").contains(&Motif::Skewer {
+            side: PieceColor::White,
+            attacker: square("a4"),
+            front: square("e4"),
+            behind: square("h4"),
+        }));
+        // Moving the knight uncovers the rook's attack on the queen, which in
+        // turn pins the knight to the rook.
+        let discovered = motifs("4k3/4q3/8/8/4N3/8/8/K3R3 w - - 0 1");
+        assert!(discovered.contains(&Motif::DiscoveredAttack {
+            side: PieceColor::White,
+            blocker: square("e4"),
+            slider: square("e1"),
+            target: square("e7"),
+        }));
+        assert!(discovered.contains(&Motif::Pin {
+            side: PieceColor::Black,
+            pinner: square("e7"),
+            pinned: square("e4"),
+            behind: square("e1"),
+        }));
+        // Black's king is boxed in by its own pawns; White's is too, but Black
+        // has no rook to use it.
+        assert_eq!(
+            motifs("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1"),
+            [Motif::BackRankWeakness { side: PieceColor::White, king: square("g8") }]
+        );
+        // A rook on the back rank guards it.
+        assert_eq!(motifs("r5k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1"), []);
+        assert_eq!(
+            motifs("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1"),
+            [Motif::HangingPiece { side: PieceColor::White, square: square("d5") }]
+        );
+
+        let app = init_service(App::new().configure(routes::config)).await;
+        let req = TestRequest::post()
+            .uri("/tactics")
+            .set_json(json!({ "fen": "r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1" }))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            body["motifs"][0],
+            json!({ "type": "fork", "side": "white", "attacker": "c7", "targets": ["a8", "e8"] })
+        );
+        let req = TestRequest::post().uri("/tactics").set_json(json!({ "fen": "not a position" })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+    }
+
+    #[test]
+    fn test_engine_search_limits_and_pv() {
+        let mut engine = Engine::new();
+        let board = Board::new();
+        let result = engine.search(&board, SearchLimits::depth(3)).unwrap();
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.pv.len(), 3);
+        assert_eq!(result.pv[0], result.best_move);
+        // The line can be played out.
+        let mut line = board.clone();
+        for mv in &result.pv {
+            assert!(line.legal_moves().contains(mv), "{} in {:?}", mv, result.pv);
+            line.make_move(*mv);
+        }
+
+        // A node limit stops deepening, but depth 1 always finishes.
+        let limits = SearchLimits { nodes: Some(100), ..SearchLimits::default() };
+        let limited = Engine::new().search(&board, limits).unwrap();
+        assert!(limited.depth >= 1 && limited.depth < result.depth);
+        assert!(limited.nodes < result.nodes);
+
+        // Nothing to search when the game is over.
+        let mated = Board::from_fen("3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1").unwrap();
+        assert!(engine.search(&mated, SearchLimits::depth(2)).is_none());
+    }
+
+    #[test]
+    fn test_null_move_pruning_and_late_move_reductions() {
+        let plain = EngineOptions { null_move_pruning: false, late_move_reductions: false, ..EngineOptions::default() };
+        let search = |options: EngineOptions, fen: &str, depth: u32| {
+            let board = Board::from_fen(fen).unwrap();
+            Engine::with_options(options).search(&board, SearchLimits::depth(depth)).unwrap()
+        };
+
+        // Both prune a middlegame search hard.
+        let middlegame = "r4rk1/pp3ppp/2n5/3p4/3P4/2N5/PP3PPP/R4RK1 w - - 0 15";
+        let pruned = search(EngineOptions::default(), middlegame, 4);
+        let full = search(plain, middlegame, 4);
+        assert!(pruned.nodes < full.nodes, "{} vs {}", pruned.nodes, full.nodes);
+        for options in [
+            EngineOptions { null_move_pruning: false, ..EngineOptions::default() },
+            EngineOptions { late_move_reductions: false, ..EngineOptions::default() },
+        ] {
+            assert!(search(options, middlegame, 4).nodes < full.nodes);
+        }
+
+        // But still find a mate in two.
+        let mate_in_two = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 10";
+        for options in [EngineOptions::default(), plain] {
+            let result = search(options, mate_in_two, 3);
+            assert_eq!(result.best_move.to_uci(), "d5f6");
+            assert!(result.score > 10_000);
+        }
+    }
+
+    #[test]
+    fn test_polyglot_book() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        // The reference hashes from the Polyglot format's description.
+        let hash = |fen: &str| polyglot_hash(&Board::from_fen(fen).unwrap());
+        let mut game = Game::new();
+        assert_eq!(polyglot_hash(game.board()), 0x463b96181691fc9c);
+        for (mv, expected) in [
+            ("e2e4", 0x823c9b50fd114196),
+            ("d7d5", 0x0756b94461c50fb0),
+            ("e4e5", 0x662fafb965db29d4),
+            ("f7f5", 0x22a48b5a8e47ff78),
+            ("e1e2", 0x652a607ca3f242c1),
+            ("e8f7", 0x00fdd303c946bdd9),
+        ] {
+            play(&mut game, &[mv]);
+            assert_eq!(polyglot_hash(game.board()), expected, "after {}", mv);
+        }
+        let mut game = Game::new();
+        play(&mut game, &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"]);
+        assert_eq!(polyglot_hash(game.board()), 0x3c8123ea7b067637);
+        play(&mut game, &["b4c3", "a1a3"]);
+        assert_eq!(polyglot_hash(game.board()), 0x5c3f9b829b279560);
+
+        // The en passant file only counts when a pawn can take.
+        assert_eq!(
+            hash("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            hash("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        assert_ne!(
+            hash("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3"),
+            hash("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3")
+        );
+        assert_ne!(hash(START_FEN), hash("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"));
+
+        // Polyglot moves are to-square | from-square << 6, and castling is
+        // written as the king taking its rook.
+        let entry = |fen: &str, from: &str, to: &str, weight: u16| {
+            let mut bytes = hash(fen).to_be_bytes().to_vec();
+            let raw = (square(to).index() | square(from).index() << 6) as u16;
+            bytes.extend(raw.to_be_bytes());
+            bytes.extend(weight.to_be_bytes());
+            bytes.extend([0; 4]);
+            bytes
+        };
+        let castling_fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let bytes = [
+            entry(START_FEN, "e2", "e4", 3),
+            entry(castling_fen, "e1", "h1", 1),
+            entry(START_FEN, "d2", "d4", 1),
+            entry(START_FEN, "c2", "c4", 0),
+        ]
+        .concat();
+        assert_eq!(Book::from_bytes(&bytes[..20]).err(), Some(BookError::BookSize(20)));
+        let book = Book::from_bytes(&bytes).unwrap().with_depth(2);
+        assert_eq!(book.len(), 4);
+
+        let start = Board::new();
+        let moves: Vec<(String, u16)> = book.moves(&start).iter().map(|book| (book.mv.to_uci(), book.weight)).collect();
+        assert_eq!(moves, [("e2e4".to_string(), 3), ("d2d4".to_string(), 1), ("c2c4".to_string(), 0)]);
+        let castle = book.moves(&Board::from_fen(castling_fen).unwrap());
+        assert_eq!(castle[0].mv.to_uci(), "e1g1");
+        assert_eq!(castle[0].mv.flag(), MoveFlag::CastleKingside);
+        assert!(book.moves(&Board::from_fen("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap()).is_empty());
+
+        // Weighted: e4 about three times as often as d4, c4 never.
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..400 {
+            *counts.entry(book.choose(&start, &mut rng).unwrap().to_uci()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get("c2c4"), None);
+        assert!(counts["e2e4"] > 2 * counts["d2d4"] && counts["d2d4"] > 50, "{:?}", counts);
+
+        // Past the book's depth the bot is on its own.
+        let mut game = Game::new();
+        assert!(book.choose_for_game(&game, &mut rng).is_some());
+        play(&mut game, &["e2e4", "e7e5"]);
+        assert_eq!(book.choose_for_game(&game, &mut rng), None);
+    }
+
+    #[test]
+    fn test_uci_protocol() {
+        use chess_game::engine::uci::{go_limits, run};
+
+        let limits = go_limits(&["wtime", "60000", "btime", "1000", "winc", "2000"], PieceColor::White);
+        assert_eq!(limits.time, Some(Duration::from_millis(3000)));
+        let limits = go_limits(&["wtime", "60000", "btime", "1000", "movestogo", "1"], PieceColor::Black);
+        assert_eq!(limits.time, Some(Duration::from_millis(950)));
+        assert_eq!(go_limits(&["depth", "5", "nodes", "900"], PieceColor::White), SearchLimits { depth: Some(5), nodes: Some(900), time: None });
+        assert_eq!(go_limits(&["movetime", "250", "wtime", "60000"], PieceColor::White).time, Some(Duration::from_millis(250)));
+        assert_eq!(go_limits(&["infinite"], PieceColor::White), SearchLimits::default());
+
+        let input = "uci\nisready\nsetoption name Hash value 1\nucinewgame\n\
+                     position startpos moves e2e4 e7e5\ngo depth 2\n\
+                     position fen 6k1/5ppp/8/8/8/8/8/R6K w - - 0 1\ngo depth 3\nquit\n";
+        let output = String::from_utf8(run(input.as_bytes(), Vec::new())).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("id name"));
+        assert!(lines.contains(&"option name Hash type spin default 16 min 1 max 1024"));
+        assert!(lines.contains(&"uciok") && lines.contains(&"readyok"));
+
+        let best: Vec<&str> = lines.iter().filter(|line| line.starts_with("bestmove")).copied().collect();
+        assert_eq!(best.len(), 2);
+        let mut board = Board::new();
+        for mv in ["e2e4", "e7e5"] {
+            board.make_move(uci(mv));
+        }
+        let first = Move::from_uci(best[0].split_whitespace().nth(1).unwrap()).unwrap();
+        assert!(board.legal_moves().iter().any(|mv| mv.same_squares(&first)), "{}", best[0]);
+        assert_eq!(best[1], "bestmove a1a8");
+        assert!(lines.iter().any(|line| line.starts_with("info depth 1 score mate 1 ") && line.ends_with("pv a1a8")), "{}", output);
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        // White can take the queen with the knight or the queen, or make
+        // any number of quiet moves.
+        let board = Board::from_fen("4k3/8/3q4/8/2N1p3/8/8/3QK3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+        let order = |ordering: &MoveOrdering, hash_move: Option<Move>| {
+            let mut moves = board.legal_moves();
+            ordering.order(&board, &mut moves, hash_move, 2);
+            moves.iter().map(Move::to_uci).collect::<Vec<_>>()
+        };
+
+        // Captures first, the least valuable attacker first.
+        let moves = order(&ordering, None);
+        assert_eq!(moves[..2], ["c4d6", "d1d6"]);
+
+        // The hash move beats everything.
+        assert_eq!(order(&ordering, Some(uci("e1f2")))[0], "e1f2");
+
+        // A quiet move that caused a cutoff at this ply comes straight after
+        // the captures; one that did so elsewhere only gains history.
+        let captures = board.legal_moves().iter().filter(|mv| board.piece_at(mv.to()).is_some()).count();
+        ordering.record_cutoff(&board, uci("d1a4"), &[uci("d1b3")], 5, 2);
+        assert_eq!(ordering.killers(2), [Some(uci("d1a4")), None]);
+        assert_eq!(order(&ordering, None)[captures], "d1a4");
+        ordering.record_cutoff(&board, uci("c4b6"), &[], 3, 7);
+        assert!(ordering.history(&board, &uci("c4b6")) > 0);
+        assert!(ordering.history(&board, &uci("d1b3")) < 0);
+        let moves = order(&ordering, None);
+        let rank = |uci: &str| moves.iter().position(|mv| mv == uci).unwrap();
+        assert!(rank("c4b6") < rank("c4e3") && rank("d1b3") > rank("c4e3"));
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.capacity().is_power_of_two());
+        let e4 = uci("e2e4");
+        table.store(42, 0, 5, 30, Bound::Exact, Some(e4));
+        let entry = table.probe(42, 0).unwrap();
+        assert_eq!((entry.best_move, entry.score, entry.depth, entry.bound), (Some(e4), 30, 5, Bound::Exact));
+        assert!(table.probe(43, 0).is_none());
+        // Exact scores cut off at any window, bounds only on their side of it.
+        assert_eq!(entry.cutoff(5, -100, 100), Some(30));
+        assert_eq!(entry.cutoff(6, -100, 100), None);
+        table.store(42, 0, 5, 30, Bound::Lower, None);
+        let entry = table.probe(42, 0).unwrap();
+        assert_eq!(entry.cutoff(5, 0, 20), Some(30));
+        assert_eq!(entry.cutoff(5, 0, 50), None);
+        // A fail-low result keeps the move that was there.
+        assert_eq!(entry.best_move, Some(e4));
+
+        // Another position in the same slot doesn't push out a deeper entry
+        // from the same search, but does once that search is over.
+        let clash = 42 + table.capacity() as u64;
+        table.store(clash, 0, 2, 0, Bound::Exact, None);
+        assert!(table.probe(42, 0).is_some() && table.probe(clash, 0).is_none());
+        table.new_search();
+        table.store(clash, 0, 2, 0, Bound::Exact, None);
+        assert!(table.probe(42, 0).is_none() && table.probe(clash, 0).is_some());
+
+        // A mate stored 3 plies down is mate in fewer plies from there.
+        table.store(7, 3, 4, 30_000 - 5, Bound::Exact, None);
+        assert_eq!(table.probe(7, 1).unwrap().score, 30_000 - 3);
+
+        // Searching the same position again is cheaper with the table filled.
+        let mut engine = Engine::with_options(EngineOptions { hash_size_mb: 1, ..EngineOptions::default() });
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let first = engine.search(&board, SearchLimits::depth(3)).unwrap();
+        assert!(engine.transposition_table().hashfull() > 0);
+        let second = engine.search(&board, SearchLimits::depth(3)).unwrap();
+        assert!(second.nodes < first.nodes);
+        assert_eq!(second.best_move, first.best_move);
+    }
+
+    #[actix_web::test]
+    async fn test_analyze_validates_fen_and_caps_depth() {
+        let app = init_service(App::new().configure(routes::config)).await;
+        let req = TestRequest::post()
+            .uri("/analyze")
+            .set_json(json!({ "fen": "rnbqkbnr/pppppppp w KQkq - 0 1" }))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+
+        // A lone-king endgame is cheap to search, so the cap is what stops it.
+        let req = TestRequest::post()
+            .uri("/analyze")
+            .set_json(json!({ "fen": "8/8/8/4k3/8/8/8/4K3 w - - 0 1", "depth": 50 }))
+            .to_request();
+        let analysis: AnalyzeResponse = call_and_read_body_json(&app, req).await;
+        assert_eq!(analysis.depth, MAX_ANALYZE_DEPTH);
+    }
+
+    #[test]
+    fn test_ply_counts_half_moves() {
+        let mut game = Game::new();
+        assert_eq!(game.ply(), 0);
+        play(&mut game, &["e2e4", "c7c5", "g1f3"]);
+        assert_eq!(game.ply(), 3);
+
+        let sans: Vec<&str> = (0..game.ply()).map(|ply| game.move_at_ply(ply).unwrap().san()).collect();
+        assert_eq!(sans, ["e4", "c5", "Nf3"]);
+        assert!(game.move_at_ply(3).is_none());
+    }
+
+    #[test]
+    fn test_position_at_ply_replays_history() {
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+
+        let start = game.position_at_ply(0).unwrap();
+        assert_eq!(start.board().to_fen(), START_FEN);
+        assert_eq!(start.ply(), 0);
+        assert!(start.result().is_none());
+
+        let end = game.position_at_ply(4).unwrap();
+        assert_eq!(end.board().to_fen(), game.board().to_fen());
+        assert_eq!(end.result(), game.result());
+
+        let middle = game.position_at_ply(2).unwrap();
+        assert_eq!(middle.board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq e6 0 2");
+
+        assert_eq!(game.position_at_ply(5).err(), Some(GameError::PlyOutOfRange { ply: 5, max: 4 }));
+    }
+
+    #[test]
+    fn test_threefold_repetition_by_shuffling() {
+        let mut game = Game::new();
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        assert!(!game.is_threefold_repetition());
+        // The start position appears for the third time.
+        play(&mut game, &["f6g8"]);
+        assert!(game.is_threefold_repetition());
+    }
+
+    #[test]
+    fn test_threefold_repetition_reset_by_capture() {
+        // The knights shuffle, a pawn is captured, and they shuffle back
+        // into the same piece placement. The positions before the capture had
+        // an extra pawn, so they don't count towards the repetition.
+        let mut game = Game::from_fen("4k3/8/8/3p4/8/8/8/3QK1N1 w - - 0 1").unwrap();
+        play(&mut game, &["g1f3", "e8f7", "f3g1", "f7e8", "d1d5", "e8f8", "d5d1", "f8e8"]);
+        assert!(!game.is_threefold_repetition());
+        play(&mut game, &["g1f3", "e8f7", "f3g1", "f7e8"]);
+        assert!(!game.is_threefold_repetition());
+        play(&mut game, &["g1f3", "e8f7", "f3g1", "f7e8"]);
+        assert!(game.is_threefold_repetition());
+    }
+
+    #[actix_web::test]
+    async fn test_create_game_from_fen() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let fen = "8/8/4k3/8/8/3QK3/8/8 b - - 12 60";
+        let req = TestRequest::post().uri("/games").set_json(json!({ "fen": fen })).to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(created.fen, fen);
+        assert_eq!(created.turn, "black");
+        assert!(created.moves.is_empty());
+        assert!(created.result.is_none());
+        assert!(!created.in_check);
+
+        let req = TestRequest::post().uri("/games").set_json(json!({ "fen": "not a fen" })).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[test]
+    fn test_piece_debug_is_readable() {
+        let queen = Piece::new(PieceKind::Queen, PieceColor::Black);
+        assert_eq!(format!("{:?}", queen), "Piece(Black Queen)");
+        assert_eq!(format!("{:?}", Some(queen)), "Some(Piece(Black Queen))");
+    }
+
+    #[test]
+    fn test_squares_with_lists_both_colors() {
+        let board = Board::new();
+        assert_eq!(
+            board.squares_with(PieceKind::Knight),
+            [
+                (square("b1"), PieceColor::White),
+                (square("g1"), PieceColor::White),
+                (square("b8"), PieceColor::Black),
+                (square("g8"), PieceColor::Black),
+            ]
+        );
+        assert!(Board::empty().squares_with(PieceKind::Knight).is_empty());
+    }
+
+    #[test]
+    fn test_move_errors_carry_distinct_codes() {
+        let mut game = Game::new();
+        let illegal = game.make_move(uci("e2e5")).unwrap_err();
+        let out_of_turn = game.make_move(uci("e7e5")).unwrap_err();
+        assert_eq!(ErrorCode::from(&illegal), ErrorCode::IllegalMove);
+        assert_eq!(ErrorCode::from(&out_of_turn), ErrorCode::NotYourTurn);
+
+        let json: serde_json::Value = serde_json::from_str(&ServerMessage::move_error(&out_of_turn).to_json()).unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["code"], "not_your_turn");
+        assert_eq!(json["message"], out_of_turn.to_string());
+        assert_eq!(json["reason"], "not_your_turn");
+
+        let json: serde_json::Value = serde_json::from_str(&ServerMessage::game_not_found(7).to_json()).unwrap();
+        assert_eq!(json["code"], "game_not_found");
+        assert!(json.get("reason").is_none());
+    }
+
+    #[test]
+    fn test_make_move_san() {
+        let mut game = Game::new();
+        for san in ["e4", "e5", "Nf3"] {
+            game.make_move_san(san).unwrap();
+        }
+        assert_eq!(game.board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        assert_eq!(game.make_move_san("Nf3"), Err(MoveError::InvalidNotation("Nf3".to_string())));
+        assert!(game.make_move_san("Nc6!").is_ok());
+    }
+
+    #[test]
+    fn test_make_move_uci() {
+        let mut game = Game::new();
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            game.make_move_uci(uci).unwrap();
+        }
+        assert_eq!(game.history().len(), 3);
+
+        assert_eq!(game.make_move_uci("e9e5"), Err(MoveError::InvalidNotation("e9e5".to_string())));
+        assert!(game.make_move_uci("e5e4").is_err());
+        assert_eq!(game.history().len(), 3);
+    }
+
+    #[test]
+    fn test_king_in_check_after_discovered_check() {
+        // The rook on d3 blocks the queen on b1 from the black king on h7.
+        // Moving the rook off the diagonal uncovers the check.
+        let mut board = Board::from_fen("8/7k/8/8/8/3R4/8/1Q2K3 w - - 0 1").unwrap();
+        assert!(!board.king_in_check(PieceColor::Black));
+        board.make_move(uci("d3d8"));
+        assert!(board.king_in_check(PieceColor::Black));
+        assert!(!board.king_in_check(PieceColor::White));
+        // Neither king is in check on an empty board.
+        assert!(!Board::empty().king_in_check(PieceColor::White));
+    }
+
+    #[test]
+    fn test_count_attacks() {
+        let board = Board::new();
+        // The pawns cover all of the third rank, and every back-rank piece
+        // except the rooks is defended by another.
+        assert_eq!(board.count_attacks(PieceColor::White), 22);
+        assert_eq!(board.count_attacks(PieceColor::White), board.count_attacks(PieceColor::Black));
+
+        let lone_rook = Board::from_fen("4k3/8/8/8/3R4/8/8/4K3 w - - 0 1").unwrap();
+        // 14 squares for the rook plus the king's 5 neighbours, two of which
+        // (d1 and d2) the rook already covers.
+        assert_eq!(lone_rook.count_attacks(PieceColor::White), 17);
+    }
+
+    #[test]
+    fn test_move_cache_matches_uncached_moves() {
+        let fens = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        let mut cache = MoveCache::new(2);
+        for _ in 0..2 {
+            for fen in fens {
+                let board = Board::from_fen(fen).unwrap();
+                assert_eq!(cache.legal_moves(&board), board.legal_moves(), "{}", fen);
+            }
+        }
+        // With room for only two positions, every lookup evicted another.
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.hits(), 0);
+
+        let board = Board::new();
+        let mut cache = MoveCache::new(16);
+        cache.legal_moves(&board);
+        assert_eq!(cache.legal_moves(&board).len(), 20);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // The engine searches the same tree with the cache on or off; only
+        // the cached engine fills it.
+        let board = Board::from_fen(fens[1]).unwrap();
+        let mut cached = Engine::new();
+        let mut uncached = Engine::with_options(EngineOptions { move_cache: false, ..EngineOptions::default() });
+        let with_cache = cached.search(&board, SearchLimits::depth(3)).unwrap();
+        let without_cache = uncached.search(&board, SearchLimits::depth(3)).unwrap();
+        assert_eq!(with_cache, without_cache);
+        assert!(cached.move_cache().hits() > 0);
+        assert_eq!(uncached.move_cache().len(), 0);
+    }
+
+    #[test]
+    fn test_zobrist_hash_identifies_positions() {
+        // The same position reached by different move orders hashes the same.
+        let mut a = Game::new();
+        play(&mut a, &["g1f3", "g8f6", "b1c3"]);
+        let mut b = Game::new();
+        play(&mut b, &["b1c3", "g8f6", "g1f3"]);
+        assert_eq!(a.board().zobrist_hash(), b.board().zobrist_hash());
+
+        // Side to move, castling rights and en passant all matter.
+        let hash = |fen: &str| Board::from_fen(fen).unwrap().zobrist_hash();
+        let start = hash(START_FEN);
+        assert_ne!(start, hash("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"));
+        assert_ne!(start, hash("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1"));
+        assert_ne!(
+            hash("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"),
+            hash("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3")
+        );
+    }
+
+    #[test]
+    fn test_bitboards_round_trip() {
+        let fens = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/4k3/8/8/3QK3/8/8 b - - 12 60",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let bitboards = BitBoards::from(&board);
+            assert_eq!(Board::from(&bitboards).to_fen(), fen);
+            assert!(Board::from(&bitboards) == board);
+        }
+
+        let start = BitBoards::from(&Board::new());
+        assert_eq!(start.pieces(PieceColor::White, PieceKind::Pawn), Bitboard(0xFF00));
+        assert_eq!(start.occupied(), Bitboard(0xFFFF_0000_0000_FFFF));
+        assert_eq!(start.piece_at(square("d8")), Some(Piece::new(PieceKind::Queen, PieceColor::Black)));
+    }
+
+    #[test]
+    fn test_leaper_attack_tables_match_offsets() {
+        let knight_jumps = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+        for sq in Square::all() {
+            let expected = knight_jumps
+                .iter()
+                .filter_map(|&(df, dr)| sq.offset(df, dr))
+                .collect::<Bitboard>();
+            assert_eq!(KNIGHT_ATTACKS[sq.index()], expected, "knight on {}", sq);
+        }
+        assert_eq!(KING_ATTACKS[square("a1").index()].count(), 3);
+        assert_eq!(KING_ATTACKS[square("e4").index()].count(), 8);
+
+        // attacks_from uses the tables for knights and kings, and stops
+        // sliders at the first piece in the way.
+        let bitboards = BitBoards::from(&Board::new());
+        let g1 = bitboards.attacks_from(square("g1"));
+        assert_eq!(g1, [square("e2"), square("f3"), square("h3")].into_iter().collect());
+        assert_eq!(bitboards.attacks_from(square("a1")), [square("a2"), square("b1")].into_iter().collect());
+        assert_eq!(bitboards.attacks_from(square("e4")), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn test_magic_attacks_match_ray_scan() {
+        // Slides from `from` in each direction, stopping after the first
+        // occupied square.
+        fn ray_scan(from: Square, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+            let mut attacks = Bitboard::EMPTY;
+            for &(df, dr) in directions {
+                let mut current = from.offset(df, dr);
+                while let Some(sq) = current {
+                    attacks |= sq;
+                    if occupied.contains(sq) {
+                        break;
+                    }
+                    current = sq.offset(df, dr);
+                }
+            }
+            attacks
+        }
+        let rook_directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+        let bishop_directions = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+        // Random occupancies from a fixed-seed xorshift, thinned out with AND
+        // so some rays are long and some short.
+        let mut state: u64 = 0x1234_5678_9ABC_DEF1;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..200 {
+            let occupied = Bitboard(random() & random());
+            for sq in Square::all() {
+                assert_eq!(rook_attacks(sq, occupied), ray_scan(sq, occupied, &rook_directions));
+                assert_eq!(bishop_attacks(sq, occupied), ray_scan(sq, occupied, &bishop_directions));
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_known_counts() {
+        let start = Board::new();
+        assert_eq!(perft(&start, 0), 1);
+        assert_eq!(perft(&start, 1), 20);
+        assert_eq!(perft(&start, 3), 8_902);
+
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&kiwipete, 2), 2_039);
+
+        let divide = perft_divide(&start, 2);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.iter().all(|&(_, nodes)| nodes == 20));
+    }
+
+    #[test]
+    fn test_ascii_board_with_coordinates() {
+        let expected = "\
+8 r n b q k b n r
+7 p p p p p p p p
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . . . . .
+3 . . . . . . . .
+2 P P P P P P P P
+1 R N B Q K B N R
+  a b c d e f g h
+White to move (move 1)
+";
+        let mut game = Game::new();
+        assert_eq!(game.to_ascii_with_coordinates(), expected);
+
+        play(&mut game, &["e2e4"]);
+        let text = game.to_ascii_with_coordinates();
+        assert!(text.contains("4 . . . . P . . .\n"));
+        assert!(text.ends_with("Black to move (move 1)\n"));
+    }
+
+    #[test]
+    fn test_capture_to_bare_kings_is_an_automatic_draw() {
+        let store = GameStore::new();
+        let id = store.create(Game::from_fen("8/8/4k3/8/3r4/3K4/8/8 w - - 0 50").unwrap());
+        let state = store.with_game(id, |game| {
+            assert!(!game.is_insufficient_material());
+            play(game, &["d3d4"]);
+            GameState::from_game(id, game)
+        });
+        let state = state.unwrap();
+        assert_eq!(state.result.as_deref(), Some("1/2-1/2"));
+        assert_eq!(state.reason.as_deref(), Some("insufficient_material"));
+
+        let json: serde_json::Value = serde_json::from_str(&ServerMessage::game_over(&state).unwrap().to_json()).unwrap();
+        assert_eq!(json, json!({ "type": "game_over", "game_id": id, "result": "1/2-1/2", "reason": "insufficient_material" }));
+        // The draw is final, like any other result.
+        assert_eq!(store.record(id).unwrap().result, "1/2-1/2");
+    }
+
+    #[test]
+    fn test_uci_history() {
+        let mut game = Game::new();
+        assert!(game.uci_history().is_empty());
+        for san in ["e4", "e5", "Nf3"] {
+            game.make_move_san(san).unwrap();
+        }
+        assert_eq!(game.uci_history(), ["e2e4", "e7e5", "g1f3"]);
+    }
+
+    #[test]
+    fn test_auto_queen_promotion() {
+        let fen = "8/4P3/8/8/8/k7/8/4K3 w - - 0 1";
+        let mut strict = Game::from_fen(fen).unwrap();
+        assert_eq!(strict.make_move_uci("e7e8"), Err(MoveError::MissingPromotion));
+
+        let mut casual = Game::from_fen(fen).unwrap();
+        casual.set_auto_queen(true);
+        casual.make_move_uci("e7e8").unwrap();
+        assert_eq!(casual.board().piece_at(square("e8")), Some(Piece::new(PieceKind::Queen, PieceColor::White)));
+        assert_eq!(casual.uci_history(), ["e7e8q"]);
+    }
+
+    #[actix_web::test]
+    async fn test_create_game_with_auto_queen() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::post()
+            .uri("/games")
+            .set_json(json!({ "fen": "8/4P3/8/8/8/k7/8/4K3 w - - 0 1", "auto_queen": true }))
+            .to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        let promoted = store.with_game(created.id, |game| game.make_move_uci("e7e8"));
+        assert_eq!(promoted, Some(Ok(())));
+    }
+
+    #[test]
+    fn test_is_check_after_matches_playing_the_move() {
+        let fens = [
+            // The bishop on e2 is pinned against the king by the rook on e7.
+            "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let us = board.side_to_move();
+            // Every move our pieces can physically make, whether or not it
+            // exposes the king.
+            let candidates = Square::all().flat_map(|from| Square::all().map(move |to| Move::new(from, to)));
+            for mv in candidates {
+                let mv = match board.explain_illegal(&mv) {
+                    None => *board.legal_moves().iter().find(|legal| legal.same_squares(&mv)).unwrap(),
+                    Some(MoveError::LeavesKingInCheck) => mv,
+                    Some(_) => continue,
+                };
+                let mut copy = board.clone();
+                copy.make_move(mv);
+                assert_eq!(board.is_check_after(&mv, us), copy.king_in_check(us), "{} in {}", mv, fen);
+            }
+        }
+        let pinned = Board::from_fen(fens[0]).unwrap();
+        assert!(pinned.is_check_after(&uci("e2d3"), PieceColor::White));
+        assert!(!pinned.is_check_after(&uci("e1d1"), PieceColor::White));
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_count_games_and_moves() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let metrics: Metrics = call_and_read_body_json(&app, TestRequest::get().uri("/metrics").to_request()).await;
+        assert_eq!((metrics.active_games, metrics.total_moves), (0, 0));
+
+        let created: GameState = call_and_read_body_json(&app, TestRequest::post().uri("/games").to_request()).await;
+        store.play_move(created.id, uci("e2e4")).unwrap().unwrap();
+        // Illegal moves and stepping through the history aren't new moves.
+        assert!(store.play_move(created.id, uci("e2e4")).unwrap().is_err());
+        store.with_game(created.id, |game| {
+            game.undo();
+            game.redo();
+            game.jump_to_ply(0).unwrap();
+            game.jump_to_ply(1).unwrap();
+        });
+
+        let metrics: Metrics = call_and_read_body_json(&app, TestRequest::get().uri("/metrics").to_request()).await;
+        assert_eq!(metrics.active_games, 1);
+        assert_eq!(metrics.total_moves, 1);
+        assert_eq!(metrics.connected_clients, 0);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_count_open_websocket_connections() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let store = web::Data::new(GameStore::new());
+        let id = store.create(Game::new());
+        let server_store = store.clone();
+        let server = actix_web::HttpServer::new(move || App::new().app_data(server_store.clone()).configure(routes::config))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address)).await.unwrap();
+        let (mut sink, mut stream) = socket.split();
+
+        // Joining the same game twice still counts as one client.
+        for _ in 0..2 {
+            sink.send(Message::Text(json!({ "type": "join", "game_id": id }).to_string())).await.unwrap();
+            match stream.next().await {
+                Some(Ok(Message::Text(text))) => assert!(text.contains("game_state"), "{}", text),
+                other => panic!("expected a text message, got {:?}", other),
+            }
+        }
+        assert_eq!(store.metrics().connected_clients, 1);
+
+        // Closing the connection takes it off the count.
+        sink.send(Message::Close(None)).await.unwrap();
+        while let Some(Ok(_)) = stream.next().await {}
+        for _ in 0..50 {
+            if store.metrics().connected_clients == 0 {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(store.metrics().connected_clients, 0);
+
+        handle.stop(false).await;
     }
 
-    // TODO: Add more integration tests
+    #[actix_web::test]
+    async fn test_malformed_websocket_message_keeps_connection_open() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let store = web::Data::new(GameStore::new());
+        let server = actix_web::HttpServer::new(move || App::new().app_data(store.clone()).configure(routes::config))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address)).await.unwrap();
+        let (mut sink, mut stream) = socket.split();
+        let mut next_json = async || -> serde_json::Value {
+            match stream.next().await {
+                Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+                other => panic!("expected a text message, got {:?}", other),
+            }
+        };
+
+        sink.send(Message::Text("not json".to_string())).await.unwrap();
+        let reply = next_json().await;
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["code"], "malformed_message");
+
+        // The connection is still usable afterwards.
+        sink.send(Message::Text(json!({ "type": "join", "game_id": 99 }).to_string())).await.unwrap();
+        assert_eq!(next_json().await["code"], "game_not_found");
+
+        // Don't wait for the open connection to wind down.
+        handle.stop(false).await;
+    }
+
+    #[test]
+    fn test_legal_moves_from_square() {
+        let game = Game::new();
+        let targets: Vec<String> = game.legal_moves_from(square("e2")).iter().map(Move::to_uci).collect();
+        assert_eq!(targets, ["e2e3", "e2e4"]);
+        assert_eq!(game.legal_moves_from(square("g1")).len(), 2);
+        // Empty squares and the opponent's pieces have no moves.
+        assert!(game.legal_moves_from(square("e4")).is_empty());
+        assert!(game.legal_moves_from(square("e7")).is_empty());
+
+        // A pinned piece can only move along the pin.
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let mut targets: Vec<String> = game.legal_moves_from(square("e2")).iter().map(Move::to_uci).collect();
+        targets.sort();
+        assert_eq!(targets, ["e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]);
+    }
+
+    #[test]
+    fn test_blocked_pawns_are_a_dead_position() {
+        // A wall of locked pawns across the board with a king on each side:
+        // the pawns can't move and the kings can't get at them.
+        let dead = "8/4k3/8/p1p1p1p1/P1P1P1P1/8/4K3/8 w - - 0 1";
+        let game = Game::from_fen(dead).unwrap();
+        assert!(game.is_dead_position());
+        assert!(!game.is_insufficient_material());
+
+        // The same wall with a gap lets the white king walk through.
+        let open = Game::from_fen("8/4k3/8/p1p1p3/P1P1P3/8/4K3/8 w - - 0 1").unwrap();
+        assert!(!open.is_dead_position());
+        // A pawn that could capture keeps the game alive, as does any piece.
+        assert!(!Game::from_fen("8/4k3/8/p1p1pp2/P1P1P1P1/8/4K3/8 w - - 0 1").unwrap().is_dead_position());
+        assert!(!Game::from_fen("8/4k3/8/p1p1p1p1/P1P1P1P1/8/4K3/7B w - - 0 1").unwrap().is_dead_position());
+        assert!(!Game::new().is_dead_position());
+
+        // Reaching the locked position ends the game.
+        let mut game = Game::from_fen("8/4k3/8/p1p1p1p1/P1P1P3/6P1/4K3/8 w - - 0 1").unwrap();
+        play(&mut game, &["g3g4"]);
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.termination(), Some(Termination::DeadPosition));
+    }
+
+    #[test]
+    fn test_piece_list_round_trip() {
+        let board = Board::from_fen("8/5k2/8/3n4/8/2K5/6P1/8 w - - 0 1").unwrap();
+        let pieces = board.piece_list();
+        assert_eq!(
+            pieces,
+            [
+                (square("g2"), Piece::new(PieceKind::Pawn, PieceColor::White)),
+                (square("c3"), Piece::new(PieceKind::King, PieceColor::White)),
+                (square("d5"), Piece::new(PieceKind::Knight, PieceColor::Black)),
+                (square("f7"), Piece::new(PieceKind::King, PieceColor::Black)),
+            ]
+        );
+        assert!(Board::from_piece_list(pieces) == board);
+
+        let start = Board::new();
+        assert_eq!(start.piece_list().len(), 32);
+        let rebuilt = Board::from_piece_list(start.piece_list());
+        assert_eq!(rebuilt.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+    }
+
+    #[test]
+    fn test_find_mate_in_two() {
+        // 1. Ra7 Kg8 2. Rb8# (or the same idea after any king move); no
+        // single rook check mates straight away.
+        let game = Game::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        assert!(find_mate(&game, 1).is_none());
+
+        let line = find_mate(&game, 2).expect("mate in two");
+        assert_eq!(line.len(), 3);
+
+        let mut played = game.clone();
+        for mv in line {
+            played.make_move(mv).unwrap();
+        }
+        assert_eq!(played.result(), Some(GameResult::WhiteWins));
+        assert_eq!(played.termination(), Some(Termination::Checkmate));
+    }
+
+    #[actix_web::test]
+    async fn test_puzzle_check() {
+        let app = init_service(App::new().configure(routes::config)).await;
+        // Back-rank mate: Ra8#.
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+
+        let request = TestRequest::post()
+            .uri("/puzzles/check")
+            .set_json(json!({ "fen": fen, "move": "a1a8" }))
+            .to_request();
+        let response: PuzzleCheckResponse = call_and_read_body_json(&app, request).await;
+        assert!(response.correct);
+        assert_eq!(response.best_move, "a1a8");
+        assert_eq!(response.san, "Ra8#");
+        assert_eq!(response.mate_in, Some(1));
+        assert_eq!(response.refutation, None);
+
+        let request = TestRequest::post()
+            .uri("/puzzles/check")
+            .set_json(json!({ "fen": fen, "move": "a1a2" }))
+            .to_request();
+        let response: PuzzleCheckResponse = call_and_read_body_json(&app, request).await;
+        assert!(!response.correct);
+        assert_eq!(response.best_move, "a1a8");
+        assert!(response.refutation.is_some());
+
+        // An illegal answer is a bad request, not a wrong one.
+        let request = TestRequest::post()
+            .uri("/puzzles/check")
+            .set_json(json!({ "fen": fen, "move": "a1b3" }))
+            .to_request();
+        assert_eq!(call_service(&app, request).await.status(), 400);
+    }
+
+    #[test]
+    fn test_log_level_parsing() {
+        use tracing::level_filters::LevelFilter;
+
+        assert_eq!(parse_level("debug"), LevelFilter::DEBUG);
+        assert_eq!(parse_level("WARN"), LevelFilter::WARN);
+        assert_eq!(parse_level("off"), LevelFilter::OFF);
+        // Unknown names fall back to the default instead of failing.
+        assert_eq!(parse_level("loud"), DEFAULT_LEVEL);
+        assert_eq!(parse_level(""), DEFAULT_LEVEL);
+        assert_eq!(DEFAULT_LEVEL, LevelFilter::INFO);
+
+        // The argument beats the environment, which beats the default.
+        assert_eq!(choose_level(Some("trace"), Some("error")), LevelFilter::TRACE);
+        assert_eq!(choose_level(None, Some("error")), LevelFilter::ERROR);
+        assert_eq!(choose_level(None, None), DEFAULT_LEVEL);
+
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(arg_value(args(&["--log-level", "debug"]), "log-level"), Some("debug".to_string()));
+        assert_eq!(arg_value(args(&["--log-level=trace"]), "log-level"), Some("trace".to_string()));
+        assert_eq!(arg_value(args(&["--port", "80"]), "log-level"), None);
+        // The same parsing serves every option, and a longer name that merely
+        // starts with the one asked for doesn't count.
+        assert_eq!(arg_value(args(&["--book-depth=4", "--book", "book.bin"]), "book"), Some("book.bin".to_string()));
+        assert_eq!(arg_value(args(&["--book-depth", "4"]), "book-depth"), Some("4".to_string()));
+    }
+
+    #[test]
+    fn test_board_repr_backends_agree() {
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let bitboards = <BitBoards as BoardRepr>::from_board(&kiwipete);
+
+        for square in Square::all() {
+            assert_eq!(BoardRepr::piece_at(&bitboards, square), kiwipete.piece_at(square));
+        }
+        assert_eq!(perft(&bitboards, 3), perft(&kiwipete, 3));
+        // The two generators list moves in different orders.
+        let sorted = |mut divide: Vec<(Move, u64)>| {
+            divide.sort();
+            divide
+        };
+        assert_eq!(sorted(perft_divide(&bitboards, 2)), sorted(perft_divide(&kiwipete, 2)));
+
+        // Every move, played on both, gives the same position, and taking it
+        // back leaves the bitboards as they were.
+        for mv in kiwipete.legal_moves() {
+            let mut board = kiwipete.clone();
+            board.make_move(mv);
+            let mut copy = bitboards.clone();
+            let undo = copy.make_move(mv);
+            assert_eq!(copy.to_board().to_fen(), board.to_fen(), "{}", mv);
+            assert_eq!(copy.king_in_check(PieceColor::Black), board.king_in_check(PieceColor::Black), "{}", mv);
+            copy.unmake_move(mv, undo);
+            assert_eq!(copy, bitboards, "{}", mv);
+        }
+    }
+
+    #[test]
+    fn test_game_on_bitboards() {
+        let mut game = Game::from_board(BitBoards::from(&Board::new()));
+        let mut reference = Game::new();
+        for mv in ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6"] {
+            game.make_move_uci(mv).unwrap();
+            reference.make_move_uci(mv).unwrap();
+            assert_eq!(game.current_fen(), reference.current_fen());
+            let mut moves = game.legal_moves();
+            moves.sort();
+            let mut expected = reference.legal_moves();
+            expected.sort();
+            assert_eq!(moves, expected);
+        }
+        assert_eq!(game.make_move_uci("h5h8"), Err(MoveError::BlockedPath));
+
+        game.make_move_san("Qxf7#").unwrap();
+        assert_eq!(game.status(), GameStatus::Checkmate(PieceColor::White));
+        assert!(game.is_in_check());
+        assert_eq!(game.history().last().unwrap().san(), "Qxf7#");
+        assert_eq!(game.board().piece_at(square("f7")), Some(Piece::new(PieceKind::Queen, PieceColor::White)));
+
+        assert_eq!(game.undo(), Some(uci("h5f7")));
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        assert_eq!(game.current_fen(), reference.current_fen());
+        assert_eq!(game.position_at_ply(2).unwrap().current_fen(), reference.position_at_ply(2).unwrap().current_fen());
+    }
+
+    #[test]
+    fn test_current_fen() {
+        let mut game = Game::new();
+        assert_eq!(game.current_fen(), START_FEN);
+
+        play(&mut game, &["e2e4", "c7c5", "g1f3"]);
+        assert_eq!(
+            game.current_fen(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+
+        // Castling rights go one side at a time as the rooks and king move.
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut game, &["h1h2", "a8b8"]);
+        assert_eq!(game.current_fen(), "1r2k2r/8/8/8/8/8/7R/R3K3 w Qk - 2 2");
+    }
+
+    #[test]
+    fn test_perft_tricky_positions() {
+        // Published counts for positions built to catch move generation
+        // bugs: discovered checks along the rank after en passant, promotion
+        // with capture, castling rights lost to captured rooks, and pins.
+        let cases = [
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2_812),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9_467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 2, 1_486),
+        ];
+        for (fen, depth, nodes) in cases {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(perft(&board, depth), nodes, "{}", fen);
+            assert_eq!(perft(&BitBoards::from(&board), depth), nodes, "bitboards: {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_pseudo_legal_and_is_legal() {
+        // The d2 knight is pinned by the bishop on b4, so none of its moves
+        // are legal even though all of them are pseudo-legal.
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+        let pseudo_legal = board.generate_pseudo_legal();
+        let legal = board.legal_moves();
+        assert!(pseudo_legal.len() > legal.len());
+
+        let filtered: Vec<Move> = pseudo_legal.iter().copied().filter(|mv| board.is_legal(mv)).collect();
+        assert_eq!(filtered, legal);
+        let d2 = Square::from_algebraic("d2").unwrap();
+        assert!(legal.iter().all(|mv| mv.from() != d2));
+    }
+
+    #[test]
+    fn test_leaper_attack_lookups() {
+        let sq = |name: &str| Square::from_algebraic(name).unwrap();
+        let set = |names: &[&str]| names.iter().map(|name| sq(name)).collect::<Bitboard>();
+
+        assert_eq!(knight_attacks(sq("a1")), set(&["b3", "c2"]));
+        assert_eq!(knight_attacks(sq("d4")).count(), 8);
+        assert_eq!(king_attacks(sq("h8")), set(&["g8", "g7", "h7"]));
+        assert_eq!(pawn_attacks(PieceColor::White, sq("e4")), set(&["d5", "f5"]));
+        assert_eq!(pawn_attacks(PieceColor::Black, sq("a7")), set(&["b6"]));
+        assert_eq!(pawn_attacks(PieceColor::White, sq("c8")), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn test_game_state_reports_check() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "f7f6", "d1h5"]);
+        let state = GameState::from_game(1, &game);
+        assert!(state.in_check);
+        assert!(game.board().is_square_attacked(Square::from_algebraic("e8").unwrap(), PieceColor::White));
+
+        play(&mut game, &["g7g6"]);
+        assert!(!GameState::from_game(1, &game).in_check);
+    }
+
+    #[test]
+    fn test_game_status() {
+        let mut game = Game::new();
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        // Fool's mate.
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(game.status(), GameStatus::Checkmate(PieceColor::Black));
+
+        let mut game = Game::from_fen("k7/8/2Q5/8/8/8/8/7K w - - 0 1").unwrap();
+        play(&mut game, &["c6b6"]);
+        assert_eq!(game.status(), GameStatus::Stalemate);
+
+        let mut game = Game::from_fen("k7/8/8/8/8/8/1q6/K7 w - - 0 1").unwrap();
+        play(&mut game, &["a1b2"]);
+        assert_eq!(game.status(), GameStatus::Draw(Termination::InsufficientMaterial));
+    }
+
+    #[test]
+    fn test_pins_and_discovered_check_candidates() {
+        let sq = |name: &str| Bitboard::from_square(Square::from_algebraic(name).unwrap());
+        // White's d2 knight is pinned by the b4 bishop and the e4 pawn by the
+        // e8 rook; Black's g7 pawn is pinned by the g1 rook. The d5 knight
+        // stands between the b3 bishop and the black king.
+        let board = Board::from_fen("4r1k1/6p1/8/3N4/1b2P3/1B6/3N4/4K1R1 w - - 0 1").unwrap();
+        assert_eq!(board.pinned_pieces(PieceColor::White), sq("d2") | sq("e4"));
+        assert_eq!(board.pinned_pieces(PieceColor::Black), sq("g7"));
+        assert_eq!(board.discovered_check_candidates(PieceColor::White), sq("d5"));
+        assert!(board.discovered_check_candidates(PieceColor::Black).is_empty());
+    }
+
+    #[test]
+    fn test_castling_rules() {
+        // Castling moves the rook too. (Black can't castle kingside here:
+        // the f1 rook now covers f8.)
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut game, &["e1g1", "e8c8"]);
+        assert_eq!(game.current_fen(), "2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2");
+
+        // Capturing a rook on its home square takes that side's right away.
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut game, &["h1h8"]);
+        assert_eq!(game.board().castling_rights().bits().count_ones(), 2);
+        assert!(game.current_fen().contains(" Qq "));
+
+        // Not out of check, not through an attacked square, not through a piece.
+        let castles = |fen: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            let mut moves: Vec<String> = board
+                .legal_moves()
+                .iter()
+                .map(|mv| mv.to_uci())
+                .filter(|uci| uci == "e1g1" || uci == "e1c1")
+                .collect();
+            moves.sort();
+            moves
+        };
+        assert_eq!(castles("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1", "e1g1"]);
+        assert!(castles("4k3/8/8/8/8/8/8/R3K2R w - - 0 1").is_empty());
+        assert!(castles("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1").is_empty());
+        assert_eq!(castles("4k3/5r2/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1"]);
+        assert_eq!(castles("4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1"), ["e1g1"]);
+        // The b1 square may be attacked on the queenside; the king never crosses it.
+        assert_eq!(castles("4k3/1r6/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1", "e1g1"]);
+    }
+
+    #[test]
+    fn test_en_passant_make_and_unmake() {
+        let mut board = Board::from_fen("4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1").unwrap();
+        let sq = |name: &str| Square::from_algebraic(name).unwrap();
+
+        let push = Move::new(sq("d2"), sq("d4"));
+        let push = *board.legal_moves().iter().find(|mv| mv.same_squares(&push)).unwrap();
+        board.make_move(push);
+        assert_eq!(board.en_passant(), Some(sq("d3")));
+        let before = board.to_fen();
+
+        let capture = *board.legal_moves().iter().find(|mv| mv.to() == sq("d3")).unwrap();
+        assert_eq!(capture.flag(), MoveFlag::EnPassant);
+        let undo = board.make_move(capture);
+        // The captured pawn is on d4, not on the square the capture lands on.
+        assert_eq!(board.piece_at(sq("d4")), None);
+        assert_eq!(board.piece_at(sq("d3")), Some(Piece::new(PieceKind::Pawn, PieceColor::Black)));
+        assert_eq!(undo.captured(), Some(Piece::new(PieceKind::Pawn, PieceColor::White)));
+        assert_eq!(board.en_passant(), None);
+
+        board.unmake_move(capture, undo);
+        assert_eq!(board.to_fen(), before);
+
+        // The chance is gone after any other move.
+        let mut game = Game::from_fen(&before).unwrap();
+        play(&mut game, &["e8e7", "e1e2"]);
+        assert!(game.make_move_uci("e4d3").is_err());
+    }
+
+    #[test]
+    fn test_client_chooses_underpromotion() {
+        let message: ClientMessage = serde_json::from_value(json!({
+            "type": "move", "game_id": 1, "uci": "a7a8", "promotion": "knight"
+        }))
+        .unwrap();
+        let ClientMessage::Move { uci, promotion, .. } = message else {
+            panic!("expected a move message");
+        };
+        assert_eq!(promotion, Some(Promotion::Knight));
+
+        let mut game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game.make_move(client_move(&uci, promotion).unwrap()).unwrap();
+        let a8 = Square::from_algebraic("a8").unwrap();
+        assert_eq!(game.board().piece_at(a8), Some(Piece::new(PieceKind::Knight, PieceColor::White)));
+
+        // Every piece but a king or pawn can be chosen, in UCI or in the field.
+        for (uci, promotion, kind) in [
+            ("a7a8r", None, PieceKind::Rook),
+            ("a7a8", Some(Promotion::Bishop), PieceKind::Bishop),
+            ("a7a8q", Some(Promotion::Knight), PieceKind::Queen),
+        ] {
+            assert_eq!(client_move(uci, promotion).unwrap().promotion(), Some(kind));
+        }
+        // Without a choice (and without auto-queen) the move is refused.
+        let mut game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.make_move(client_move("a7a8", None).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_fifty_and_seventy_five_move_rules() {
+        // Rooks shuffling back and forth; the clock starts at 96.
+        let mut game = Game::from_fen("4k3/r7/8/8/8/8/R7/4K3 w - - 96 80").unwrap();
+        play(&mut game, &["a2b2", "a7b7", "b2a2"]);
+        assert!(!game.fifty_move_rule_reached());
+        play(&mut game, &["b7a7"]);
+        assert!(game.fifty_move_rule_reached());
+        // Claimable, but play goes on.
+        assert_eq!(game.result(), None);
+
+        // A capture or pawn move starts the count again.
+        let mut game = Game::from_fen("4k3/r7/8/8/8/8/R6P/4K3 w - - 99 80").unwrap();
+        play(&mut game, &["h2h3"]);
+        assert_eq!(game.board().halfmove_clock(), 0);
+        assert!(!game.fifty_move_rule_reached());
+
+        // At 75 moves the game ends by itself.
+        let mut game = Game::from_fen("4k3/r7/8/8/8/8/R7/4K3 w - - 148 100").unwrap();
+        play(&mut game, &["a2b2"]);
+        assert_eq!(game.result(), None);
+        play(&mut game, &["a7b7"]);
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.termination(), Some(Termination::SeventyFiveMoveRule));
+    }
+
+    #[test]
+    fn test_board_insufficient_material() {
+        let insufficient = |fen: &str| Board::from_fen(fen).unwrap().insufficient_material();
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/1n2K3 w - - 0 1"));
+        // c1 and f8 are both dark squares, and so is a7.
+        assert!(insufficient("4kb2/b7/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // c1 is dark, c8 light.
+        assert!(!insufficient("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // Two knights can't force mate, but mate is still possible.
+        assert!(!insufficient("4k3/8/8/8/8/8/8/1N2K1N1 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/2B1K1N1 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_board_dead_position() {
+        let dead = |fen: &str| Board::from_fen(fen).unwrap().is_dead_position();
+        assert!(dead("8/8/1k6/p1p1p1p1/P1P1P1P1/8/6K1/8 b - - 0 1"));
+        // A king already touching an enemy pawn might win it.
+        assert!(!dead("8/8/8/pkp1p1p1/P1P1P1P1/8/4K3/8 w - - 0 1"));
+        // Bare kings are a material question, not a dead position.
+        assert!(!dead("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_move_is_two_bytes() {
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+
+        // Every field survives packing.
+        let board = Board::new();
+        for mv in board.legal_moves() {
+            assert_eq!(Move::from_uci(&mv.to_uci()).unwrap().to_uci(), mv.to_uci());
+        }
+        let sq = |name: &str| Square::from_algebraic(name).unwrap();
+        for kind in [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen] {
+            let mv = Move::with_promotion(sq("h7"), sq("g8"), kind);
+            assert_eq!((mv.from(), mv.to(), mv.promotion()), (sq("h7"), sq("g8"), Some(kind)));
+            assert_eq!(mv.flag(), MoveFlag::Normal);
+        }
+
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "d7d5"]);
+        let capture = game.legal_moves().into_iter().find(|mv| mv.to_uci() == "e4d5").unwrap();
+        assert_eq!(
+            capture.detail(game.board()),
+            Some(MoveDetail {
+                from: sq("e4"),
+                to: sq("d5"),
+                piece: Piece::new(PieceKind::Pawn, PieceColor::White),
+                captured: Some(Piece::new(PieceKind::Pawn, PieceColor::Black)),
+                promotion: None,
+                flag: MoveFlag::Normal,
+                uci: "e4d5".to_string(),
+                san: "exd5".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_san_accepts_loose_notation() {
+        let san = |text: &str, fen: &str| Move::from_san(text, &Board::from_fen(fen).unwrap()).map(|mv| mv.to_uci());
+
+        // Needed disambiguation, and more than needed.
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san("Nbd2", knights), Ok("b1d2".to_string()));
+        assert_eq!(san("Nb1d2", knights), Ok("b1d2".to_string()));
+        assert_eq!(san("Nfg3", knights), Ok("f1g3".to_string()));
+        assert!(san("Nd2", knights).is_err());
+
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san("R1a3", rooks), Ok("a1a3".to_string()));
+        assert_eq!(san("R5a3", rooks), Ok("a5a3".to_string()));
+
+        // Missing "x" and "=", captures, promotions and castling.
+        let board = "r3k3/1P6/8/3p4/4P3/8/8/4K2R w K - 0 1";
+        assert_eq!(san("exd5", board), Ok("e4d5".to_string()));
+        assert_eq!(san("ed5", board), Ok("e4d5".to_string()));
+        assert_eq!(san("bxa8=N", board), Ok("b7a8n".to_string()));
+        assert_eq!(san("ba8Q+", board), Ok("b7a8q".to_string()));
+        assert_eq!(san("b8=R", board), Ok("b7b8r".to_string()));
+        assert_eq!(san("0-0", board), Ok("e1g1".to_string()));
+        assert_eq!(san("O-O-O", board).map_err(|e| e.to_string()), Err("'O-O-O' is not a valid move".to_string()));
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san("exd6 e.p.", en_passant), Ok("e5d6".to_string()));
+        assert_eq!(san("exd6e.p.", en_passant), Ok("e5d6".to_string()));
+
+        // Whatever `to_san` writes reads back as the same move.
+        let kiwipete = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for mv in kiwipete.legal_moves() {
+            assert_eq!(Move::from_san(&mv.to_san(&kiwipete), &kiwipete), Ok(mv));
+        }
+    }
+
+    #[test]
+    fn test_san_needs_file_and_rank() {
+        // Queens on a1, a3 and c1 can all reach b2: a1 shares a file with a3
+        // and a rank with c1, so only the full square will do.
+        let board = Board::from_fen("8/8/6k1/8/8/Q7/8/Q1Q1K3 w - - 0 1").unwrap();
+        assert_eq!(uci("a1b2").to_san(&board), "Qa1b2");
+        assert_eq!(uci("a3b2").to_san(&board), "Q3b2");
+        assert_eq!(uci("c1b2").to_san(&board), "Qcb2");
+
+        // Mate, a capturing promotion, and queenside castling.
+        let board = Board::from_fen("rn5k/1P4pp/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert_eq!(uci("b7a8q").to_san(&board), "bxa8=Q");
+        assert_eq!(uci("e1c1").to_san(&board), "O-O-O");
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(uci("a1a8").to_san(&board), "Ra8#");
+    }
+
+    #[test]
+    fn test_uci_parse_print_and_json() {
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for mv in kiwipete.legal_moves() {
+            let parsed: Move = mv.to_uci().parse().unwrap();
+            assert!(parsed.same_squares(&mv));
+        }
+
+        assert_eq!("e7e8n".parse::<Move>().unwrap().promotion(), Some(PieceKind::Knight));
+        // Kings are promotion pieces in Antichess.
+        assert_eq!("e7e8k".parse::<Move>().unwrap().promotion(), Some(PieceKind::King));
+        for bad in ["", "e2", "e2e9", "e7e8p", "E2E4", "e2e4qq"] {
+            assert_eq!(bad.parse::<Move>(), Err(MoveError::InvalidNotation(bad.to_string())));
+        }
+
+        let mv = uci("e7e8q");
+        assert_eq!(serde_json::to_value(mv).unwrap(), json!("e7e8q"));
+        assert_eq!(serde_json::from_value::<Move>(json!("e7e8q")).unwrap(), mv);
+        assert!(serde_json::from_value::<Move>(json!("castle")).is_err());
+    }
+
+    #[test]
+    fn test_lenient_move_input() {
+        let mut game = Game::new();
+        for text in ["E4", "e7-e5", "nf3", "Nc6", "bc4", "g8f6", "0-0", "Bc5"] {
+            game.make_move_lenient(text).unwrap_or_else(|e| panic!("{}: {}", text, e));
+        }
+        assert_eq!(
+            game.current_fen(),
+            "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 6 5"
+        );
+
+        let board = Board::from_fen("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(Move::parse_lenient("Qd1xd8+", &board).unwrap().to_uci(), "d1d8");
+        assert_eq!(Move::parse_lenient("qxd8", &board).unwrap().to_uci(), "d1d8");
+
+        // "bc3" could be the b-pawn or the bishop taking on c3.
+        let board = Board::from_fen("4k3/8/8/8/8/2n5/1P6/4BK2 w - - 0 1").unwrap();
+        let error = Move::parse_lenient("bc3", &board).unwrap_err();
+        assert_eq!(error, MoveError::Ambiguous(vec![uci("e1c3"), uci("b2c3")]));
+        assert_eq!(error.to_string(), "that could be any of e1c3, b2c3");
+        assert_eq!(ErrorCode::from(&error), ErrorCode::MalformedMessage);
+        assert_eq!(Move::parse_lenient("Bxc3", &board).unwrap().to_uci(), "e1c3");
+
+        assert!(matches!(Move::parse_lenient("hello", &board), Err(MoveError::InvalidNotation(_))));
+    }
+
+    #[test]
+    fn test_square_distance_and_direction() {
+        assert_eq!(square("e4").to_string(), "e4");
+        assert_eq!((square("e4").file(), square("e4").rank()), (4, 3));
+        assert_eq!(Square::from_algebraic("i9"), None);
+
+        assert_eq!(square("a1").distance(square("h8")), 7);
+        assert_eq!(square("e1").distance(square("g4")), 3);
+        assert_eq!(square("b7").file_distance(square("g3")), 5);
+        assert_eq!(square("b7").rank_distance(square("g3")), 4);
+
+        assert_eq!(square("a1").direction_to(square("h8")), Some((1, 1)));
+        assert_eq!(square("e4").direction_to(square("e1")), Some((0, -1)));
+        assert_eq!(square("h3").direction_to(square("c3")), Some((-1, 0)));
+        assert_eq!(square("g1").direction_to(square("f3")), None);
+        assert_eq!(square("d4").direction_to(square("d4")), None);
+    }
+
+    #[test]
+    fn test_bitboard_set_operations() {
+        let sq = |name: &str| Square::from_algebraic(name).unwrap();
+        let mut set: Bitboard = [sq("h1"), sq("a2"), sq("e4")].into_iter().collect();
+        assert_eq!(set.count(), 3);
+        assert!(set.contains(sq("e4")) && !set.contains(sq("e5")));
+        assert_eq!(format!("{:?}", set), "Bitboard[h1, a2, e4]");
+
+        // Sideways shifts drop squares at the edge instead of wrapping.
+        assert_eq!(set.east(), [sq("b2"), sq("f4")].into_iter().collect());
+        assert_eq!(set.west(), [sq("g1"), sq("d4")].into_iter().collect());
+        assert_eq!(set.north(), [sq("h2"), sq("a3"), sq("e5")].into_iter().collect());
+        assert_eq!(set.south(), [sq("a1"), sq("e3")].into_iter().collect());
+
+        assert_eq!(set.pop_lsb(), Some(sq("h1")));
+        assert_eq!(set.iter_squares().collect::<Vec<_>>(), vec![sq("a2"), sq("e4")]);
+        assert_eq!(set & Bitboard::FILE_A, Bitboard::from_square(sq("a2")));
+        assert_eq!((set | sq("c3")) ^ set, Bitboard::from_square(sq("c3")));
+        assert_eq!(!Bitboard::EMPTY, Bitboard::FULL);
+        assert_eq!(Bitboard::EMPTY.lsb(), None);
+    }
+
+    #[test]
+    fn test_board_display_diagram() {
+        let board = Board::from_fen("4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1").unwrap();
+        let expected = "\
+8 . . . . k . . .
+7 . . . . . . . .
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . P . . .
+3 . . . . . . . .
+2 . . . . . . . .
+1 . . . . K . . R
+  a b c d e f g h
+";
+        assert_eq!(board.to_string(), expected);
+        assert!(format!("{}", Board::new()).starts_with("8 r n b q k b n r\n"));
+    }
+
+    #[test]
+    fn test_unicode_board_from_both_sides() {
+        let board = Board::from_fen("4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1").unwrap();
+        let white = "\
+8 · · · · ♚ · · ·
+7 · · · · · · · ·
+6 · · · · · · · ·
+5 · · · · · · · ·
+4 · · · · ♙ · · ·
+3 · · · · · · · ·
+2 · · · · · · · ·
+1 · · · · ♔ · · ♖
+  a b c d e f g h
+";
+        assert_eq!(board.unicode(PieceColor::White).to_string(), white);
+
+        // From Black's side the board is turned round: rank 1 on top and the
+        // h-file on the left.
+        let black = board.unicode(PieceColor::Black).to_string();
+        let lines: Vec<&str> = black.lines().collect();
+        assert_eq!(lines[0], "1 ♖ · · ♔ · · · ·");
+        assert_eq!(lines[4], "5 · · · · · · · ·");
+        assert_eq!(lines[7], "8 · · · ♚ · · · ·");
+        assert_eq!(lines[8], "  h g f e d c b a");
+
+        // The ASCII Display is unchanged.
+        assert!(board.to_string().is_ascii());
+    }
+
+    #[test]
+    fn test_piece_fen_chars_round_trip() {
+        let black_knight = Piece::new(PieceKind::Knight, PieceColor::Black);
+        assert_eq!(black_knight.to_fen_char(), 'n');
+        assert_eq!(Piece::from_fen_char('n'), Some(black_knight));
+        assert_eq!(Piece::new(PieceKind::Queen, PieceColor::White).to_fen_char(), 'Q');
+
+        for c in "PNBRQKpnbrqk".chars() {
+            let piece = Piece::try_from(c).unwrap();
+            assert_eq!(piece.to_fen_char(), c);
+        }
+        assert_eq!(Piece::try_from('x'), Err(InvalidPieceChar('x')));
+        assert_eq!(Piece::from_fen_char('1'), None);
+        assert_eq!(InvalidPieceChar('x').to_string(), "'x' is not a piece letter");
+    }
+
+    #[test]
+    fn test_piece_from_raw_rejects_bad_bytes() {
+        for kind in [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen, PieceKind::King] {
+            for color in [PieceColor::White, PieceColor::Black] {
+                let piece = Piece::new(kind, color);
+                assert_eq!(Piece::from_raw(piece.to_raw()), Some(piece));
+                assert_eq!(PieceKind::try_from(kind as u8), Ok(kind));
+                assert_eq!(PieceColor::try_from(color as u8), Ok(color));
+            }
+        }
+        // A kind of 6 or 7, or a stray bit in the unused middle of the byte.
+        assert_eq!(Piece::from_raw(0b0000_0110), None);
+        assert_eq!(Piece::from_raw(0b1000_0111), None);
+        assert_eq!(Piece::from_raw(0b0001_0001), None);
+        assert_eq!(Piece::try_from(0b0000_0111u8), Err(InvalidPieceByte(7)));
+        assert_eq!(PieceKind::try_from(6), Err(InvalidPieceByte(6)));
+        assert_eq!(PieceColor::try_from(2), Err(InvalidPieceByte(2)));
+    }
+
+    #[test]
+    fn test_chess_types_hash_and_sort() {
+        use std::collections::{HashMap, HashSet};
+
+        let mut counts: HashMap<Piece, u32> = HashMap::new();
+        let board = Board::new();
+        for sq in Square::all() {
+            if let Some(piece) = board.piece_at(sq) {
+                *counts.entry(piece).or_default() += 1;
+            }
+        }
+        assert_eq!(counts[&Piece::new(PieceKind::Pawn, PieceColor::Black)], 8);
+        assert_eq!(counts[&Piece::new(PieceKind::King, PieceColor::White)], 1);
+
+        let white_queen = Piece::new(PieceKind::Queen, PieceColor::White);
+        assert!(white_queen < Piece::new(PieceKind::Pawn, PieceColor::Black));
+        assert!(PieceKind::Pawn < PieceKind::King && PieceColor::White < PieceColor::Black);
+        assert!(square("h1") < square("a2"));
+
+        // Sorting orders by from-square, then to-square, then promotion.
+        let mut moves = [uci("g1f3"), uci("a7a8q"), uci("b1c3"), uci("a7a8n"), uci("b1a3")];
+        moves.sort();
+        let sorted: Vec<String> = moves.iter().map(Move::to_uci).collect();
+        assert_eq!(sorted, ["b1a3", "b1c3", "g1f3", "a7a8n", "a7a8q"]);
+
+        let unique: HashSet<Move> = board.legal_moves().into_iter().chain(board.legal_moves()).collect();
+        assert_eq!(unique.len(), 20);
+    }
+
+    #[test]
+    fn test_serde_json_forms() {
+        let board = Board::from_fen("4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1").unwrap();
+        assert_eq!(serde_json::to_value(&board).unwrap(), json!("4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1"));
+        let back: Board = serde_json::from_value(json!("4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1")).unwrap();
+        assert!(back == board);
+        assert!(serde_json::from_value::<Board>(json!("not a fen")).is_err());
+
+        let knight = Piece::new(PieceKind::Knight, PieceColor::White);
+        assert_eq!(serde_json::to_value(knight).unwrap(), json!("N"));
+        assert_eq!(serde_json::from_value::<Piece>(json!("q")).unwrap(), Piece::new(PieceKind::Queen, PieceColor::Black));
+        assert_eq!(serde_json::to_value(square("e4")).unwrap(), json!("e4"));
+        assert_eq!(serde_json::to_value(PieceKind::Knight).unwrap(), json!("knight"));
+        assert_eq!(serde_json::to_value(PieceColor::Black).unwrap(), json!("black"));
+
+        assert_eq!(serde_json::to_value(GameResult::Draw).unwrap(), json!("1/2-1/2"));
+        assert_eq!(serde_json::to_value(Termination::DeadPosition).unwrap(), json!("dead_position"));
+        assert_eq!(serde_json::to_value(GameStatus::Ongoing).unwrap(), json!("ongoing"));
+        assert_eq!(
+            serde_json::to_value(GameStatus::Checkmate(PieceColor::White)).unwrap(),
+            json!({ "checkmate": "white" })
+        );
+        assert_eq!(serde_json::to_value(GameMode::Analysis).unwrap(), json!("analysis"));
+
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let mut saved = serde_json::to_value(&game).unwrap();
+        // When the moves were played varies from run to run.
+        let played_at = saved.as_object_mut().unwrap().remove("played_at_ms").unwrap();
+        assert_eq!(played_at.as_array().unwrap().len(), 4);
+        assert_eq!(
+            saved,
+            json!({
+                "start": START_FEN,
+                "moves": ["f2f3", "e7e5", "g2g4", "d8h4"],
+                "mode": "standard",
+                "auto_queen": false,
+                "rated": false,
+                "players": [null, null],
+                "result": null,
+                "termination": null,
+                "variant": "standard",
+                "start_fen": null,
+                "time_taken_ms": [null, null, null, null],
+                "clock_remaining_ms": [null, null, null, null],
+            })
+        );
+        let loaded: Game = serde_json::from_value(saved).unwrap();
+        assert_eq!(loaded.result(), Some(GameResult::BlackWins));
+        assert_eq!(loaded.history()[3].san(), "Qh4#");
+
+        // Loading replays the moves, so an illegal one is refused.
+        let tampered = json!({ "start": START_FEN, "moves": ["e2e5"], "mode": "standard", "auto_queen": false });
+        assert!(serde_json::from_value::<Game>(tampered).is_err());
+    }
+
+    #[test]
+    fn test_serde_binary_forms() {
+        let board = Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 3 20").unwrap();
+        let bytes = bincode::serialize(&board).unwrap();
+        let back: Board = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.to_fen(), board.to_fen());
+
+        // Binary formats store the packed forms, not strings.
+        assert_eq!(bincode::serialize(&uci("e7e8q")).unwrap(), uci("e7e8q").to_raw().to_le_bytes());
+        assert_eq!(bincode::serialize(&square("h8")).unwrap(), [63]);
+        let rook = Piece::new(PieceKind::Rook, PieceColor::Black);
+        assert_eq!(bincode::deserialize::<Piece>(&bincode::serialize(&rook).unwrap()).unwrap(), rook);
+        assert!(bincode::deserialize::<Piece>(&[0b0000_0111]).is_err());
+        assert!(bincode::deserialize::<Move>(&0xF000u16.to_le_bytes()).is_err());
+
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+        let loaded: Game = bincode::deserialize(&bincode::serialize(&game).unwrap()).unwrap();
+        assert_eq!(loaded.uci_history(), game.uci_history());
+        assert_eq!(loaded.current_fen(), game.current_fen());
+    }
+
+    #[test]
+    fn test_validate_rejects_impossible_positions() {
+        let check = |fen: &str| Board::from_fen(fen).unwrap().validate();
+        assert_eq!(check(START_FEN), Ok(()));
+        assert_eq!(check("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"), Ok(()));
+
+        assert_eq!(
+            check("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(PositionError::KingCount { color: PieceColor::Black, count: 0 })
+        );
+        assert_eq!(
+            check("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            Err(PositionError::KingCount { color: PieceColor::White, count: 2 })
+        );
+        assert_eq!(check("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"), Err(PositionError::PawnOnBackRank(square("h8"))));
+        // White to move while Black's king is attacked by the e1 rook.
+        assert_eq!(check("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), Err(PositionError::OpponentInCheck));
+        assert_eq!(
+            check("4k3/8/8/8/8/8/8/4K3 w K - 0 1"),
+            Err(PositionError::CastlingWithoutPieces(CastlingRights::WHITE_KINGSIDE))
+        );
+        // e6 can't be an en passant square with White to move, and e3 needs
+        // a white pawn on e4.
+        assert_eq!(check("4k3/8/8/8/4P3/8/8/4K3 w - e6 0 1"), Err(PositionError::ImpossibleEnPassant(square("e6"))));
+        assert_eq!(check("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"), Err(PositionError::ImpossibleEnPassant(square("e3"))));
+        assert_eq!(
+            PositionError::KingCount { color: PieceColor::White, count: 2 }.to_string(),
+            "White has 2 kings, expected exactly one"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_endpoints_reject_invalid_positions() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        for uri in ["/analyze", "/games", "/puzzles/check"] {
+            let req = TestRequest::post()
+                .uri(uri)
+                .set_json(json!({ "fen": "4k3/8/8/8/8/8/8/3KK3 w - - 0 1", "move": "d1d2" }))
+                .to_request();
+            assert_eq!(call_service(&app, req).await.status(), 400, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn test_board_builder() {
+        let white_king = Piece::new(PieceKind::King, PieceColor::White);
+        let black_king = Piece::new(PieceKind::King, PieceColor::Black);
+        let white_rook = Piece::new(PieceKind::Rook, PieceColor::White);
+
+        let mut builder = BoardBuilder::new();
+        builder.place(square("e1"), white_king).place(square("h1"), white_rook);
+        // Only one king so far.
+        assert!(matches!(builder.build(), Err(PositionError::KingCount { .. })));
+
+        builder
+            .place(square("e8"), black_king)
+            .place(square("a1"), white_rook)
+            .remove(square("a1"))
+            .side_to_move(PieceColor::Black)
+            .castling_rights(CastlingRights::WHITE_KINGSIDE);
+        let board = builder.build().unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+
+        // Clearing keeps the castling rights, which no longer fit.
+        let mut from_start = BoardBuilder::from_board(Board::new());
+        from_start.clear().place(square("a1"), white_king).place(square("h8"), black_king);
+        assert!(matches!(from_start.build(), Err(PositionError::CastlingWithoutPieces(_))));
+        from_start.castling_rights(CastlingRights::NONE);
+        assert_eq!(from_start.build().unwrap().to_fen(), "7k/8/8/8/8/8/8/K7 w - - 0 1");
+    }
+
+    #[actix_web::test]
+    async fn test_position_endpoint() {
+        let app = init_service(App::new().configure(routes::config)).await;
+        let req = TestRequest::post()
+            .uri("/positions")
+            .set_json(json!({
+                "pieces": { "e1": "K", "h1": "R", "e8": "k", "d7": "p" },
+                "side_to_move": "black",
+                "castling": "K",
+            }))
+            .to_request();
+        let position: PositionResponse = call_and_read_body_json(&app, req).await;
+        assert_eq!(position.fen, "4k3/3p4/8/8/8/8/8/4K2R b K - 0 1");
+
+        // No black king, and a castling right with no rook behind it.
+        for body in [
+            json!({ "pieces": { "e1": "K" } }),
+            json!({ "pieces": { "e1": "K", "e8": "k" }, "castling": "Q" }),
+            json!({ "pieces": { "e1": "K", "e8": "k" }, "castling": "X" }),
+        ] {
+            let req = TestRequest::post().uri("/positions").set_json(body).to_request();
+            assert_eq!(call_service(&app, req).await.status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_perft_divide_sums_to_perft() {
+        // Kiwipete one ply deeper than above, and position 6 of the usual
+        // perft suite: a quiet middlegame with both sides castled.
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&kiwipete, 3), 97_862);
+        let position_6 =
+            Board::from_fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10").unwrap();
+        assert_eq!(perft(&position_6, 3), 89_890);
+
+        // Divide splits the count by root move, so a mismatch can be chased
+        // down one move at a time.
+        let divide = perft_divide(&kiwipete, 2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|&(_, nodes)| nodes).sum::<u64>(), perft(&kiwipete, 2));
+        let castle = divide.iter().find(|(mv, _)| mv.to_uci() == "e1g1").unwrap();
+        assert_eq!(castle.1, 43);
+    }
+
+    #[test]
+    fn test_incremental_state_matches_recomputation() {
+        // After every move and every take-back, the running hash, material
+        // and piece-square totals must equal those of the same position
+        // built up from scratch.
+        fn check(board: &Board) {
+            let fresh = Board::from_fen(&board.to_fen()).unwrap();
+            assert_eq!(board.zobrist_hash(), fresh.zobrist_hash(), "{}", board.to_fen());
+            for color in [PieceColor::White, PieceColor::Black] {
+                assert_eq!(board.material(color), fresh.material(color));
+                assert_eq!(board.positional_score(color), fresh.positional_score(color));
+            }
+        }
+
+        // Kiwipete has castling, en passant, promotions and captures within
+        // two plies.
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let original_hash = board.zobrist_hash();
+        for mv in board.legal_moves() {
+            let undo = board.make_move(mv);
+            check(&board);
+            for reply in board.legal_moves() {
+                let reply_undo = board.make_move(reply);
+                check(&board);
+                board.unmake_move(reply, reply_undo);
+            }
+            board.unmake_move(mv, undo);
+            assert_eq!(board.zobrist_hash(), original_hash);
+        }
+
+        let start = Board::new();
+        assert_eq!(start.material(PieceColor::White), 8 * 100 + 2 * 320 + 2 * 330 + 2 * 500 + 900);
+        assert_eq!(start.positional_score(PieceColor::White), start.positional_score(PieceColor::Black));
+        assert_eq!(evaluate(&start), 0);
+        // A knight on the rim scores less than one in the centre.
+        let rim = Board::from_fen("4k3/8/8/8/N7/8/8/4K3 w - - 0 1").unwrap();
+        let centre = Board::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&centre) > evaluate(&rim));
+    }
+
+    #[test]
+    fn test_evaluation_terms() {
+        let eval = |fen: &str| evaluate(&Board::from_fen(fen).unwrap());
+        assert_eq!(game_phase(&Board::new()), MAX_PHASE);
+        assert_eq!(game_phase(&Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap()), 0);
+
+        // The score is the same for either side, just negated.
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        assert_eq!(eval(fen), -eval(&fen.replace(" w ", " b ")));
+
+        // Doubled and isolated pawns are worse than connected ones.
+        assert!(eval("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1") > eval("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1"));
+        // A passed pawn is worth more the further it has come.
+        assert!(eval("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1") > eval("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1"));
+        // With the pieces off, the king belongs in the centre...
+        assert!(eval("4k3/p7/8/8/3K4/8/P7/8 w - - 0 1") > eval("4k3/p7/8/8/8/8/P7/K7 w - - 0 1"));
+        // ...but in the middlegame behind its pawns.
+        let sheltered = "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 7";
+        let exposed = "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P1PP/2NP1N2/PPP2P2/R1BQ1RK1 w - - 0 7";
+        assert!(eval(sheltered) > eval(exposed));
+    }
+
+    #[test]
+    fn test_attack_map_counts_attackers() {
+        // White: queen d1 behind a rook on d2, king e1, knight f3.
+        // Black: king e8, pawn e5.
+        let board = Board::from_fen("4k3/8/8/4p3/8/5N2/3R4/3QK3 w - - 0 1").unwrap();
+        let white = board.attack_map(PieceColor::White);
+        let black = board.attack_map(PieceColor::Black);
+
+        // The queen's d-file ray stops at the rook, so d4 is hit by the
+        // rook and the knight only.
+        assert_eq!(white[square("e5").index()], 1);
+        assert_eq!(white[square("d4").index()], 2);
+        // Own pieces count as defended: d2 by queen, king and knight.
+        assert_eq!(white[square("d2").index()], 3);
+        assert_eq!(white[square("e2").index()], 3);
+        assert_eq!(black[square("d4").index()], 1);
+        assert_eq!(black[square("e4").index()], 0);
+        assert_eq!(board.attacks_from(square("f3")).count(), 8);
+        assert!(board.attacks_from(square("a4")).is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_attacks_endpoint() {
+        let store = web::Data::new(GameStore::new());
+        let id = store.create(Game::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::get().uri(&format!("/games/{}/attacks", id)).to_request();
+        let attacks: AttacksResponse = call_and_read_body_json(&app, req).await;
+        assert_eq!(attacks.white.len(), 64);
+        // f3 is covered by the g1 knight and the e2 and g2 pawns.
+        assert_eq!(attacks.white[square("f3").index()], 3);
+        assert_eq!(attacks.black[square("f6").index()], 3);
+        assert_eq!(attacks.white[square("e4").index()], 0);
+
+        let req = TestRequest::get().uri("/games/999/attacks").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_square_moves_endpoint() {
+        let store = web::Data::new(GameStore::new());
+        // White can castle short and promote on b8.
+        let id = store.create(Game::from_fen("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::get().uri(&format!("/games/{}/moves/e1", id)).to_request();
+        let moves: Vec<MoveDetail> = call_and_read_body_json(&app, req).await;
+        let castle = moves.iter().find(|mv| mv.to == square("g1")).unwrap();
+        assert_eq!(castle.flag, MoveFlag::CastleKingside);
+        assert_eq!(castle.san, "O-O");
+
+        let req = TestRequest::get().uri(&format!("/games/{}/moves/b7", id)).to_request();
+        let moves: Vec<MoveDetail> = call_and_read_body_json(&app, req).await;
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|mv| mv.to == square("b8") && mv.promotion.is_some()));
+
+        // Black's pieces have no moves while it is White's turn.
+        let req = TestRequest::get().uri(&format!("/games/{}/moves/e8", id)).to_request();
+        let moves: Vec<MoveDetail> = call_and_read_body_json(&app, req).await;
+        assert!(moves.is_empty());
+
+        let req = TestRequest::get().uri(&format!("/games/{}/moves/z9", id)).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404);
+        let req = TestRequest::get().uri("/games/999/moves/e2").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 404);
+    }
+
+    #[test]
+    fn test_rejected_moves_report_their_reason() {
+        let mut game = Game::from_fen("4k3/1P6/8/8/4r3/8/8/R3K3 w Q - 0 1").unwrap();
+        let reason = |game: &mut Game, text: &str| {
+            let error = game.make_move(uci(text)).unwrap_err();
+            let json: serde_json::Value = serde_json::from_str(&ServerMessage::move_error(&error).to_json()).unwrap();
+            assert_eq!(json["code"], "illegal_move");
+            json["reason"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(reason(&mut game, "a1b2"), "piece_cannot_reach");
+        assert_eq!(reason(&mut game, "e1e2"), "leaves_king_in_check");
+        assert_eq!(reason(&mut game, "b7b8"), "missing_promotion");
+        assert_eq!(reason(&mut game, "a1e1"), "occupied_by_own_piece");
+        assert_eq!(reason(&mut game, "e1c1"), "castling_not_allowed");
+        assert_eq!(reason(&mut game, "c3c4"), "no_piece_on_square");
+    }
+
+    #[actix_web::test]
+    async fn test_game_players_are_named() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::post().uri("/games").set_json(json!({ "white": "Ada" })).to_request();
+        let created: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(created.white_player.as_deref(), Some("Ada"));
+        assert_eq!(created.black_player, None);
+
+        // Names survive a reset and a save and load.
+        let mut game = store.with_game(created.id, |game| game.clone()).unwrap();
+        game.set_player(PieceColor::Black, Some("Grace".to_string()));
+        game.make_move(uci("e2e4")).unwrap();
+        game.reset();
+        assert_eq!(game.player(PieceColor::White), Some("Ada"));
+        let loaded: Game = serde_json::from_value(serde_json::to_value(&game).unwrap()).unwrap();
+        assert_eq!(loaded.player(PieceColor::Black), Some("Grace"));
+    }
+
+    #[test]
+    fn test_games_end_by_resignation_agreement_and_abort() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4"]);
+        game.resign(PieceColor::White).unwrap();
+        assert_eq!(game.result(), Some(GameResult::BlackWins));
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::Black, Termination::Resignation));
+        assert_eq!(game.make_move(uci("e7e5")), Err(MoveError::GameOver));
+        assert_eq!(game.agree_draw(), Err(GameError::GameOver));
+
+        // A resignation can't be read off the moves, so it is saved with them.
+        let loaded: Game = serde_json::from_value(serde_json::to_value(&game).unwrap()).unwrap();
+        assert_eq!(loaded.termination(), Some(Termination::Resignation));
+        assert_eq!(loaded.result(), Some(GameResult::BlackWins));
+
+        let mut game = Game::new();
+        game.agree_draw().unwrap();
+        assert_eq!(game.status(), GameStatus::Draw(Termination::Agreement));
+
+        // Aborting is only possible before both sides have moved, and leaves
+        // the game without a result.
+        let mut game = Game::new();
+        play(&mut game, &["e2e4"]);
+        game.abort().unwrap();
+        assert!(game.is_over());
+        assert_eq!(game.result(), None);
+        assert_eq!(game.status(), GameStatus::Aborted);
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5"]);
+        assert_eq!(game.abort(), Err(GameError::TooLateToAbort));
+    }
+
+    #[test]
+    fn test_draws_by_repetition_and_fifty_moves_are_claimed() {
+        let mut game = Game::new();
+        assert_eq!(
+            game.claim_draw(DrawClaim::ThreefoldRepetition),
+            Err(GameError::DrawClaimRejected(DrawClaim::ThreefoldRepetition))
+        );
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]);
+        // Repetition doesn't end the game by itself.
+        assert!(!game.is_over());
+        assert!(game.claim_draw(DrawClaim::FiftyMoveRule).is_err());
+        game.claim_draw(DrawClaim::ThreefoldRepetition).unwrap();
+        assert_eq!(game.status(), GameStatus::Draw(Termination::ThreefoldRepetition));
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        game.claim_draw(DrawClaim::FiftyMoveRule).unwrap();
+        assert_eq!(game.termination(), Some(Termination::FiftyMoveRule));
+        assert_eq!(Termination::FiftyMoveRule.pgn_tag(), "normal");
+    }
+
+    #[test]
+    fn test_flag_fall_loses_on_time() {
+        let mut game = Game::new();
+        game.set_clock(Some(Clock::new(Duration::from_secs(10), Duration::ZERO)));
+        let start = Instant::now();
+        game.make_move_at(uci("e2e4"), start).unwrap();
+        assert!(!game.check_time(start + Duration::from_secs(9)));
+
+        // Black's move comes too late and doesn't count.
+        let late = start + Duration::from_secs(11);
+        assert_eq!(game.make_move_at(uci("e7e5"), late), Err(MoveError::GameOver));
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::Timeout));
+        assert_eq!(game.termination().unwrap().pgn_tag(), "time forfeit");
+        assert_eq!(game.ply(), 1);
+    }
+
+    #[test]
+    fn test_client_resigns() {
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "resign", "game_id": 3, "color": "black" })).unwrap();
+        let ClientMessage::Resign { game_id, color } = message else {
+            panic!("expected a resign message");
+        };
+        assert_eq!((game_id, color), (3, PieceColor::Black));
+
+        let store = GameStore::new();
+        let id = store.create(Game::new());
+        store.with_game(id, |game| game.resign(color)).unwrap().unwrap();
+        let record = store.record(id).unwrap();
+        assert_eq!(record.result, "1-0");
+        assert_eq!(record.reason, "resignation");
+
+        let error = store.with_game(id, |game| game.resign(color)).unwrap().unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&ServerMessage::game_error(&error).to_json()).unwrap();
+        assert_eq!(json["code"], "not_allowed");
+    }
+
+    #[test]
+    fn test_undo_redo_and_jump_to_ply() {
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(game.result(), Some(GameResult::BlackWins));
+
+        // Taking back the mate reopens the game.
+        assert_eq!(game.undo().map(|mv| mv.to_uci()).as_deref(), Some("d8h4"));
+        assert!(!game.is_over());
+        assert_eq!(game.undo().map(|mv| mv.to_uci()).as_deref(), Some("g2g4"));
+        assert_eq!(game.ply(), 2);
+
+        assert_eq!(game.redo().map(|mv| mv.to_uci()).as_deref(), Some("g2g4"));
+        game.jump_to_ply(4).unwrap();
+        assert_eq!(game.status(), GameStatus::Checkmate(PieceColor::Black));
+        assert_eq!(game.redo(), None);
+
+        game.jump_to_ply(0).unwrap();
+        assert_eq!(game.current_fen(), START_FEN);
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.jump_to_ply(5), Err(GameError::PlyOutOfRange { ply: 5, max: 4 }));
+
+        // Playing a different move starts a new line and forgets the old one.
+        game.jump_to_ply(1).unwrap();
+        play(&mut game, &["e7e6"]);
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.uci_history(), ["f2f3", "e7e6"]);
+        assert_eq!(game.jump_to_ply(3), Err(GameError::PlyOutOfRange { ply: 3, max: 2 }));
+
+        // A resignation isn't taken back with the move before it.
+        game.jump_to_ply(1).unwrap();
+        game.resign(PieceColor::White).unwrap();
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.jump_to_ply(0), Err(GameError::GameOver));
+        assert_eq!(game.ply(), 1);
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::Black, Termination::Resignation));
+    }
+
+    #[test]
+    fn test_draw_offers() {
+        let mut game = Game::new();
+        assert_eq!(game.respond_draw(true), Err(GameError::NoDrawOffer));
+
+        // Declined outright.
+        game.offer_draw(PieceColor::White).unwrap();
+        game.respond_draw(false).unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        // The offerer may still move; the opponent moving instead declines.
+        game.offer_draw(PieceColor::White).unwrap();
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.draw_offer(), Some(PieceColor::White));
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.draw_offer(), None);
+
+        game.offer_draw(PieceColor::Black).unwrap();
+        let state = GameState::from_game(1, &game);
+        assert_eq!(serde_json::to_value(&state).unwrap()["draw_offer"], "black");
+        game.respond_draw(true).unwrap();
+        assert_eq!(game.status(), GameStatus::Draw(Termination::Agreement));
+        assert_eq!(game.offer_draw(PieceColor::White), Err(GameError::GameOver));
+
+        // Two offers meet in the middle.
+        let mut game = Game::new();
+        game.offer_draw(PieceColor::White).unwrap();
+        game.offer_draw(PieceColor::Black).unwrap();
+        assert_eq!(game.termination(), Some(Termination::Agreement));
+
+        let message: ClientMessage =
+            serde_json::from_value(json!({ "type": "respond_draw", "game_id": 2, "accept": false })).unwrap();
+        assert!(matches!(message, ClientMessage::RespondDraw { game_id: 2, accept: false }));
+    }
+
+    #[test]
+    fn test_fivefold_repetition_ends_the_game() {
+        let mut game = Game::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        play(&mut game, &shuffle);
+        assert!(!game.is_over());
+        play(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 5);
+        assert_eq!(game.status(), GameStatus::Draw(Termination::FivefoldRepetition));
+        assert_eq!(game.claim_draw(DrawClaim::ThreefoldRepetition), Err(GameError::GameOver));
+
+        let message: ClientMessage = serde_json::from_value(json!({
+            "type": "claim_draw", "game_id": 4, "claim": "fifty_move_rule"
+        }))
+        .unwrap();
+        assert!(matches!(message, ClientMessage::ClaimDraw { game_id: 4, claim: DrawClaim::FiftyMoveRule }));
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        let squares = |names: &[&str]| names.iter().map(|name| square(name)).collect::<Bitboard>();
+        let board = Board::from_fen("4k3/p7/3p3P/4P3/2P3p1/2P5/P4P2/4K3 w - - 0 1").unwrap();
+
+        let white = board.pawn_structure(PieceColor::White);
+        assert_eq!(white.doubled, squares(&["c3", "c4"]));
+        assert_eq!(white.isolated, squares(&["a2", "c3", "c4", "h6"]));
+        // f2's neighbour on e5 is far ahead, and g4 stops it from catching up.
+        assert_eq!(white.backward, squares(&["f2"]));
+        assert_eq!(white.passed, squares(&["h6"]));
+
+        let black = board.pawn_structure(PieceColor::Black);
+        assert_eq!(black.isolated, squares(&["a7", "d6", "g4"]));
+        assert!(black.passed.is_empty());
+        assert!(black.backward.is_empty());
+
+        assert_eq!(Board::new().pawn_structure(PieceColor::White), PawnStructure::default());
+    }
+
+    #[test]
+    fn test_game_events_reach_observers() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let mut game = Game::new();
+        game.set_clock(Some(Clock::new(Duration::from_secs(40), Duration::ZERO)));
+        game.add_observer(Arc::new(move |event: &GameEvent| seen.lock().unwrap().push(event.clone())));
+
+        let start = Instant::now();
+        game.make_move_at(uci("f2f3"), start).unwrap();
+        game.offer_draw(PieceColor::White).unwrap();
+        game.make_move_at(uci("e7e5"), start + Duration::from_secs(15)).unwrap();
+        game.make_move_at(uci("g2g4"), start + Duration::from_secs(16)).unwrap();
+        game.make_move_at(uci("d8h4"), start + Duration::from_secs(17)).unwrap();
+
+        let events = events.lock().unwrap();
+        // Moves are compared by UCI, since the event carries the move as
+        // played, flags and all.
+        let played = |event: &GameEvent, color, text: &str, san_text: &str| {
+            matches!(event, GameEvent::MovePlayed { color: c, mv, san } if *c == color && mv.to_uci() == text && san == san_text)
+        };
+        assert!(played(&events[0], PieceColor::White, "f2f3", "f3"));
+        assert_eq!(events[1], GameEvent::DrawOffered { color: PieceColor::White });
+        assert!(played(&events[2], PieceColor::Black, "e7e5", "e5"));
+        // Black spent 15 of their 40 seconds.
+        assert_eq!(events[3], GameEvent::ClockLow { color: PieceColor::Black, remaining_ms: 25_000 });
+        assert!(played(&events[4], PieceColor::White, "g2g4", "g4"));
+        assert!(played(&events[5], PieceColor::Black, "d8h4", "Qh4#"));
+        assert_eq!(
+            events[6..],
+            [
+                GameEvent::CheckGiven { color: PieceColor::White },
+                GameEvent::GameEnded { result: Some(GameResult::BlackWins), termination: Termination::Checkmate },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&events[1]).unwrap(),
+            json!({ "type": "draw_offered", "color": "white" })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_move_timeline() {
+        let mut game = Game::new();
+        game.set_clock(Some(Clock::new(Duration::from_secs(300), Duration::from_secs(2))));
+        let start = Instant::now();
+        game.make_move_at(uci("e2e4"), start).unwrap();
+        game.make_move_at(uci("e7e5"), start + Duration::from_millis(3500)).unwrap();
+        assert_eq!(game.history()[1].clock_remaining(), Some(Duration::from_millis(298_500)));
+        assert_eq!(game.history()[1].clock_comment().as_deref(), Some("[%clk 0:04:58]"));
+
+        // A saved game keeps every move's timings.
+        let loaded: Game = serde_json::from_value(serde_json::to_value(&game).unwrap()).unwrap();
+        let millis = |time: std::time::SystemTime| time.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
+        for (before, after) in game.history().iter().zip(loaded.history()) {
+            assert_eq!(millis(after.played_at()), millis(before.played_at()));
+            assert_eq!(after.time_taken(), before.time_taken());
+            assert_eq!(after.clock_remaining(), before.clock_remaining());
+        }
+        assert_eq!(loaded.history()[1].time_taken(), Some(Duration::from_millis(3500)));
+        assert!(loaded.to_pgn().contains("{[%clk 0:04:58]}"));
+
+        let store = web::Data::new(GameStore::new());
+        let id = store.create(game);
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+        let req = TestRequest::get().uri(&format!("/games/{}/timeline", id)).to_request();
+        let timeline: Vec<MoveTiming> = call_and_read_body_json(&app, req).await;
+        assert_eq!(timeline.len(), 2);
+        assert_eq!((timeline[1].ply, timeline[1].san.as_str()), (2, "e5"));
+        assert_eq!(timeline[1].think_ms, Some(3500));
+        assert_eq!(timeline[1].clock_ms, Some(298_500));
+        assert!(timeline[0].played_at_ms > 0 && timeline[0].played_at_ms <= timeline[1].played_at_ms);
+
+        // Without a clock only the timestamps are known.
+        let id = store.create({
+            let mut game = Game::new();
+            play(&mut game, &["d2d4"]);
+            game
+        });
+        let req = TestRequest::get().uri(&format!("/games/{}/timeline", id)).to_request();
+        let timeline: Vec<MoveTiming> = call_and_read_body_json(&app, req).await;
+        assert_eq!((timeline[0].think_ms, timeline[0].clock_ms), (None, None));
+        assert_eq!(store.with_game(id, |game| game.history()[0].clock_comment()), Some(None));
+    }
+
+    #[test]
+    fn test_pgn_export() {
+        let mut game = Game::new();
+        game.set_player(PieceColor::White, Some("Ada \"The Countess\"".to_string()));
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let pgn = game.to_pgn();
+        let (tags, movetext) = pgn.split_once("\n\n").unwrap();
+        let tags: Vec<&str> = tags.lines().collect();
+        assert_eq!(tags[0], "[Event \"?\"]");
+        assert!(tags[2].starts_with("[Date \"") && tags[2].len() == "[Date \"2024.01.01\"]".len());
+        assert_eq!(tags[4], "[White \"Ada \\\"The Countess\\\"\"]");
+        assert_eq!(tags[5], "[Black \"?\"]");
+        assert_eq!(tags[6], "[Result \"0-1\"]");
+        assert_eq!(tags[7], "[Termination \"normal\"]");
+        assert_eq!(movetext, "1. f3 e5 2. g4 Qh4# 0-1\n");
+
+        // A game from a set-up position with Black to move, still going.
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 30").unwrap();
+        play(&mut game, &["e8d7", "e2e4"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]"));
+        assert!(pgn.ends_with("\n\n30... Kd7 31. e4 *\n"));
+        assert!(Game::new().to_pgn().contains("[Date \"????.??.??\"]"));
+    }
+
+    #[actix_web::test]
+    async fn test_pgn_download_wraps_long_games() {
+        let mut game = Game::new();
+        game.set_clock(Some(Clock::new(Duration::from_secs(600), Duration::ZERO)));
+        let start = Instant::now();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for (i, mv) in shuffle.iter().cycle().take(16).enumerate() {
+            game.make_move_at(uci(mv), start + Duration::from_secs(i as u64)).unwrap();
+        }
+        let store = web::Data::new(GameStore::new());
+        let id = store.create(game);
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::get().uri(&format!("/games/{}/pgn", id)).to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.headers().get("content-type").unwrap(), "application/x-chess-pgn");
+        let body = actix_web::test::read_body(response).await;
+        let pgn = std::str::from_utf8(&body).unwrap();
+        assert!(pgn.contains("1. Nf3 {[%clk 0:10:00]} Nf6 {[%clk 0:09:59]}"));
+        let movetext = pgn.split_once("\n\n").unwrap().1;
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= 80));
+        assert!(movetext.trim_end().ends_with("Ng8 {[%clk 0:09:52]} 1/2-1/2"));
+    }
+
+    #[test]
+    fn test_pgn_import() {
+        let pgn = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[White "Ada"]
+[Black "?"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {developing} Nc6 $1 3. Bb5!? a6 (3... Nf6 4. O-O) 4. Ba4
+; a line comment
+Nf6 5.O-O 1-0
+"#;
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.uci_history(), ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1"]);
+        assert_eq!(game.player(PieceColor::White), Some("Ada"));
+        assert_eq!(game.player(PieceColor::Black), None);
+        // No mate on the board, so Black must have resigned.
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::Resignation));
+
+        // Exported games read back the same.
+        let mut original = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 30").unwrap();
+        play(&mut original, &["e8d7", "e2e4"]);
+        let loaded = Game::from_pgn(&original.to_pgn()).unwrap();
+        assert_eq!(loaded.current_fen(), original.current_fen());
+        assert_eq!(loaded.result(), None);
+
+        let timeout = Game::from_pgn("[Termination \"time forfeit\"]\n\n1. d4 0-1").unwrap();
+        assert_eq!(timeout.status(), GameStatus::Won(PieceColor::Black, Termination::Timeout));
+    }
+
+    #[test]
+    fn test_pgn_import_errors_say_where() {
+        let error = Game::from_pgn("[White \"Ada\"]\n\n1. e4 e5\n2. Ke3 *").err().unwrap();
+        assert_eq!((error.line, error.column), (4, 4));
+        assert!(matches!(error.kind, PgnErrorKind::IllegalMove { ref san, .. } if san == "Ke3"));
+        assert_eq!(error.to_string(), "line 4, column 4: can't play Ke3: 'Ke3' is not a valid move");
+
+        let unclosed = Game::from_pgn("1. e4 {never closed").err().unwrap();
+        assert_eq!(unclosed, PgnError { line: 1, column: 7, kind: PgnErrorKind::UnterminatedComment });
+        assert_eq!(Game::from_pgn("[White Ada]").err().unwrap().kind, PgnErrorKind::MalformedTag);
+        assert_eq!(Game::from_pgn("1. e4 (1. d4").err().unwrap().kind, PgnErrorKind::UnbalancedVariation);
+        assert!(matches!(Game::from_pgn("[FEN \"nonsense\"]").err().unwrap().kind, PgnErrorKind::InvalidFen(_)));
+    }
+
+    #[actix_web::test]
+    async fn test_pgn_upload() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::post().uri("/games/import").set_payload("1. f3 e5 2. g4 Qh4# 0-1").to_request();
+        let state: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(state.moves.len(), 4);
+        assert_eq!(state.reason.as_deref(), Some("checkmate"));
+
+        let req = TestRequest::post().uri("/games/import").set_payload("1. e5").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+    }
+
+    #[test]
+    fn test_pgn_annotations_round_trip() {
+        let text = r#"[Event "Annotated"]
+
+{An old line.} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Ruy Lopez.} a6 (3... Nf6 4. O-O
+(4. d3 Bc5) 4... Nxe4 $5) 4. Ba4?! Nf6 *
+"#;
+        let pgn = PgnGame::parse(text).unwrap();
+        assert_eq!(pgn.tag("Event"), Some("Annotated"));
+        assert_eq!(pgn.moves.len(), 8);
+        assert_eq!(pgn.moves[0].comments_before, ["An old line."]);
+        assert_eq!(pgn.moves[4].nags, [1]);
+        assert_eq!(pgn.moves[4].comments, ["The Ruy Lopez."]);
+        assert_eq!(pgn.moves[6].nags, [6]);
+
+        // The variation replaces 3... a6 and has one of its own.
+        let variation = &pgn.moves[5].variations[0];
+        let sans: Vec<&str> = variation.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, ["Nf6", "O-O", "Nxe4"]);
+        assert_eq!(variation[1].variations[0][1].san, "Bc5");
+        assert_eq!(variation[2].nags, [5]);
+
+        let written = pgn.to_pgn();
+        let movetext = written.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(movetext.contains("3. Bb5 $1 {The Ruy Lopez.} a6 (3... Nf6 4. O-O (4. d3 Bc5) 4... Nxe4 $5) 4. Ba4 $6"));
+        assert!(PgnGame::parse(&written).unwrap() == pgn);
+
+        // Game only plays the main line.
+        let game = Game::from_pgn(text).unwrap();
+        assert_eq!(game.uci_history(), ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6"]);
+    }
+
+    #[test]
+    fn test_pgn_variations_are_checked() {
+        // Ke3 isn't legal after 1. e4 e5 either.
+        let error = PgnGame::parse("1. e4 e5 (1... c5 2. Ke3) 2. Nf3 *").err().unwrap();
+        assert_eq!((error.line, error.column), (1, 22));
+        assert!(matches!(error.kind, PgnErrorKind::IllegalMove { ref san, .. } if san == "Ke3"));
+
+        assert_eq!(PgnGame::parse("(1. e4) 1. d4").err().unwrap().kind, PgnErrorKind::UnbalancedVariation);
+        assert_eq!(PgnGame::parse("1. e4 e5 (1... c5").err().unwrap().kind, PgnErrorKind::UnbalancedVariation);
+    }
+
+    #[test]
+    fn test_game_tree_branches() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+        let mut tree = GameTree::from_game(&game);
+        let main = tree.main_line();
+        assert_eq!(main.len(), 3);
+
+        // Try 2. Nc3 instead of 2. Nf3, with a reply.
+        let after_e5 = main[1];
+        let nc3 = tree.add_move(after_e5, uci("b1c3")).unwrap();
+        let nf6 = tree.add_move(nc3, uci("g8f6")).unwrap();
+        assert_eq!(tree.node(after_e5).children(), [main[2], nc3]);
+        assert!(!tree.is_main_line(nf6));
+        // Playing a move again finds the existing node.
+        assert_eq!(tree.add_move(after_e5, uci("b1c3")).unwrap(), nc3);
+        assert!(tree.add_move(nc3, uci("e1e3")).is_err());
+
+        tree.node_mut(nc3).comments.push("The Vienna.".to_string());
+        tree.node_mut(nc3).nags.push(5);
+        tree.node_mut(nf6).eval = Some(20);
+
+        let vienna = tree.to_game(nf6);
+        assert_eq!(vienna.uci_history(), ["e2e4", "e7e5", "b1c3", "g8f6"]);
+        assert_eq!(tree.node(nf6).board().to_fen(), vienna.current_fen());
+
+        tree.promote(nc3);
+        assert!(tree.is_main_line(nf6));
+        assert_eq!(tree.to_game(*tree.main_line().last().unwrap()).uci_history(), vienna.uci_history());
+
+        // Round trip through PGN keeps the branch and its annotations.
+        let pgn = PgnGame { tags: Vec::new(), start: Board::new(), moves: tree.pgn_line(tree.root()), result: "*".to_string() };
+        let movetext = pgn.to_pgn().split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(movetext.contains("1. e4 e5 2. Nc3 $5 {The Vienna.} (2. Nf3) 2... Nf6 *"), "{}", movetext);
+        let back = GameTree::from_pgn_game(&PgnGame::parse(&movetext).unwrap());
+        assert_eq!(back.node(back.main_line()[2]).comments, ["The Vienna."]);
+        assert_eq!(back.node(back.main_line()[1]).children().len(), 2);
+
+        tree.remove(main[2]);
+        assert_eq!(tree.node(after_e5).children(), [nc3]);
+    }
+
+    #[test]
+    fn test_epd_records() {
+        let suite = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - bm Rg3; id "WAC.003";
+
+r1bqkb1r/pppp1ppp/2n2n2/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - am Qxf7+; ce -30; c0 "greedy; but wrong";
+8/8/8/8/8/8/8/k6K w - - bm Kh9;
+"#;
+        let records: Vec<_> = Epd::parse_suite(suite).collect();
+        assert_eq!(records.len(), 4);
+
+        let (line, wac1) = &records[0];
+        let wac1 = wac1.as_ref().unwrap();
+        assert_eq!(*line, 1);
+        assert_eq!(wac1.id.as_deref(), Some("WAC.001"));
+        assert_eq!(wac1.best_moves[0].to_uci(), "g3g6");
+        assert!(wac1.is_solved_by(&uci("g3g6")));
+        assert!(!wac1.is_solved_by(&uci("f6e8")));
+
+        let (line, greedy) = &records[2];
+        let greedy = greedy.as_ref().unwrap();
+        assert_eq!(*line, 4);
+        assert_eq!(greedy.centipawn_eval, Some(-30));
+        assert_eq!(greedy.operation("c0"), Some(&["greedy; but wrong".to_string()][..]));
+        assert!(!greedy.is_solved_by(&uci("h5f7")));
+        assert!(greedy.is_solved_by(&uci("d2d3")));
+        // Writing a record reads back the same.
+        assert_eq!(
+            greedy.to_string(),
+            r#"r1bqkb1r/pppp1ppp/2n2n2/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - am Qxf7+; ce -30; c0 "greedy; but wrong";"#
+        );
+        assert!(Epd::parse(&greedy.to_string()).unwrap() == *greedy);
+
+        let (line, bad) = &records[3];
+        assert_eq!(*line, 5);
+        assert!(matches!(bad, Err(EpdError::InvalidMove { san, .. }) if san == "Kh9"));
+
+        assert!(matches!(Epd::parse("8/8 w - -").err().unwrap(), EpdError::InvalidPosition(_)));
+        assert_eq!(Epd::parse("8/8/8/8/8/8/8/k6K w - - id \"x\"").err().unwrap(), EpdError::MissingSemicolon("id".to_string()));
+        assert_eq!(Epd::parse("8/8/8/8/8/8/8/k6K w - - ce lots;").err().unwrap().to_string(), "invalid operand 'lots' for ce");
+    }
+
+    #[test]
+    fn test_opening_classification() {
+        let moves = |ucis: &[&str]| -> Vec<Move> { ucis.iter().map(|text| uci(text)).collect() };
+        let openings = Openings::standard();
+
+        let ruy = openings.classify(&moves(&["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]));
+        assert!(ruy.in_theory);
+        assert_eq!(ruy.opening.map(|opening| (opening.eco, opening.name)), Some(("C60", "Ruy Lopez")));
+
+        // 3... d6 isn't in the table, so the game has left theory, still as
+        // a Ruy Lopez.
+        let left = openings.classify(&moves(&["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "d7d6", "e1g1"]));
+        assert!(!left.in_theory);
+        assert_eq!(left.opening.unwrap().eco, "C60");
+
+        // Between named lines the last name applies.
+        let sicilian = openings.classify(&moves(&["e2e4", "c7c5", "g1f3", "d7d6", "d2d4"]));
+        assert!(sicilian.in_theory);
+        assert_eq!(sicilian.opening.unwrap().name, "Sicilian Defense");
+
+        assert_eq!(openings.classify(&moves(&["h2h4"])).opening, None);
+        assert!(!openings.classify_game(&Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap()).in_theory);
+    }
+
+    #[actix_web::test]
+    async fn test_opening_is_announced_when_theory_ends() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let store = web::Data::new(GameStore::new());
+        let id = store.create(Game::new());
+        let server = actix_web::HttpServer::new(move || App::new().app_data(store.clone()).configure(routes::config))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address)).await.unwrap();
+        let (mut sink, mut stream) = socket.split();
+        let mut next_json = async || -> serde_json::Value {
+            match stream.next().await {
+                Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+                other => panic!("expected a text message, got {:?}", other),
+            }
+        };
+
+        sink.send(Message::Text(json!({ "type": "join", "game_id": id }).to_string())).await.unwrap();
+        assert_eq!(next_json().await["type"], "game_state");
+        for mv in ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "d7d6", "e1g1"] {
+            sink.send(Message::Text(json!({ "type": "move", "game_id": id, "uci": mv }).to_string())).await.unwrap();
+            assert_eq!(next_json().await["type"], "game_state");
+            if mv == "d7d6" {
+                let opening = next_json().await;
+                assert_eq!(opening["type"], "opening");
+                assert_eq!(opening["eco"], "C60");
+                assert_eq!(opening["name"], "Ruy Lopez");
+            }
+        }
+        // Only announced once: the next message is the next state.
+        sink.send(Message::Text(json!({ "type": "move", "game_id": id, "uci": "g8f6" }).to_string())).await.unwrap();
+        assert_eq!(next_json().await["type"], "game_state");
+
+        handle.stop(false).await;
+    }
+
+    // Standard chess, except that pawns only promote to queens and a king
+    // reaching the eighth rank wins. Only for testing the Variant trait.
+    struct RacingKings;
+
+    impl Variant for RacingKings {
+        fn name(&self) -> &'static str {
+            "test_racing"
+        }
+
+        fn start_position(&self) -> Board {
+            Board::from_fen("4k3/8/8/8/8/8/1p6/4K3 w - - 0 1").unwrap()
+        }
+
+        fn promotion_pieces(&self) -> &'static [PieceKind] {
+            &[PieceKind::Queen]
+        }
+
+        fn game_end(&self, game: &Game) -> Option<(GameResult, Termination)> {
+            let on_last_rank = game.board().find_king(PieceColor::White).is_some_and(|king| king.rank() == 7);
+            if on_last_rank {
+                return Some((GameResult::WhiteWins, Termination::VariantEnd));
+            }
+            standard_game_end(game)
+        }
+    }
+
+    #[test]
+    fn test_variant_rules() {
+        let mut game = Game::with_variant(Arc::new(RacingKings));
+        assert_eq!(game.variant().name(), "test_racing");
+        assert_eq!(game.current_fen(), "4k3/8/8/8/8/8/1p6/4K3 w - - 0 1");
+
+        play(&mut game, &["e1d2"]);
+        // Underpromotion isn't part of this variant.
+        assert_eq!(game.legal_moves_from(square("b2")).iter().map(Move::to_uci).collect::<Vec<_>>(), ["b2b1q"]);
+        assert_eq!(game.explain_illegal(&uci("b2b1n")), Some(MoveError::IllegalMove(uci("b2b1n"))));
+        assert!(game.make_move(uci("b2b1n")).is_err());
+        play(&mut game, &["e8f8"]);
+
+        game.reset();
+        assert_eq!(game.current_fen(), "4k3/8/8/8/8/8/1p6/4K3 w - - 0 1");
+
+        let mut game = Game::from_fen("8/8/8/8/8/3K4/P7/7k w - - 0 1").unwrap();
+        assert_eq!(game.variant().name(), "standard");
+        let mut racing = Game::from_board_with_variant(game.board().clone(), Arc::new(RacingKings));
+        for mv in ["d3d4", "h1g1", "d4d5", "g1h1", "d5d6", "h1g1", "d6d7", "g1h1", "d7d8"] {
+            racing.make_move(uci(mv)).unwrap();
+            game.make_move(uci(mv)).unwrap();
+        }
+        assert_eq!(racing.status(), GameStatus::Won(PieceColor::White, Termination::VariantEnd));
+        assert_eq!(game.status(), GameStatus::Ongoing);
+
+        // Saved games remember their variant, and only known ones load.
+        let saved = serde_json::to_value(&game).unwrap();
+        assert_eq!(saved["variant"], "standard");
+        assert!(serde_json::from_value::<Game>(saved).is_ok());
+        let saved = serde_json::to_value(&racing).unwrap();
+        assert!(serde_json::from_value::<Game>(saved).is_err());
+        assert!(variant_by_name("standard").is_some());
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let mut game = Game::from_fen_with_variant("4k3/p7/8/8/8/4K3/8/8 w - - 0 1", Arc::new(KingOfTheHill)).unwrap();
+        play(&mut game, &["e3d4"]);
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::VariantEnd));
+
+        // Under the standard rules the king is just in the middle.
+        let mut game = Game::from_fen("4k3/p7/8/8/8/4K3/8/8 w - - 0 1").unwrap();
+        play(&mut game, &["e3d4"]);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn test_three_check() {
+        // Black has already given two checks and needs one more.
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+1 0 1";
+        let mut game = Game::from_fen_with_variant(fen, Arc::new(ThreeCheck::default())).unwrap();
+        assert_eq!(game.variant_fen(), fen);
+        assert_eq!(game.current_fen(), START_FEN);
+        play(&mut game, &["d2d4", "e7e6", "g1f3"]);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+
+        // A saved game remembers the checks given before it started.
+        let mut loaded: Game = serde_json::from_value(serde_json::to_value(&game).unwrap()).unwrap();
+        assert_eq!(loaded.variant_fen(), game.variant_fen());
+
+        for game in [&mut game, &mut loaded] {
+            play(game, &["f8b4"]);
+            assert_eq!(game.status(), GameStatus::Won(PieceColor::Black, Termination::VariantEnd));
+            assert!(game.variant_fen().contains(" 3+0 "));
+        }
+
+        // Counted from the moves in a game from the usual start.
+        let mut game = Game::with_variant(Arc::new(ThreeCheck::default()));
+        // Bxf7+, Qh5+ and Qf3+.
+        play(&mut game, &["e2e4", "e7e5", "f1c4", "b8c6", "c4f7", "e8f7", "d1h5", "g7g6"]);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        play(&mut game, &["h5f3"]);
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::VariantEnd));
+        assert!(Game::from_fen_with_variant("8/8/8/8/8/8/8/k6K w - - 4+0 0 1", Arc::new(ThreeCheck::default())).is_err());
+    }
+
+    #[test]
+    fn test_atomic_explosions() {
+        // Kings may stand next to each other.
+        let mut game = Game::from_fen_with_variant("8/8/8/8/4k3/8/8/4K3 w - - 0 1", Arc::new(Atomic)).unwrap();
+        assert_eq!(game.explain_illegal(&uci("e1e2")), None);
+        assert!(game.make_move(uci("e1e2")).is_ok());
+
+        let mut game = Game::with_variant(Arc::new(Atomic));
+        play(&mut game, &["g1f3", "a7a6", "f3g5", "a6a5"]);
+        let before = game.current_fen();
+        // Nxf7 blows up the knight, the pawn and everything around f7 but
+        // the pawns, the black king included.
+        play(&mut game, &["g5f7"]);
+        let board = game.board();
+        for gone in ["g5", "f7", "e8", "f8", "g8"] {
+            assert_eq!(board.piece_at(square(gone)), None, "{}", gone);
+        }
+        assert!(board.piece_at(square("e7")).is_some() && board.piece_at(square("g7")).is_some());
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::VariantEnd));
+
+        // Undo brings the exploded pieces back.
+        game.undo();
+        assert_eq!(game.current_fen(), before);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+
+        // Kings never capture.
+        let game = Game::from_fen_with_variant("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", Arc::new(Atomic)).unwrap();
+        let king_moves: Vec<String> = game.legal_moves_from(square("e1")).iter().map(Move::to_uci).collect();
+        assert!(!king_moves.contains(&"e1d2".to_string()));
+        assert!(king_moves.contains(&"e1f2".to_string()));
+
+        // Touching kings can't be checked.
+        let fen = "8/8/8/8/8/8/3kQ3/3K4 b - - 0 1";
+        assert!(!Game::from_fen_with_variant(fen, Arc::new(Atomic)).unwrap().is_in_check());
+        assert!(Game::from_fen(fen).unwrap().is_in_check());
+    }
+
+    #[actix_web::test]
+    async fn test_create_variant_game() {
+        let store = web::Data::new(GameStore::new());
+        let app = init_service(App::new().app_data(store.clone()).configure(routes::config)).await;
+
+        let req = TestRequest::post().uri("/games").set_json(json!({ "variant": "kingofthehill" })).to_request();
+        let state: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(state.variant, "kingofthehill");
+        assert_eq!(state.variant_fen, None);
+        assert_eq!(store.with_game(state.id, |game| game.variant().name()), Some("kingofthehill"));
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 2+3 0 1";
+        let req = TestRequest::post().uri("/games").set_json(json!({ "variant": "threecheck", "fen": fen })).to_request();
+        let state: GameState = call_and_read_body_json(&app, req).await;
+        assert_eq!(state.fen, START_FEN);
+        assert_eq!(state.variant_fen.as_deref(), Some(fen));
+
+        // A position with no kings at all is fine in Antichess.
+        let fen = "8/8/8/8/8/8/1p6/R7 b - - 0 1";
+        let req = TestRequest::post().uri("/games").set_json(json!({ "variant": "antichess", "fen": fen })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 201);
+        let req = TestRequest::post().uri("/games").set_json(json!({ "fen": fen })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+
+        let req = TestRequest::post().uri("/games").set_json(json!({ "variant": "crazyhouse" })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+
+        // The bot only plays standard chess.
+        for variant in ["threecheck", "atomic"] {
+            let req = TestRequest::post().uri("/games?bot=easy").set_json(json!({ "variant": variant })).to_request();
+            assert_eq!(call_service(&app, req).await.status(), 400, "{}", variant);
+        }
+        let req = TestRequest::post().uri("/games?bot=easy").set_json(json!({ "variant": "standard" })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 201);
+        // In Antichess it would stall at the first forced capture, with
+        // Black to move straight away here; Horde isn't playable for it
+        // either.
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - e3 0 1";
+        let req = TestRequest::post().uri("/games?bot=easy").set_json(json!({ "variant": "antichess", "fen": fen })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+        let req = TestRequest::post().uri("/games?bot=hard").set_json(json!({ "variant": "horde" })).to_request();
+        assert_eq!(call_service(&app, req).await.status(), 400);
+        // None of the refused games were created.
+        assert_eq!(store.metrics().active_games, 4);
+    }
+
+    #[test]
+    fn test_antichess() {
+        let mut game = Game::with_variant(Arc::new(Antichess));
+        assert_eq!(game.current_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+        play(&mut game, &["e2e3", "b7b5"]);
+        // The capture is compulsory.
+        assert_eq!(game.legal_moves().iter().map(Move::to_uci).collect::<Vec<_>>(), ["f1b5"]);
+        assert_eq!(game.explain_illegal(&uci("g1f3")), Some(MoveError::IllegalMove(uci("g1f3"))));
+        assert!(game.make_move(uci("g1f3")).is_err());
+
+        // There is no check, so the king may walk onto an attacked square.
+        let mut game = Game::from_fen_with_variant("4k3/8/8/8/8/8/r7/4K3 w - - 0 1", Arc::new(Antichess)).unwrap();
+        assert_eq!(game.explain_illegal(&uci("e1e2")), None);
+        assert!(game.make_move(uci("e1e2")).is_ok());
+
+        // A pawn may promote to a king, and losing every piece wins.
+        let mut game = Game::from_fen_with_variant("8/8/8/8/8/8/1p6/R7 b - - 0 1", Arc::new(Antichess)).unwrap();
+        assert!(game.legal_moves().iter().any(|mv| mv.to_uci() == "b2a1k"));
+        play(&mut game, &["b2a1k"]);
+        assert_eq!(game.board().piece_at(square("a1")), Some(Piece::new(PieceKind::King, PieceColor::Black)));
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::White, Termination::VariantEnd));
+
+        // There is no check: the king can stay where it is attacked.
+        let game = Game::from_fen_with_variant("4k3/8/8/8/8/8/8/4R2K b - - 0 1", Arc::new(Antichess)).unwrap();
+        assert!(!game.is_in_check());
+        assert!(game.legal_moves().iter().any(|mv| mv.to_uci() == "e8e7"));
+    }
+
+    #[test]
+    fn test_horde() {
+        let game = Game::with_variant(Arc::new(Horde));
+        let pawns = game.board().piece_list().iter().filter(|(_, piece)| piece.color() == PieceColor::White).count();
+        assert_eq!(pawns, 36);
+        assert_eq!(game.board().find_king(PieceColor::White), None);
+
+        // Pawns on the first rank can advance two squares.
+        let mut game = Game::from_fen_with_variant("4k3/8/8/8/8/8/8/P7 w - - 0 1", Arc::new(Horde)).unwrap();
+        play(&mut game, &["a1a3"]);
+        assert_eq!(game.board().en_passant(), None);
+
+        // Black wins by taking White's last piece.
+        let board = Board::from_fen("4k3/8/8/8/8/8/1q6/P7 b - - 0 1").unwrap();
+        assert!(Horde.validate(&board).is_ok());
+        assert!(board.validate().is_err());
+        let mut game = Game::from_board_with_variant(board, Arc::new(Horde));
+        play(&mut game, &["b2a1"]);
+        assert_eq!(game.status(), GameStatus::Won(PieceColor::Black, Termination::VariantEnd));
+    }
 }